@@ -30,6 +30,9 @@ export default interface SIStorageInfo {
 	/** Does storage support limited API. */
 	limitedApi?: boolean;
 
+	/** Does storage provide package details (content statistics, rounds) despite the limited API. */
+	packageDetailsSupported?: boolean;
+
 	/** Message to show when there are no filtered packages. */
 	emptyMessage?: string;
 }
\ No newline at end of file
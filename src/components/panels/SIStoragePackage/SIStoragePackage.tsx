@@ -36,6 +36,14 @@ const getContentName = (contentKey: string) => {
 	}
 };
 
+const formatDuration = (seconds: number) => {
+	const totalSeconds = Math.round(seconds);
+	const minutes = Math.floor(totalSeconds / 60);
+	const restSeconds = totalSeconds % 60;
+
+	return `${minutes}:${restSeconds.toString().padStart(2, '0')}`;
+};
+
 const SIStoragePackage: React.FC<SIStoragePackageProps> = (props: SIStoragePackageProps) => {
 	const [isExpanded, setIsExpanded] = React.useState(false);
 
@@ -66,7 +74,10 @@ const SIStoragePackage: React.FC<SIStoragePackageProps> = (props: SIStoragePacka
 		logo = logoUri.startsWith('http') ? logoUri : props.storage.uri + logoUri;
 	}
 	const content = contentUri ?? directContentUri;
-	const hasDetails = !props.storage.limitedApi && (questionCount || contentTypeStatistic || rounds);
+	const mediaDuration: number | undefined = (props.package as any).mediaDuration;
+	const detailsSupported = !props.storage.limitedApi || props.storage.packageDetailsSupported;
+	const hasContentTypes = contentTypeStatistic && Object.keys(contentTypeStatistic).length > 0;
+	const hasDetails = detailsSupported && (questionCount || hasContentTypes || (rounds && rounds.length > 0) || mediaDuration);
 
 	// Make the whole card clickable except the details button
 	const handleCardClick = (e: React.MouseEvent) => {
@@ -181,6 +192,11 @@ const SIStoragePackage: React.FC<SIStoragePackageProps> = (props: SIStoragePacka
 						</span>
 					</div> : null}
 
+					{mediaDuration ? <div className='detailsRow'>
+						<span className='metaLabel'>{localization.duration}:</span>
+						<span>{formatDuration(mediaDuration)}</span>
+					</div> : null}
+
 					{rounds && rounds.length > 0 ? <div className='roundsSection'>
 						<span className='metaLabel'>{localization.roundsAndThemes}:</span>
 						<ul className='roundsList'>
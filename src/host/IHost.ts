@@ -85,12 +85,14 @@ export default interface IHost {
 	 * @param packageName Name for the package
 	 * @param contentServiceUri URI of the content service
 	 * @param callbacks Upload progress callbacks
+	 * @param mirrorUris Content service mirrors used in order when the primary service is unavailable
 	 * @returns Package URI if successful, null if not supported or failed
 	 */
 	uploadPackageToContentService?(
 		id: string,
 		packageName: string,
 		contentServiceUri: string,
-		callbacks: UploadCallbacks
+		callbacks: UploadCallbacks,
+		mirrorUris?: string[]
 	): Promise<string | null>;
 }
\ No newline at end of file
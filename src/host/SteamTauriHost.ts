@@ -146,6 +146,7 @@ export default class SteamTauriHost extends TauriHost {
 			packageProperties: [],
 			facets: [],
 			limitedApi: true,
+			packageDetailsSupported: true,
 			emptyMessage: localization.noPackagesSteam,
 		};
 
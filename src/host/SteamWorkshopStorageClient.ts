@@ -2,6 +2,34 @@ import SIStorageClient from 'sistorage-client';
 import Package from 'sistorage-client/dist/models/Package';
 import SIStorageClientOptions from 'sistorage-client/dist/SIStorageClientOptions';
 
+/**
+ * Converts a Workshop item returned by the Tauri backend into a storage package
+ */
+export const workshopItemToPackage = (item: any): Package => (<Package>{
+	id: item.id.toString(),
+	name: item.title,
+	difficulty: 0,
+	restrictionIds: [],
+	publisherId: 0,
+	authorIds: [],
+	createDate: new Date(item.created_time * 1000),
+	tagIds: [],
+	languageId: 0,
+	contentUri: `steam://workshop/${item.id}`,
+	logoUri: item.preview_url,
+	size: item.file_size || 0,
+	// Statistics are only available for installed items
+	rounds: item.statistics?.rounds.map((round: any) => ({
+		name: round.name,
+		themeNames: round.theme_names,
+	})) ?? [],
+	questionCount: item.statistics?.question_count ?? 0,
+	contentTypeStatistic: item.statistics?.content_type_statistic ?? {},
+	mediaDuration: item.statistics?.media_duration ?? 0,
+	downloadCount: item.subscriptions ?? 0,
+	rating: item.score,
+});
+
 /**
  * Storage client for Steam Workshop items
  */
@@ -24,25 +52,7 @@ export class SteamWorkshopStorageClient extends SIStorageClient {
 
 				if (response && response.items) {
 					// Convert workshop items to package info objects
-					const packages: Package[] = response.items.map((item: any) => (<Package>{
-						id: item.id.toString(),
-						name: item.title,
-						difficulty: 0,
-						restrictionIds: [],
-						publisherId: 0,
-						authorIds: [],
-						createDate: new Date(item.created_time * 1000),
-						tagIds: [],
-						languageId: 0,
-						contentUri: `steam://workshop/${item.id}`,
-						logoUri: item.preview_url,
-						size: item.file_size || 0,
-						rounds: [],
-						questionCount: 0,
-						contentTypeStatistic: {},
-						downloadCount: 0,
-						rating: item.score,
-					}));
+					const packages: Package[] = response.items.map(workshopItemToPackage);
 
 					return {
 						packages,
@@ -16,6 +16,8 @@ const ACCEPT_LICENSE_KEY = 'ACCEPT_LICENSE';
 
 /** Payload for upload progress events from Rust */
 export interface UploadProgressPayload {
+	upload_id: number;
+	item_id: number;
 	loaded: number;
 	total: number;
 	progress: number;
@@ -23,10 +25,17 @@ export interface UploadProgressPayload {
 
 /** Payload for upload result events from Rust */
 export interface UploadResultPayload {
+	upload_id: number;
+	item_id: number;
 	success: boolean;
+	cancelled: boolean;
 	uri: string | null;
 	error: string | null;
+	/** Machine-readable error kind, e.g. 'rejected' or 'connection_failed' */
+	error_kind: string | null;
 	already_existed: boolean;
+	/** Content service (primary or mirror) the package was uploaded to */
+	service_uri: string | null;
 }
 
 declare global {
@@ -77,6 +86,8 @@ export default class TauriHost implements IHost {
 
 	private currentLogFilePath: string | null = null;
 
+	private activeUploadId: number | null = null;
+
 	constructor(private isLegacy: boolean) {
 		if (this.app && this.app.http) {
 			const originalFetch = globalThis.fetch.bind(globalThis);
@@ -280,15 +291,47 @@ export default class TauriHost implements IHost {
 			console.log(`File size: ${fileInfo.size} bytes, URL: ${fileInfo.file_url}`);
 
 			// Now we use fetch to get the file through our custom protocol
-			// This approach is more memory efficient as the browser handles streaming
-			const response = await fetch(fileInfo.file_url);
+			// Request the package in ranges, so the backend never reads more than a chunk of it at once
+			// Every range must belong to the same version of the package (If-Range), otherwise the load is aborted
+			const parts: Blob[] = [];
+			let received = 0;
+			let total: number | null = null;
+			let etag: string | null = null;
+
+			do {
+				const headers: Record<string, string> = { Range: `bytes=${received}-` };
+
+				if (etag) {
+					headers['If-Range'] = etag;
+				}
 
-			if (!response.ok) {
-				throw new Error(`Failed to fetch file: ${response.status} ${response.statusText}`);
-			}
+				const response = await fetch(fileInfo.file_url, { headers });
+
+				if (!response.ok) {
+					throw new Error(`Failed to fetch file: ${response.status} ${response.statusText}`);
+				}
+
+				const responseEtag = response.headers.get('ETag');
+
+				if (received > 0 && (response.status !== 206 || responseEtag !== etag)) {
+					throw new Error('Package file has changed while it was loaded');
+				}
+
+				const part = await response.blob();
+				const contentRange = response.headers.get('Content-Range');
+				parts.push(part);
+
+				if (response.status !== 206 || !contentRange || part.size === 0) {
+					break;
+				}
+
+				etag = responseEtag;
+				received += part.size;
+				total = parseInt(contentRange.split('/')[1], 10);
+			} while (total !== null && received < total);
 
 			// Get the file as a blob
-			const blob = await response.blob();
+			const blob = new Blob(parts);
 			console.log(`Retrieved blob of size: ${blob.size} bytes`);
 
 			const file = new File([blob], 'package.siq', { type: 'application/x-zip-compressed' });
@@ -382,6 +425,7 @@ export default class TauriHost implements IHost {
 	 * @param packageName Name for the package
 	 * @param contentServiceUri URI of the content service to upload to
 	 * @param callbacks Upload progress callbacks
+	 * @param mirrorUris Content service mirrors used in order when the primary service is unavailable
 	 * @returns Package URI if successful, null otherwise
 	 */
 	async uploadPackageToContentService(
@@ -389,6 +433,7 @@ export default class TauriHost implements IHost {
 		packageName: string,
 		contentServiceUri: string,
 		callbacks: UploadCallbacks,
+		mirrorUris: string[] = [],
 	): Promise<string | null> {
 		const app = this.app;
 		if (!app || !app.core || !app.event) {
@@ -403,10 +448,26 @@ export default class TauriHost implements IHost {
 			return null;
 		}
 
+		if (this.activeUploadId !== null) {
+			// Only one direct upload is shown to the user at a time
+			const previousUploadId = this.activeUploadId;
+			this.activeUploadId = null;
+
+			try {
+				await app.core.invoke('cancel_upload', { uploadId: previousUploadId });
+			} catch (error) {
+				console.warn('Failed to cancel previous upload:', error);
+			}
+		}
+
 		console.log(`Starting direct upload of workshop item ${itemId} to ${contentServiceUri}`);
 
 		let progressUnlisten: (() => void) | null = null;
 		let resultUnlisten: (() => void) | null = null;
+		let uploadId: number | null = null;
+
+		// Results that arrive before the upload id is known
+		const earlyResults = new Map<number, UploadResultPayload>();
 
 		const cleanup = () => {
 			if (progressUnlisten) {
@@ -415,6 +476,9 @@ export default class TauriHost implements IHost {
 			if (resultUnlisten) {
 				resultUnlisten();
 			}
+			if (this.activeUploadId === uploadId) {
+				this.activeUploadId = null;
+			}
 		};
 
 		try {
@@ -423,10 +487,30 @@ export default class TauriHost implements IHost {
 				// We need to set up the listener asynchronously but use the promise synchronously
 				// So we'll use a nested approach
 
+				const handleResult = (payload: UploadResultPayload) => {
+					callbacks.onFinishUpload();
+					cleanup();
+
+					if (payload.success && payload.uri) {
+						console.log(`Package uploaded successfully to ${payload.service_uri}: ${payload.uri}`);
+						resolve(payload.uri);
+					} else if (payload.cancelled) {
+						console.log(`Package upload ${payload.upload_id} was cancelled`);
+						resolve(null);
+					} else {
+						console.error('Package upload failed:', payload.error);
+						resolve(null);
+					}
+				};
+
 				const setupListeners = async () => {
 					progressUnlisten = await app.event?.listen<UploadProgressPayload>(
 						'upload-progress',
 						(event) => {
+							if (event.payload.upload_id !== uploadId) {
+								return;
+							}
+
 							if (event.payload.progress === 0 && event.payload.loaded === 0) {
 								callbacks.onStartUpload();
 							}
@@ -437,26 +521,32 @@ export default class TauriHost implements IHost {
 					resultUnlisten = await app.event?.listen<UploadResultPayload>(
 						'upload-result',
 						(event) => {
-							callbacks.onFinishUpload();
-
-							if (event.payload.success && event.payload.uri) {
-								console.log(`Package uploaded successfully: ${event.payload.uri}`);
-								cleanup();
-								resolve(event.payload.uri);
-							} else {
-								console.error('Package upload failed:', event.payload.error);
-								cleanup();
-								resolve(null);
+							if (uploadId === null) {
+								earlyResults.set(event.payload.upload_id, event.payload);
+								return;
+							}
+
+							if (event.payload.upload_id === uploadId) {
+								handleResult(event.payload);
 							}
 						}
 					) ?? null;
 
 					// Invoke the Rust command to start the upload
-					await app.core?.invoke('upload_workshop_package', {
+					uploadId = await app.core?.invoke('upload_workshop_package', {
 						itemId,
 						contentServiceUri,
+						mirrorUris,
 						packageName,
-					});
+					}) ?? null;
+
+					this.activeUploadId = uploadId;
+
+					const earlyResult = uploadId !== null ? earlyResults.get(uploadId) : undefined;
+
+					if (earlyResult) {
+						handleResult(earlyResult);
+					}
 				};
 
 				setupListeners().catch((error) => {
@@ -0,0 +1,444 @@
+//! Hardened reading of zip archives.
+//!
+//! Packages come from Workshop folders and the content service, so their archives are untrusted.
+//! Every archive is checked before any entry is read:
+//! - The number of entries, their total uncompressed size and compression ratios are limited
+//! - Entry paths must be relative, must not contain `..` and must not be nested too deeply
+//! - Entry names must not contain control or reserved characters
+//!
+//! Entries are read through a reader that never yields more than the declared size,
+//! so an archive that lies about its sizes cannot be used to exhaust memory or disk.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Entries smaller than this are not checked for their compression ratio
+/// (tiny files and empty-ish XML compress extremely well without being dangerous)
+const RATIO_CHECK_MIN_SIZE: u64 = 1024 * 1024; // 1MB
+
+/// Characters that are not allowed in entry names
+const RESERVED_CHARACTERS: &[char] = &['\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Limits applied to an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveLimits {
+    /// Maximum number of entries (including directories)
+    pub max_entries: usize,
+    /// Maximum total uncompressed size of all entries in bytes
+    pub max_total_size: u64,
+    /// Maximum ratio between the uncompressed and compressed size of an entry
+    pub max_compression_ratio: u64,
+    /// Maximum number of path components of an entry
+    pub max_path_depth: usize,
+    /// Maximum length of an entry path in bytes
+    pub max_name_length: usize,
+    /// Maximum size of an entry read into memory at once in bytes
+    pub max_in_memory_entry_size: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_total_size: 4 * 1024 * 1024 * 1024, // 4 GB
+            max_compression_ratio: 100,
+            max_path_depth: 4,
+            max_name_length: 1024,
+            max_in_memory_entry_size: 256 * 1024 * 1024, // 256 MB
+        }
+    }
+}
+
+/// Archive violating the limits or not readable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    IoError(String),
+    /// The file is not a valid zip archive
+    InvalidArchive(String),
+    TooManyEntries { count: usize, limit: usize },
+    TooLarge { size: u64, limit: u64 },
+    /// An entry is compressed suspiciously well (zip bomb)
+    CompressionRatio { name: String, ratio: u64, limit: u64 },
+    PathTooDeep { name: String, depth: usize, limit: usize },
+    /// An entry path is absolute or escapes the archive root
+    UnsafePath { name: String },
+    /// An entry name is empty, too long or contains forbidden characters
+    InvalidFileName { name: String },
+    EntryNotFound { name: String },
+    /// An entry is too large to be read into memory
+    EntryTooLarge { name: String, size: u64, limit: u64 },
+    /// An entry contains more data than its declared size
+    SizeMismatch { name: String },
+}
+
+impl ArchiveError {
+    /// Machine-readable error kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ArchiveError::IoError(_) => "io",
+            ArchiveError::InvalidArchive(_) => "invalid_archive",
+            ArchiveError::TooManyEntries { .. } => "too_many_entries",
+            ArchiveError::TooLarge { .. } => "archive_too_large",
+            ArchiveError::CompressionRatio { .. } => "compression_ratio",
+            ArchiveError::PathTooDeep { .. } => "path_too_deep",
+            ArchiveError::UnsafePath { .. } => "unsafe_path",
+            ArchiveError::InvalidFileName { .. } => "invalid_file_name",
+            ArchiveError::EntryNotFound { .. } => "entry_not_found",
+            ArchiveError::EntryTooLarge { .. } => "entry_too_large",
+            ArchiveError::SizeMismatch { .. } => "size_mismatch",
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::IoError(msg) => write!(f, "IO error: {}", msg),
+            ArchiveError::InvalidArchive(msg) => write!(f, "Invalid archive: {}", msg),
+            ArchiveError::TooManyEntries { count, limit } => {
+                write!(f, "Archive has {} entries, the limit is {}", count, limit)
+            }
+            ArchiveError::TooLarge { size, limit } => {
+                write!(f, "Archive unpacks to {} bytes, the limit is {}", size, limit)
+            }
+            ArchiveError::CompressionRatio { name, ratio, limit } => {
+                write!(f, "Entry {} has compression ratio {}, the limit is {}", name, ratio, limit)
+            }
+            ArchiveError::PathTooDeep { name, depth, limit } => {
+                write!(f, "Entry {} is nested {} levels deep, the limit is {}", name, depth, limit)
+            }
+            ArchiveError::UnsafePath { name } => write!(f, "Entry {} points outside the archive", name),
+            ArchiveError::InvalidFileName { name } => write!(f, "Entry name {:?} is not allowed", name),
+            ArchiveError::EntryNotFound { name } => write!(f, "Entry {} not found", name),
+            ArchiveError::EntryTooLarge { name, size, limit } => {
+                write!(f, "Entry {} has {} bytes, the limit is {}", name, size, limit)
+            }
+            ArchiveError::SizeMismatch { name } => write!(f, "Entry {} is larger than declared", name),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<zip::result::ZipError> for ArchiveError {
+    fn from(error: zip::result::ZipError) -> Self {
+        match error {
+            zip::result::ZipError::Io(e) => ArchiveError::IoError(e.to_string()),
+            e => ArchiveError::InvalidArchive(e.to_string()),
+        }
+    }
+}
+
+/// Archive entry that has passed the checks
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    pub is_dir: bool,
+    index: usize,
+}
+
+/// Zip archive whose entries have been checked against the limits
+pub struct SafeArchive<R> {
+    archive: ZipArchive<R>,
+    entries: Vec<ArchiveEntry>,
+    max_in_memory_entry_size: u64,
+}
+
+impl SafeArchive<BufReader<File>> {
+    /// Open and check an archive file with the default limits
+    pub fn open(path: &Path) -> Result<Self, ArchiveError> {
+        let file = File::open(path).map_err(|e| ArchiveError::IoError(e.to_string()))?;
+        Self::new(BufReader::new(file), &ArchiveLimits::default())
+    }
+}
+
+impl<R: Read + Seek> SafeArchive<R> {
+    /// Check an archive against the limits
+    pub fn new(reader: R, limits: &ArchiveLimits) -> Result<Self, ArchiveError> {
+        let mut archive = ZipArchive::new(reader)?;
+
+        if archive.len() > limits.max_entries {
+            return Err(ArchiveError::TooManyEntries { count: archive.len(), limit: limits.max_entries });
+        }
+
+        let mut entries = Vec::with_capacity(archive.len());
+        let mut total_size = 0u64;
+
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index)?;
+            let name = entry.name().to_string();
+
+            check_entry_name(&name, limits)?;
+
+            let size = entry.size();
+            let compressed_size = entry.compressed_size();
+
+            if size >= RATIO_CHECK_MIN_SIZE {
+                let ratio = size / compressed_size.max(1);
+
+                if ratio > limits.max_compression_ratio {
+                    return Err(ArchiveError::CompressionRatio { name, ratio, limit: limits.max_compression_ratio });
+                }
+            }
+
+            total_size = total_size.saturating_add(size);
+
+            if total_size > limits.max_total_size {
+                return Err(ArchiveError::TooLarge { size: total_size, limit: limits.max_total_size });
+            }
+
+            entries.push(ArchiveEntry {
+                name,
+                size,
+                is_dir: entry.is_dir(),
+                index,
+            });
+        }
+
+        Ok(Self {
+            archive,
+            entries,
+            max_in_memory_entry_size: limits.max_in_memory_entry_size,
+        })
+    }
+
+    /// Get the archive entries
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// Maximum size of an entry read into memory at once
+    pub fn max_in_memory_entry_size(&self) -> u64 {
+        self.max_in_memory_entry_size
+    }
+
+    /// Find an entry by its path
+    pub fn entry(&self, name: &str) -> Option<&ArchiveEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Get a reader for an entry that fails if the entry is larger than declared
+    pub fn entry_reader(&mut self, name: &str) -> Result<impl Read + '_, ArchiveError> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| ArchiveError::EntryNotFound { name: name.to_string() })?;
+
+        let (index, size) = (entry.index, entry.size);
+        let file = self.archive.by_index(index)?;
+
+        Ok(LimitedReader {
+            inner: file,
+            remaining: size,
+            name: name.to_string(),
+        })
+    }
+
+    /// Read a whole entry
+    pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, ArchiveError> {
+        self.read_entry_with_limit(name, self.max_in_memory_entry_size)
+    }
+
+    /// Read a whole entry that must not be larger than the limit
+    /// The limit cannot exceed the in-memory entry size limit of the archive
+    pub fn read_entry_with_limit(&mut self, name: &str, limit: u64) -> Result<Vec<u8>, ArchiveError> {
+        let limit = limit.min(self.max_in_memory_entry_size);
+        let size = self.entry(name).map(|entry| entry.size).unwrap_or(0);
+
+        if size > limit {
+            return Err(ArchiveError::EntryTooLarge { name: name.to_string(), size, limit });
+        }
+
+        let mut data = Vec::with_capacity(size as usize);
+
+        self.entry_reader(name)?
+            .read_to_end(&mut data)
+            .map_err(|e| unwrap_io_error(e, name))?;
+
+        Ok(data)
+    }
+}
+
+/// Check an archive file without reading its entries
+pub fn check_file(path: &Path) -> Result<(), ArchiveError> {
+    SafeArchive::open(path).map(|_| ())
+}
+
+/// Check the path of an entry
+fn check_entry_name(name: &str, limits: &ArchiveLimits) -> Result<(), ArchiveError> {
+    let invalid_name = || ArchiveError::InvalidFileName { name: name.to_string() };
+    let unsafe_path = || ArchiveError::UnsafePath { name: name.to_string() };
+
+    if name.is_empty() || name.len() > limits.max_name_length {
+        return Err(invalid_name());
+    }
+
+    let bytes = name.as_bytes();
+
+    // Absolute paths: `/etc/passwd`, `\\server\share` and `C:\Windows`
+    if name.starts_with('/') || name.starts_with('\\') || (bytes.len() >= 2 && bytes[1] == b':') {
+        return Err(unsafe_path());
+    }
+
+    if name.split(['/', '\\']).any(|component| component == "..") {
+        return Err(unsafe_path());
+    }
+
+    if name.chars().any(|c| c.is_control() || RESERVED_CHARACTERS.contains(&c)) {
+        return Err(invalid_name());
+    }
+
+    let depth = name.split('/').filter(|component| !component.is_empty()).count();
+
+    if depth > limits.max_path_depth {
+        return Err(ArchiveError::PathTooDeep {
+            name: name.to_string(),
+            depth,
+            limit: limits.max_path_depth,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reader that fails instead of reading past the declared entry size
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    name: String,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            // Anything after the declared size means the header lied about it
+            let mut probe = [0u8; 1];
+
+            return match self.inner.read(&mut probe)? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::other(ArchiveError::SizeMismatch { name: self.name.clone() })),
+            };
+        }
+
+        let max = buf.len().min(self.remaining.min(usize::MAX as u64) as usize);
+        let read = self.inner.read(&mut buf[..max])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Recover an archive error passed through an IO error
+fn unwrap_io_error(error: std::io::Error, name: &str) -> ArchiveError {
+    match error.get_ref().and_then(|inner| inner.downcast_ref::<ArchiveError>()) {
+        Some(archive_error) => archive_error.clone(),
+        None => ArchiveError::InvalidArchive(format!("Failed to read {}: {}", name, error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn build_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn open(data: Vec<u8>, limits: &ArchiveLimits) -> Result<SafeArchive<Cursor<Vec<u8>>>, ArchiveError> {
+        SafeArchive::new(Cursor::new(data), limits)
+    }
+
+    #[test]
+    fn test_valid_archive_is_readable() {
+        let data = build_archive(&[("content.xml", b"<package />"), ("Images/a%20b.png", b"png")]);
+        let mut archive = open(data, &ArchiveLimits::default()).unwrap();
+
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.read_entry("content.xml").unwrap(), b"<package />");
+        assert!(matches!(archive.read_entry("missing.xml"), Err(ArchiveError::EntryNotFound { .. })));
+    }
+
+    #[test]
+    fn test_unsafe_paths_are_rejected() {
+        let limits = ArchiveLimits::default();
+
+        for name in ["../evil.txt", "Images/../../evil.txt", "/etc/passwd", "C:/Windows/evil.dll", "Images\\..\\evil"] {
+            assert!(
+                matches!(check_entry_name(name, &limits), Err(ArchiveError::UnsafePath { .. })),
+                "{} should be rejected",
+                name
+            );
+        }
+
+        assert!(matches!(check_entry_name("Images/a\u{0}b.png", &limits), Err(ArchiveError::InvalidFileName { .. })));
+        assert!(matches!(check_entry_name("a/b/c/d/e.png", &limits), Err(ArchiveError::PathTooDeep { depth: 5, .. })));
+        assert!(check_entry_name("Images/..a.png", &limits).is_ok());
+        assert!(check_entry_name("Audio/", &limits).is_ok());
+    }
+
+    #[test]
+    fn test_limits_are_enforced() {
+        let data = build_archive(&[("a.txt", b"aaaa"), ("b.txt", b"bbbb"), ("c.txt", b"cccc")]);
+
+        let limits = ArchiveLimits { max_entries: 2, ..ArchiveLimits::default() };
+        assert!(matches!(open(data.clone(), &limits), Err(ArchiveError::TooManyEntries { count: 3, limit: 2 })));
+
+        let limits = ArchiveLimits { max_total_size: 10, ..ArchiveLimits::default() };
+        assert!(matches!(open(data, &limits), Err(ArchiveError::TooLarge { size: 12, limit: 10 })));
+    }
+
+    #[test]
+    fn test_highly_compressed_entry_is_rejected() {
+        let zeros = vec![0u8; 4 * RATIO_CHECK_MIN_SIZE as usize];
+        let data = build_archive(&[("Images/bomb.png", &zeros)]);
+
+        let result = open(data, &ArchiveLimits::default());
+
+        assert!(matches!(result, Err(ArchiveError::CompressionRatio { ref name, .. }) if name == "Images/bomb.png"));
+    }
+
+    #[test]
+    fn test_entry_larger_than_declared_is_rejected() {
+        let data = build_archive(&[("content.xml", b"<package />")]);
+        let mut archive = open(data, &ArchiveLimits::default()).unwrap();
+
+        // Pretend the header declares fewer bytes than the entry contains
+        archive.entries[0].size = 4;
+
+        assert_eq!(
+            archive.read_entry("content.xml"),
+            Err(ArchiveError::SizeMismatch { name: "content.xml".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_oversized_entry_is_not_read_into_memory() {
+        let data = build_archive(&[("content.xml", b"<package />"), ("Images/a.png", b"png")]);
+        let limits = ArchiveLimits { max_in_memory_entry_size: 1024, ..ArchiveLimits::default() };
+        let mut archive = open(data, &limits).unwrap();
+
+        // A crafted header declaring a huge entry must fail before anything is allocated
+        archive.entries[0].size = u64::MAX;
+
+        assert_eq!(
+            archive.read_entry("content.xml"),
+            Err(ArchiveError::EntryTooLarge { name: "content.xml".to_string(), size: u64::MAX, limit: 1024 })
+        );
+        assert!(matches!(
+            archive.read_entry_with_limit("Images/a.png", 2),
+            Err(ArchiveError::EntryTooLarge { size: 3, limit: 2, .. })
+        ));
+        assert_eq!(archive.read_entry_with_limit("Images/a.png", u64::MAX).unwrap(), b"png");
+    }
+}
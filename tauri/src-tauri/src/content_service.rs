@@ -2,9 +2,17 @@
 //!
 //! This module provides functionality to:
 //! - Check if a package already exists in the content service
-//! - Upload packages with progress reporting
+//! - Upload packages with progress reporting, streaming them from disk
+//! - Resume interrupted uploads when the service supports chunked uploads
+//! - Retry transient failures with exponential backoff
+//! - Download packages and verify them against their SHA-1 hash
+//! - Upload individual media files (images, audio, video, avatars)
+//! - Probe the service for reachability, version and limits
+//! - Fail over to mirror services when the primary one is unavailable
 //! - Calculate SHA-1 hashes for package integrity
 
+use crate::media::{detect_mime_type, MediaKind};
+use crate::protocol::parse_http_date;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
 use sha1::{Digest, Sha1};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
@@ -13,12 +21,72 @@ use reqwest::{
     Client, StatusCode, Body,
 };
 use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
 use std::path::Path;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, atomic::{AtomicU64, AtomicUsize, Ordering}};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::OnceCell;
 use futures::stream::{self, StreamExt};
 
+/// Size of the buffer used for hashing and streaming package files
+const CHUNK_SIZE: usize = 64 * 1024; // 64KB chunks
+
+/// Size of a single request body in chunked upload mode
+const UPLOAD_CHUNK_SIZE: u64 = 4 * 1024 * 1024; // 4MB chunks
+
+/// Time to wait for a mirror to answer when choosing the mirror to use
+const MIRROR_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of downloads started by this process, used to give each download its own temporary file
+static DOWNLOAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Retry policy for content service requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per request (including the first one)
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every next one
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts (longer Retry-After values are shortened to it)
+    pub max_backoff: Duration,
+    /// Random deviation of the delay as a fraction of it (0.0 - 1.0)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Get the delay before the next attempt after the given number of failed attempts
+    fn delay(&self, failed_attempts: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+
+        let exponent = failed_attempts.saturating_sub(1).min(16);
+        let backoff = self.initial_backoff.saturating_mul(1 << exponent).min(self.max_backoff);
+
+        // Map a random value to [-jitter, +jitter]
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        let factor = 1.0 + self.jitter.clamp(0.0, 1.0) * (random * 2.0 - 1.0);
+
+        backoff.mul_f64(factor).min(self.max_backoff)
+    }
+}
+
 /// File key used to identify a package in the content service
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileKey {
@@ -31,26 +99,218 @@ pub struct FileKey {
 pub struct UploadResult {
     pub uri: String,
     pub already_existed: bool,
+    /// Content service (primary or mirror) that stores the file
+    pub service_uri: String,
+}
+
+/// Information about the content service and its limits
+/// The service reports it in camelCase; it is passed on to the webview in snake_case like the other payloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "snake_case", deserialize = "camelCase"))]
+pub struct ContentServiceInfo {
+    /// Version of the service
+    #[serde(default)]
+    pub server_version: Option<String>,
+    /// Version of the API
+    #[serde(default)]
+    pub api_version: Option<String>,
+    /// Maximum package size in bytes
+    #[serde(default)]
+    pub max_package_size: Option<u64>,
+    /// Maximum media file size in bytes
+    #[serde(default)]
+    pub max_media_size: Option<u64>,
+    /// Whether resumable chunked uploads are supported
+    #[serde(default)]
+    pub supports_chunked_upload: bool,
+}
+
+/// Result of a content service probe
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentServiceProbe {
+    /// Whether the service responded at all
+    pub reachable: bool,
+    /// Time to get the response, if there was one
+    pub latency_ms: Option<u64>,
+    /// Service information, if the service provides it
+    pub info: Option<ContentServiceInfo>,
+    pub error: Option<String>,
+    /// Machine-readable error kind (see `ContentServiceError::kind`)
+    pub error_kind: Option<String>,
+}
+
+/// Chunked upload session created by the content service
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadSession {
+    id: String,
+    offset: u64,
+}
+
+/// Chunked upload session creation request
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadSessionRequest<'a> {
+    name: &'a str,
+    hash: &'a str,
+    size: u64,
+}
+
+/// Acknowledgement of the bytes received by the content service in a chunked upload
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadChunkAck {
+    offset: u64,
+    uri: Option<String>,
 }
 
 /// Error types for content service operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ContentServiceError {
     IoError(String),
+    /// The content service could not be reached
+    ConnectionFailed(String),
+    /// The request did not complete in time
+    Timeout(String),
+    /// The connection failed while the request was in progress
     NetworkError(String),
     HashError(String),
-    UploadFailed(String, u16),
+    /// The content service is temporarily unavailable (502, 503, 504)
+    ServiceUnavailable { status: u16, message: String },
+    /// Too many requests (429)
+    RateLimited { retry_after_secs: Option<u64>, message: String },
+    /// The content service rejected the request (4xx)
+    Rejected { status: u16, message: String },
+    /// The content service failed to process the request (other 5xx)
+    ServerError { status: u16, message: String },
+    /// The content service returned a response that could not be understood
+    InvalidResponse(String),
+    /// The file type cannot be stored as the requested kind of media
+    UnsupportedMedia(String),
+    /// The file exceeds the size limit advertised by the content service
+    FileTooLarge { size: u64, limit: u64 },
     PackageNotFound,
 }
 
+impl ContentServiceError {
+    /// Classify a transport error
+    fn from_reqwest(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ContentServiceError::Timeout(error.to_string())
+        } else if error.is_connect() {
+            ContentServiceError::ConnectionFailed(error.to_string())
+        } else {
+            ContentServiceError::NetworkError(error.to_string())
+        }
+    }
+
+    /// Classify an unsuccessful response
+    async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        let retry_after_secs = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, now));
+
+        let message = response.text().await.unwrap_or_default();
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => ContentServiceError::RateLimited { retry_after_secs, message },
+            StatusCode::REQUEST_TIMEOUT => ContentServiceError::Timeout(message),
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+                ContentServiceError::ServiceUnavailable { status: status.as_u16(), message }
+            }
+            status if status.is_client_error() => {
+                ContentServiceError::Rejected { status: status.as_u16(), message }
+            }
+            status => ContentServiceError::ServerError { status: status.as_u16(), message },
+        }
+    }
+
+    /// Whether the request may succeed if it is repeated
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ContentServiceError::ConnectionFailed(_)
+                | ContentServiceError::Timeout(_)
+                | ContentServiceError::ServiceUnavailable { .. }
+                | ContentServiceError::RateLimited { .. }
+        )
+    }
+
+    /// Whether the error means the service itself is unavailable, so a mirror may succeed
+    pub fn is_service_failure(&self) -> bool {
+        matches!(
+            self,
+            ContentServiceError::ConnectionFailed(_)
+                | ContentServiceError::Timeout(_)
+                | ContentServiceError::NetworkError(_)
+                | ContentServiceError::ServiceUnavailable { .. }
+                | ContentServiceError::ServerError { .. }
+        )
+    }
+
+    /// Machine-readable error kind for the frontend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ContentServiceError::IoError(_) => "io",
+            ContentServiceError::ConnectionFailed(_) => "connection_failed",
+            ContentServiceError::Timeout(_) => "timeout",
+            ContentServiceError::NetworkError(_) => "network",
+            ContentServiceError::HashError(_) => "hash",
+            ContentServiceError::ServiceUnavailable { .. } => "service_unavailable",
+            ContentServiceError::RateLimited { .. } => "rate_limited",
+            ContentServiceError::Rejected { .. } => "rejected",
+            ContentServiceError::ServerError { .. } => "server_error",
+            ContentServiceError::InvalidResponse(_) => "invalid_response",
+            ContentServiceError::UnsupportedMedia(_) => "unsupported_media",
+            ContentServiceError::FileTooLarge { .. } => "file_too_large",
+            ContentServiceError::PackageNotFound => "package_not_found",
+        }
+    }
+
+    /// Delay requested by the server before the next attempt
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ContentServiceError::RateLimited { retry_after_secs, .. } => retry_after_secs.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for ContentServiceError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ContentServiceError::IoError(msg) => write!(f, "IO error: {}", msg),
+            ContentServiceError::ConnectionFailed(msg) => write!(f, "Connection failed: {}", msg),
+            ContentServiceError::Timeout(msg) => write!(f, "Request timed out: {}", msg),
             ContentServiceError::NetworkError(msg) => write!(f, "Network error: {}", msg),
             ContentServiceError::HashError(msg) => write!(f, "Hash error: {}", msg),
-            ContentServiceError::UploadFailed(msg, status) => {
-                write!(f, "Upload failed ({}): {}", status, msg)
+            ContentServiceError::ServiceUnavailable { status, message } => {
+                write!(f, "Service unavailable ({}): {}", status, message)
+            }
+            ContentServiceError::RateLimited { retry_after_secs: Some(secs), message } => {
+                write!(f, "Too many requests, retry after {} s: {}", secs, message)
+            }
+            ContentServiceError::RateLimited { retry_after_secs: None, message } => {
+                write!(f, "Too many requests: {}", message)
+            }
+            ContentServiceError::Rejected { status, message } => {
+                write!(f, "Request rejected ({}): {}", status, message)
+            }
+            ContentServiceError::ServerError { status, message } => {
+                write!(f, "Server error ({}): {}", status, message)
+            }
+            ContentServiceError::InvalidResponse(msg) => write!(f, "Invalid response: {}", msg),
+            ContentServiceError::UnsupportedMedia(msg) => write!(f, "Unsupported media: {}", msg),
+            ContentServiceError::FileTooLarge { size, limit } => {
+                write!(f, "File size {} bytes exceeds the limit of {} bytes", size, limit)
             }
             ContentServiceError::PackageNotFound => write!(f, "Package not found"),
         }
@@ -58,7 +318,7 @@ impl std::fmt::Display for ContentServiceError {
 }
 
 /// Escape base64 string for URL usage (replace + with -, / with _, remove = padding)
-fn escape_base64(input: &str) -> String {
+pub(crate) fn escape_base64(input: &str) -> String {
     input.replace('+', "-").replace('/', "_").replace('=', "")
 }
 
@@ -70,18 +330,237 @@ pub fn calculate_sha1_base64(data: &[u8]) -> String {
     BASE64_STANDARD.encode(result)
 }
 
+/// Calculate SHA-1 hash of everything read from a reader and return as base64 string
+pub fn calculate_reader_sha1_base64(mut reader: impl std::io::Read) -> std::io::Result<String> {
+    let mut hasher = Sha1::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(BASE64_STANDARD.encode(hasher.finalize()))
+}
+
 /// SIContentService client
 pub struct SIContentServiceClient {
     client: Client,
-    service_uri: String,
+    /// Primary service followed by its mirrors
+    service_uris: Vec<String>,
+    /// Index of the service currently used
+    active: AtomicUsize,
+    mirror_selection: OnceCell<()>,
+    chunk_size: u64,
+    retry_policy: RetryPolicy,
+    /// Service information by service index
+    server_info: Vec<OnceCell<Option<ContentServiceInfo>>>,
 }
 
 impl SIContentServiceClient {
-    /// Create a new content service client
+    /// Create a new content service client with the default retry policy
     pub fn new(service_uri: &str) -> Self {
+        Self::with_retry_policy(service_uri, RetryPolicy::default())
+    }
+
+    /// Create a new content service client with a custom retry policy
+    pub fn with_retry_policy(service_uri: &str, retry_policy: RetryPolicy) -> Self {
+        Self::with_mirrors(service_uri, &[], retry_policy)
+    }
+
+    /// Create a new content service client that falls back to mirrors, in order, when a service is unavailable
+    pub fn with_mirrors(primary_uri: &str, mirror_uris: &[String], retry_policy: RetryPolicy) -> Self {
+        let mut service_uris = vec![primary_uri.trim_end_matches('/').to_string()];
+
+        for uri in mirror_uris {
+            let uri = uri.trim_end_matches('/').to_string();
+
+            if !uri.is_empty() && !service_uris.contains(&uri) {
+                service_uris.push(uri);
+            }
+        }
+
         Self {
             client: Client::new(),
-            service_uri: service_uri.trim_end_matches('/').to_string(),
+            server_info: service_uris.iter().map(|_| OnceCell::new()).collect(),
+            service_uris,
+            active: AtomicUsize::new(0),
+            mirror_selection: OnceCell::new(),
+            chunk_size: UPLOAD_CHUNK_SIZE,
+            retry_policy,
+        }
+    }
+
+    /// Get the URI of the service currently used
+    pub fn service_uri(&self) -> &str {
+        &self.service_uris[self.active.load(Ordering::SeqCst)]
+    }
+
+    /// Choose the first service, in priority order, that answers a probe
+    /// Stays with the primary service if none of them answers
+    async fn select_mirror(&self) {
+        let probes = self.service_uris.iter().map(|service_uri| async move {
+            let started = Instant::now();
+
+            let result = tokio::time::timeout(MIRROR_PROBE_TIMEOUT, self.fetch_server_info_from(service_uri))
+                .await
+                .unwrap_or_else(|_| Err(ContentServiceError::Timeout("Mirror probe timed out".to_string())));
+
+            (result, started.elapsed())
+        });
+
+        let results = futures::future::join_all(probes).await;
+        let mut selected = None;
+
+        for (index, (result, latency)) in results.into_iter().enumerate() {
+            match result {
+                Ok(info) => {
+                    log::info!("Content service {} answered in {:?}", self.service_uris[index], latency);
+                    let _ = self.server_info[index].set(info);
+                    selected.get_or_insert(index);
+                }
+                Err(e) => log::warn!("Content service {} is unavailable: {}", self.service_uris[index], e),
+            }
+        }
+
+        if let Some(index) = selected {
+            self.active.store(index, Ordering::SeqCst);
+        }
+    }
+
+    /// Run an operation against the current service, switching to the next mirror when the service is unavailable
+    /// Every service is tried at most once
+    async fn with_failover<T, A, Fut>(&self, operation: &str, mut action: A) -> Result<T, ContentServiceError>
+    where
+        A: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ContentServiceError>>,
+    {
+        if self.service_uris.len() > 1 {
+            self.mirror_selection.get_or_init(|| self.select_mirror()).await;
+        }
+
+        let mut remaining = self.service_uris.len();
+
+        loop {
+            let error = match action().await {
+                Err(e) if e.is_service_failure() && remaining > 1 => e,
+                result => return result,
+            };
+
+            remaining -= 1;
+
+            let failed = self.active.load(Ordering::SeqCst);
+            let next = (failed + 1) % self.service_uris.len();
+            self.active.store(next, Ordering::SeqCst);
+
+            log::warn!(
+                "{} failed on {}, switching to {}: {}",
+                operation,
+                self.service_uris[failed],
+                self.service_uris[next],
+                error
+            );
+        }
+    }
+
+    /// Check whether the service is reachable and get its information
+    pub async fn probe(&self) -> ContentServiceProbe {
+        let started = Instant::now();
+        let result = self.fetch_server_info().await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(info) => ContentServiceProbe {
+                reachable: true,
+                latency_ms: Some(latency_ms),
+                info,
+                error: None,
+                error_kind: None,
+            },
+            Err(e) => {
+                let reachable = !matches!(
+                    e,
+                    ContentServiceError::ConnectionFailed(_)
+                        | ContentServiceError::Timeout(_)
+                        | ContentServiceError::NetworkError(_)
+                );
+
+                ContentServiceProbe {
+                    reachable,
+                    latency_ms: reachable.then_some(latency_ms),
+                    info: None,
+                    error: Some(e.to_string()),
+                    error_kind: Some(e.kind().to_string()),
+                }
+            }
+        }
+    }
+
+    /// Get the service information, requesting it once per client
+    /// Returns None if the service does not provide it or cannot be asked
+    pub async fn server_info(&self) -> Option<ContentServiceInfo> {
+        self.server_info[self.active.load(Ordering::SeqCst)]
+            .get_or_init(|| async {
+                self.fetch_server_info().await.unwrap_or_else(|e| {
+                    log::warn!("Failed to get content service info: {}", e);
+                    None
+                })
+            })
+            .await
+            .clone()
+    }
+
+    async fn fetch_server_info(&self) -> Result<Option<ContentServiceInfo>, ContentServiceError> {
+        self.fetch_server_info_from(self.service_uri()).await
+    }
+
+    async fn fetch_server_info_from(&self, service_uri: &str) -> Result<Option<ContentServiceInfo>, ContentServiceError> {
+        let url = format!("{}/api/v1/info", service_uri);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(ContentServiceError::from_reqwest)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let data = response
+                    .bytes()
+                    .await
+                    .map_err(ContentServiceError::from_reqwest)?;
+
+                serde_json::from_slice(&data)
+                    .map(Some)
+                    .map_err(|e| ContentServiceError::InvalidResponse(e.to_string()))
+            }
+            _ => Err(ContentServiceError::from_response(response).await),
+        }
+    }
+
+    /// Run a request, repeating it after transient failures according to the retry policy
+    async fn with_retry<T, A, Fut>(&self, operation: &str, mut action: A) -> Result<T, ContentServiceError>
+    where
+        A: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ContentServiceError>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let error = match action().await {
+                Err(e) if e.is_transient() && attempt < self.retry_policy.max_attempts => e,
+                result => return result,
+            };
+
+            let delay = self.retry_policy.delay(attempt, error.retry_after());
+
+            log::warn!(
+                "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                operation,
+                attempt,
+                self.retry_policy.max_attempts,
+                delay,
+                error
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
     }
 
@@ -91,81 +570,266 @@ impl SIContentServiceClient {
         &self,
         package_key: &FileKey,
     ) -> Result<Option<String>, ContentServiceError> {
-        let escaped_hash = escape_base64(&package_key.hash);
-        let encoded_name = utf8_percent_encode(&package_key.name, NON_ALPHANUMERIC).to_string();
+        self.try_get_content_uri("packages", package_key).await
+    }
 
-        let url = format!(
-            "{}/api/v1/content/packages/{}/{}",
-            self.service_uri, escaped_hash, encoded_name
-        );
+    /// Try to get the URI of an existing media file
+    /// Returns None if the file doesn't exist (404), or the URI if it does
+    pub async fn try_get_media_uri(
+        &self,
+        media_kind: MediaKind,
+        media_key: &FileKey,
+    ) -> Result<Option<String>, ContentServiceError> {
+        self.try_get_content_uri(media_kind.path_segment(), media_key).await
+    }
+
+    async fn try_get_content_uri(
+        &self,
+        content_type: &str,
+        file_key: &FileKey,
+    ) -> Result<Option<String>, ContentServiceError> {
+        let escaped_hash = escape_base64(&file_key.hash);
+        let encoded_name = utf8_percent_encode(&file_key.name, NON_ALPHANUMERIC).to_string();
+
+        self.with_failover("File lookup", || async {
+            let url = format!(
+                "{}/api/v1/content/{}/{}/{}",
+                self.service_uri(), content_type, escaped_hash, encoded_name
+            );
+
+            log::info!("Checking if file exists at: {}", url);
 
-        log::info!("Checking if package exists at: {}", url);
+            self.with_retry("File lookup", || self.lookup_content_uri(&url)).await
+        })
+        .await
+    }
 
+    async fn lookup_content_uri(&self, url: &str) -> Result<Option<String>, ContentServiceError> {
         let response = self
             .client
-            .get(&url)
+            .get(url)
             .send()
             .await
-            .map_err(|e| ContentServiceError::NetworkError(e.to_string()))?;
+            .map_err(ContentServiceError::from_reqwest)?;
 
         match response.status() {
             StatusCode::OK => {
                 let uri = response
                     .text()
                     .await
-                    .map_err(|e| ContentServiceError::NetworkError(e.to_string()))?;
-                log::info!("Package already exists at: {}", uri);
+                    .map_err(ContentServiceError::from_reqwest)?;
+                log::info!("File already exists at: {}", uri);
                 Ok(Some(uri))
             }
             StatusCode::NOT_FOUND => {
-                log::info!("Package does not exist, upload required");
+                log::info!("File does not exist, upload required");
                 Ok(None)
             }
-            status => {
-                let error_text = response.text().await.unwrap_or_default();
-                Err(ContentServiceError::UploadFailed(error_text, status.as_u16()))
-            }
+            _ => Err(ContentServiceError::from_response(response).await),
         }
     }
 
-    /// Upload a package to the content service
+    /// Upload a package file to the content service
+    /// The file is streamed from disk, so it is never fully loaded into memory
+    /// Uses resumable chunked upload if the service supports it, and a single multipart request otherwise
     /// Returns the URI of the uploaded package
     pub async fn upload_package<F>(
         &self,
         package_key: &FileKey,
-        file_data: Vec<u8>,
+        path: &Path,
+        on_progress: F,
+    ) -> Result<String, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let on_progress = Arc::new(on_progress);
+
+        self.with_failover("Package upload", || {
+            self.upload_package_to_service(package_key, path, on_progress.clone())
+        })
+        .await
+    }
+
+    async fn upload_package_to_service<F>(
+        &self,
+        package_key: &FileKey,
+        path: &Path,
+        on_progress: Arc<F>,
+    ) -> Result<String, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let total_size = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?
+            .len();
+
+        let info = self.server_info().await;
+
+        if let Some(limit) = info.as_ref().and_then(|info| info.max_package_size) {
+            if total_size > limit {
+                return Err(ContentServiceError::FileTooLarge { size: total_size, limit });
+            }
+        }
+
+        // Without service information, support for chunked uploads is detected by trying to start a session
+        let try_chunked = info.is_none_or(|info| info.supports_chunked_upload);
+
+        if total_size > 0 && try_chunked {
+            let session = self
+                .with_retry("Upload session creation", || self.start_chunked_upload(package_key, total_size))
+                .await?;
+
+            if let Some(session) = session {
+                log::info!("Using chunked upload session {} starting at offset {}", session.id, session.offset);
+                return self.upload_chunks(&session, path, total_size, on_progress).await;
+            }
+
+            log::info!("Content service does not support chunked uploads, using multipart upload");
+        }
+
+        self.with_retry("Package upload", || {
+            self.upload_multipart(
+                "packages",
+                package_key,
+                "application/x-zip-compressed",
+                path,
+                total_size,
+                on_progress.clone(),
+            )
+        })
+        .await
+    }
+
+    /// Upload a media file to the content service
+    /// The MIME type is detected from the file, which must match the media kind
+    /// Returns the URI of the uploaded file
+    pub async fn upload_media<F>(
+        &self,
+        media_kind: MediaKind,
+        media_key: &FileKey,
+        path: &Path,
         on_progress: F,
     ) -> Result<String, ContentServiceError>
     where
         F: Fn(u64, u64) + Send + Sync + 'static,
     {
-        let url = format!("{}/api/v1/content/packages", self.service_uri);
+        let mime_type = detect_mime_type(path).map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        if !media_kind.accepts(mime_type) {
+            return Err(ContentServiceError::UnsupportedMedia(format!(
+                "{} cannot be uploaded as {:?}",
+                mime_type, media_kind
+            )));
+        }
+
+        let on_progress = Arc::new(on_progress);
+
+        self.with_failover("Media upload", || {
+            self.upload_media_to_service(media_kind, media_key, mime_type, path, on_progress.clone())
+        })
+        .await
+    }
+
+    async fn upload_media_to_service<F>(
+        &self,
+        media_kind: MediaKind,
+        media_key: &FileKey,
+        mime_type: &str,
+        path: &Path,
+        on_progress: Arc<F>,
+    ) -> Result<String, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let total_size = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?
+            .len();
+
+        if let Some(limit) = self.server_info().await.and_then(|info| info.max_media_size) {
+            if total_size > limit {
+                return Err(ContentServiceError::FileTooLarge { size: total_size, limit });
+            }
+        }
+
+        self.with_retry("Media upload", || {
+            self.upload_multipart(
+                media_kind.path_segment(),
+                media_key,
+                mime_type,
+                path,
+                total_size,
+                on_progress.clone(),
+            )
+        })
+        .await
+    }
+
+    /// Upload a media file if it doesn't already exist
+    /// Returns the URI and whether it already existed
+    pub async fn upload_media_if_not_exists<F>(
+        &self,
+        media_kind: MediaKind,
+        media_key: &FileKey,
+        path: &Path,
+        on_progress: F,
+    ) -> Result<UploadResult, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        if let Some(uri) = self.try_get_media_uri(media_kind, media_key).await? {
+            return Ok(UploadResult {
+                uri,
+                already_existed: true,
+                service_uri: self.service_uri().to_string(),
+            });
+        }
+
+        let uri = self.upload_media(media_kind, media_key, path, on_progress).await?;
+        Ok(UploadResult {
+            uri,
+            already_existed: false,
+            service_uri: self.service_uri().to_string(),
+        })
+    }
+
+    /// Upload a file in a single multipart request
+    async fn upload_multipart<F>(
+        &self,
+        content_type: &str,
+        file_key: &FileKey,
+        mime_type: &str,
+        path: &Path,
+        total_size: u64,
+        on_progress: Arc<F>,
+    ) -> Result<String, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let url = format!("{}/api/v1/content/{}", self.service_uri(), content_type);
 
         log::info!(
-            "Uploading package to {} (size: {} bytes)",
+            "Uploading file to {} (size: {} bytes)",
             url,
-            file_data.len()
+            total_size
         );
 
-        let total_size = file_data.len() as u64;
-        let on_progress = Arc::new(on_progress);
+        let file = File::open(path)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
 
         // Report initial progress
         on_progress(0, total_size);
 
         // Create a streaming body with progress tracking
-        // Split data into chunks and report progress as each chunk is consumed
-        const CHUNK_SIZE: usize = 64 * 1024; // 64KB chunks
-        let chunks: Vec<Vec<u8>> = file_data
-            .chunks(CHUNK_SIZE)
-            .map(|chunk| chunk.to_vec())
-            .collect();
-
+        // Read the file chunk by chunk and report progress as each chunk is consumed
         let uploaded = Arc::new(AtomicU64::new(0));
         let uploaded_clone = uploaded.clone();
         let on_progress_clone = on_progress.clone();
 
-        let stream = stream::iter(chunks).map(move |chunk| {
+        let stream = read_file_chunks(file).map(move |chunk| {
+            let chunk = chunk?;
             let chunk_len = chunk.len() as u64;
             let prev = uploaded_clone.fetch_add(chunk_len, Ordering::SeqCst);
             let current = prev + chunk_len;
@@ -177,8 +841,8 @@ impl SIContentServiceClient {
 
         // Build multipart form with streaming body
         let part = Part::stream_with_length(body, total_size)
-            .file_name(package_key.name.clone())
-            .mime_str("application/x-zip-compressed")
+            .file_name(file_key.name.clone())
+            .mime_str(mime_type)
             .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
 
         let form = Form::new().part("file", part);
@@ -188,36 +852,235 @@ impl SIContentServiceClient {
         let response = self
             .client
             .post(&url)
-            .header("Content-MD5", &package_key.hash)
+            .header("Content-MD5", &file_key.hash)
             .multipart(form)
             .send()
             .await
-            .map_err(|e| ContentServiceError::NetworkError(e.to_string()))?;
-
-        // Ensure we report 100% at the end
-        on_progress(total_size, total_size);
+            .map_err(ContentServiceError::from_reqwest)?;
 
         if response.status().is_success() {
             let uri = response
                 .text()
                 .await
-                .map_err(|e| ContentServiceError::NetworkError(e.to_string()))?;
-            log::info!("Package uploaded successfully: {}", uri);
+                .map_err(ContentServiceError::from_reqwest)?;
+
+            // Ensure we report 100% at the end
+            on_progress(total_size, total_size);
+
+            log::info!("File uploaded successfully: {}", uri);
             Ok(uri)
         } else {
-            let status = response.status().as_u16();
-            let error_text = response.text().await.unwrap_or_default();
-            log::error!("Upload failed with status {}: {}", status, error_text);
-            Err(ContentServiceError::UploadFailed(error_text, status))
+            let error = ContentServiceError::from_response(response).await;
+            log::error!("Upload failed: {}", error);
+            Err(error)
         }
     }
 
+    /// Start a chunked upload session
+    /// Returns None if the content service does not support chunked uploads
+    async fn start_chunked_upload(
+        &self,
+        package_key: &FileKey,
+        total_size: u64,
+    ) -> Result<Option<UploadSession>, ContentServiceError> {
+        let url = format!("{}/api/v1/content/packages/uploads", self.service_uri());
+
+        let request = UploadSessionRequest {
+            name: &package_key.name,
+            hash: &package_key.hash,
+            size: total_size,
+        };
+
+        let body = serde_json::to_vec(&request)
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Content-MD5", &package_key.hash)
+            .body(body)
+            .send()
+            .await
+            .map_err(ContentServiceError::from_reqwest)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => Ok(None),
+            status if status.is_success() => {
+                let data = response
+                    .bytes()
+                    .await
+                    .map_err(ContentServiceError::from_reqwest)?;
+
+                serde_json::from_slice(&data)
+                    .map(Some)
+                    .map_err(|e| ContentServiceError::InvalidResponse(e.to_string()))
+            }
+            _ => Err(ContentServiceError::from_response(response).await),
+        }
+    }
+
+    /// Upload the file chunk by chunk, resuming from the last acknowledged offset after network failures
+    async fn upload_chunks<F>(
+        &self,
+        session: &UploadSession,
+        path: &Path,
+        total_size: u64,
+        on_progress: Arc<F>,
+    ) -> Result<String, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let mut file = File::open(path)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        let mut offset = session.offset.min(total_size);
+        let mut failures = 0;
+
+        on_progress(offset, total_size);
+
+        loop {
+            // Nothing is left to send (a resumed session), so the service must report the package URI
+            if offset == total_size {
+                return self.get_uploaded_package_uri(&session.id).await;
+            }
+
+            let chunk_len = self.chunk_size.min(total_size - offset);
+            let mut chunk = vec![0u8; chunk_len as usize];
+
+            file.seek(SeekFrom::Start(offset))
+                .await
+                .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+            file.read_exact(&mut chunk)
+                .await
+                .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+            let error = match self.send_chunk(&session.id, offset, total_size, chunk).await {
+                Ok(ack) => {
+                    failures = 0;
+                    offset = ack.offset.min(total_size);
+                    on_progress(offset, total_size);
+
+                    if let Some(uri) = ack.uri {
+                        log::info!("Package uploaded successfully: {}", uri);
+                        return Ok(uri);
+                    }
+
+                    continue;
+                }
+                // A connection dropped in the middle of a chunk is safe to resume from the acknowledged offset
+                Err(e) if e.is_transient() || matches!(e, ContentServiceError::NetworkError(_)) => e,
+                Err(e) => return Err(e),
+            };
+
+            failures += 1;
+
+            if failures >= self.retry_policy.max_attempts {
+                log::error!("Giving up chunked upload at offset {} of {}: {}", offset, total_size, error);
+                return Err(error);
+            }
+
+            let delay = self.retry_policy.delay(failures, error.retry_after());
+
+            log::warn!(
+                "Chunk upload at offset {} failed (attempt {}/{}), resuming in {:?}: {}",
+                offset,
+                failures,
+                self.retry_policy.max_attempts,
+                delay,
+                error
+            );
+
+            tokio::time::sleep(delay).await;
+
+            // Ask the service how much it has actually received before continuing
+            match self.get_upload_status(&session.id).await {
+                Ok(ack) => {
+                    offset = ack.offset.min(total_size);
+                    on_progress(offset, total_size);
+
+                    if let Some(uri) = ack.uri {
+                        log::info!("Package uploaded successfully: {}", uri);
+                        return Ok(uri);
+                    }
+                }
+                Err(e) if e.is_transient() || matches!(e, ContentServiceError::NetworkError(_)) => {
+                    log::warn!("Failed to query upload offset, retrying chunk: {}", e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send a single chunk of a chunked upload
+    async fn send_chunk(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        total_size: u64,
+        chunk: Vec<u8>,
+    ) -> Result<UploadChunkAck, ContentServiceError> {
+        let url = self.upload_session_url(upload_id);
+        let chunk_end = offset + chunk.len() as u64 - 1;
+
+        let response = self
+            .client
+            .put(&url)
+            .query(&[("offset", offset)])
+            .header("Content-Type", "application/octet-stream")
+            .header("Content-Range", format!("bytes {}-{}/{}", offset, chunk_end, total_size))
+            .body(chunk)
+            .send()
+            .await
+            .map_err(ContentServiceError::from_reqwest)?;
+
+        parse_chunk_ack(response).await
+    }
+
+    /// Get the number of bytes the content service has acknowledged for an upload session
+    /// and the package URI once the whole file has been received
+    async fn get_upload_status(&self, upload_id: &str) -> Result<UploadChunkAck, ContentServiceError> {
+        let url = self.upload_session_url(upload_id);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(ContentServiceError::from_reqwest)?;
+
+        parse_chunk_ack(response).await
+    }
+
+    /// Get the package URI of an upload session that has received the whole file
+    async fn get_uploaded_package_uri(&self, upload_id: &str) -> Result<String, ContentServiceError> {
+        match self.get_upload_status(upload_id).await?.uri {
+            Some(uri) => {
+                log::info!("Package uploaded successfully: {}", uri);
+                Ok(uri)
+            }
+            None => Err(ContentServiceError::InvalidResponse(
+                "Upload completed without a package URI".to_string(),
+            )),
+        }
+    }
+
+    fn upload_session_url(&self, upload_id: &str) -> String {
+        format!(
+            "{}/api/v1/content/packages/uploads/{}",
+            self.service_uri(),
+            utf8_percent_encode(upload_id, NON_ALPHANUMERIC)
+        )
+    }
+
     /// Upload a package if it doesn't already exist
     /// Returns the URI and whether it already existed
     pub async fn upload_package_if_not_exists<F>(
         &self,
         package_key: &FileKey,
-        file_data: Vec<u8>,
+        path: &Path,
         on_progress: F,
     ) -> Result<UploadResult, ContentServiceError>
     where
@@ -228,36 +1091,251 @@ impl SIContentServiceClient {
             return Ok(UploadResult {
                 uri,
                 already_existed: true,
+                service_uri: self.service_uri().to_string(),
             });
         }
 
         // Package doesn't exist, upload it
-        let uri = self.upload_package(package_key, file_data, on_progress).await?;
+        let uri = self.upload_package(package_key, path, on_progress).await?;
         Ok(UploadResult {
             uri,
             already_existed: false,
+            service_uri: self.service_uri().to_string(),
         })
     }
+
+    /// Download a package to a file and verify it against the package hash
+    /// The file is written only if the hash matches; returns the file size
+    pub async fn download_package<F>(
+        &self,
+        package_uri: &str,
+        package_key: &FileKey,
+        destination: &Path,
+        on_progress: F,
+    ) -> Result<u64, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let on_progress = Arc::new(on_progress);
+
+        // Package URIs may be relative to the service, in which case any mirror can serve them
+        if package_uri.starts_with("http://") || package_uri.starts_with("https://") {
+            log::info!("Downloading package from {} to {}", package_uri, destination.display());
+
+            return self
+                .with_retry("Package download", || {
+                    self.download_to_file(package_uri, &package_key.hash, destination, on_progress.clone())
+                })
+                .await;
+        }
+
+        self.with_failover("Package download", || async {
+            let url = format!("{}/{}", self.service_uri(), package_uri.trim_start_matches('/'));
+            log::info!("Downloading package from {} to {}", url, destination.display());
+
+            self.with_retry("Package download", || {
+                self.download_to_file(&url, &package_key.hash, destination, on_progress.clone())
+            })
+            .await
+        })
+        .await
+    }
+
+    async fn download_to_file<F>(
+        &self,
+        url: &str,
+        expected_hash: &str,
+        destination: &Path,
+        on_progress: Arc<F>,
+    ) -> Result<u64, ContentServiceError>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(ContentServiceError::from_reqwest)?;
+
+        if !response.status().is_success() {
+            return Err(ContentServiceError::from_response(response).await);
+        }
+
+        let total_size = response.content_length().unwrap_or(0);
+
+        // Download next to the destination so the final rename is atomic
+        // Every download gets its own file, so concurrent downloads of the same package do not overwrite each other
+        let download_id = DOWNLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = destination.with_extension(format!("{}-{}.part", std::process::id(), download_id));
+
+        let mut file = File::create(&temp_path)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        let mut hasher = Sha1::new();
+        let mut loaded = 0u64;
+        let mut stream = response.bytes_stream();
+
+        on_progress(0, total_size);
+
+        let result: Result<(), ContentServiceError> = async {
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(ContentServiceError::from_reqwest)?;
+
+                hasher.update(&chunk);
+
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+                loaded += chunk.len() as u64;
+                on_progress(loaded, total_size.max(loaded));
+            }
+
+            file.flush()
+                .await
+                .map_err(|e| ContentServiceError::IoError(e.to_string()))
+        }
+        .await;
+
+        drop(file);
+
+        let actual_hash = BASE64_STANDARD.encode(hasher.finalize());
+
+        let result = result.and_then(|_| {
+            if actual_hash == expected_hash {
+                Ok(())
+            } else {
+                Err(ContentServiceError::HashError(format!(
+                    "Downloaded package hash {} does not match expected hash {}",
+                    actual_hash, expected_hash
+                )))
+            }
+        });
+
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+
+        tokio::fs::rename(&temp_path, destination)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        log::info!("Package downloaded successfully: {} bytes", loaded);
+        Ok(loaded)
+    }
 }
 
-/// Read file from path and calculate its MD5 hash
-pub async fn read_file_with_hash(path: &Path) -> Result<(Vec<u8>, String), ContentServiceError> {
-    let mut file = File::open(path)
+/// Probe a content service for reachability, latency, version and limits
+#[tauri::command]
+pub async fn probe_content_service(content_service_uri: String) -> Result<ContentServiceProbe, String> {
+    let probe = SIContentServiceClient::new(&content_service_uri).probe().await;
+
+    log::info!(
+        "Content service {} probed: reachable = {}, latency = {:?} ms",
+        content_service_uri,
+        probe.reachable,
+        probe.latency_ms
+    );
+
+    Ok(probe)
+}
+
+/// Parse a `Retry-After` header given in seconds or as an HTTP date into the number of seconds to wait
+fn parse_retry_after(value: &str, now: u64) -> Option<u64> {
+    let value = value.trim();
+
+    value
+        .parse::<u64>()
+        .ok()
+        .or_else(|| parse_http_date(value).map(|date| date.saturating_sub(now)))
+}
+
+/// Parse a chunked upload acknowledgement
+async fn parse_chunk_ack(response: reqwest::Response) -> Result<UploadChunkAck, ContentServiceError> {
+    if !response.status().is_success() {
+        return Err(ContentServiceError::from_response(response).await);
+    }
+
+    let data = response
+        .bytes()
         .await
-        .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+        .map_err(ContentServiceError::from_reqwest)?;
+
+    serde_json::from_slice(&data).map_err(|e| ContentServiceError::InvalidResponse(e.to_string()))
+}
+
+/// Stream a file in fixed-size chunks
+fn read_file_chunks(file: File) -> impl futures::Stream<Item = std::io::Result<Vec<u8>>> {
+    stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(buffer), Some(file)))
+            }
+            // Stop the stream after the first error
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
 
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)
+/// Calculate the SHA-1 hash of a file without loading it into memory
+/// Returns the file size and the base64-encoded hash
+pub async fn calculate_file_sha1_base64(path: &Path) -> Result<(u64, String), ContentServiceError> {
+    let mut file = File::open(path)
         .await
         .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
 
-    let hash = calculate_sha1_base64(&data);
-    Ok((data, hash))
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut size = 0u64;
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| ContentServiceError::IoError(e.to_string()))?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((size, BASE64_STANDARD.encode(hasher.finalize())))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_content_service::{MockContentService, MockOptions};
+
+    fn write_test_file(name: &str, size: usize) -> (std::path::PathBuf, Vec<u8>) {
+        let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("sigame-{}-{}.bin", name, std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        (path, data)
+    }
+
+    fn test_client(service: &MockContentService) -> SIContentServiceClient {
+        let retry_policy = RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        let mut client = SIContentServiceClient::with_retry_policy(&service.uri(), retry_policy);
+        client.chunk_size = 1024;
+        client
+    }
 
     #[test]
     fn test_escape_base64() {
@@ -274,4 +1352,405 @@ mod tests {
         // SHA-1 of "test data" should produce a consistent base64 hash
         assert!(!hash.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_calculate_file_sha1_base64_matches_in_memory_hash() {
+        // Use more than one chunk to exercise the streaming path
+        let (path, data) = write_test_file("hash-test", CHUNK_SIZE * 2 + 17);
+
+        let (size, hash) = calculate_file_sha1_base64(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(hash, calculate_sha1_base64(&data));
+    }
+
+    #[tokio::test]
+    async fn test_upload_falls_back_to_multipart() {
+        let service = MockContentService::start(MockOptions::default()).await;
+        let (path, data) = write_test_file("multipart-test", 5000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let uri = test_client(&service).upload_package(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(uri, format!("http://mock/packages/{}", escape_base64(&key.hash)));
+        assert_eq!(service.state.lock().unwrap().multipart_uploads, 1);
+        assert_eq!(service.state.lock().unwrap().chunk_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_resumes_after_dropped_connections() {
+        let service = MockContentService::start(MockOptions { supports_chunked: true, dropped_chunks: 2, ..MockOptions::default() }).await;
+        let (path, data) = write_test_file("chunked-test", 4500);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress_clone = progress.clone();
+
+        let uri = test_client(&service)
+            .upload_package(&key, &path, move |loaded, total| progress_clone.lock().unwrap().push((loaded, total)))
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let state = service.state.lock().unwrap();
+        assert_eq!(uri, "http://mock/packages/chunked");
+        assert_eq!(state.received, data);
+        assert_eq!(state.multipart_uploads, 0);
+        // 5 chunks plus the 2 dropped ones
+        assert_eq!(state.chunk_requests, 7);
+        assert_eq!(progress.lock().unwrap().last(), Some(&(4500, 4500)));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_of_fully_uploaded_session_sends_no_chunks() {
+        let options = MockOptions { supports_chunked: true, completed_sessions: true, ..MockOptions::default() };
+        let service = MockContentService::start(options).await;
+        let (path, data) = write_test_file("chunked-complete-test", 3000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let uri = test_client(&service).upload_package(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let state = service.state.lock().unwrap();
+        assert_eq!(uri, "http://mock/packages/chunked");
+        assert_eq!(state.chunk_requests, 0);
+        assert_eq!(state.multipart_uploads, 0);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_gives_up_after_max_attempts() {
+        let max_attempts = RetryPolicy::default().max_attempts as usize;
+        let options = MockOptions { supports_chunked: true, dropped_chunks: max_attempts, ..MockOptions::default() };
+        let service = MockContentService::start(options).await;
+        let (path, data) = write_test_file("chunked-fail-test", 3000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = test_client(&service).upload_package(&key, &path, |_, _| {}).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(service.state.lock().unwrap().chunk_requests, max_attempts);
+    }
+
+    #[tokio::test]
+    async fn test_upload_if_not_exists_skips_known_package() {
+        let service = MockContentService::start(MockOptions::default()).await;
+        let (path, data) = write_test_file("existing-test", 100);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+        let client = test_client(&service);
+
+        let first = client.upload_package_if_not_exists(&key, &path, |_, _| {}).await.unwrap();
+        let second = client.upload_package_if_not_exists(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!first.already_existed);
+        assert!(second.already_existed);
+        assert_eq!(first.uri, second.uri);
+        assert_eq!(service.state.lock().unwrap().multipart_uploads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let options = MockOptions { failures: vec![503, 429], ..MockOptions::default() };
+        let service = MockContentService::start(options).await;
+        let key = FileKey { name: "package.siq".to_string(), hash: "hash".to_string() };
+
+        let uri = test_client(&service).try_get_package_uri(&key).await.unwrap();
+
+        assert_eq!(uri, None);
+        assert_eq!(service.state.lock().unwrap().requests, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_after_max_attempts() {
+        let options = MockOptions { failures: vec![502; 10], ..MockOptions::default() };
+        let service = MockContentService::start(options).await;
+        let key = FileKey { name: "package.siq".to_string(), hash: "hash".to_string() };
+
+        let result = test_client(&service).try_get_package_uri(&key).await;
+
+        assert!(matches!(result, Err(ContentServiceError::ServiceUnavailable { status: 502, .. })));
+        assert_eq!(service.state.lock().unwrap().requests, RetryPolicy::default().max_attempts as usize);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_upload_is_not_retried() {
+        let options = MockOptions { reject_uploads: true, ..MockOptions::default() };
+        let service = MockContentService::start(options).await;
+        let (path, data) = write_test_file("rejected-test", 100);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = test_client(&service).upload_package(&key, &path, |_, _| {}).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, ContentServiceError::Rejected { status: 413, .. }));
+        assert_eq!(error.kind(), "rejected");
+        assert_eq!(service.state.lock().unwrap().multipart_uploads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_failure_is_transient() {
+        // Nothing listens on this port once the listener is dropped
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let retry_policy = RetryPolicy { max_attempts: 2, initial_backoff: Duration::ZERO, ..RetryPolicy::default() };
+        let client = SIContentServiceClient::with_retry_policy(&uri, retry_policy);
+        let key = FileKey { name: "package.siq".to_string(), hash: "hash".to_string() };
+
+        let error = client.try_get_package_uri(&key).await.unwrap_err();
+
+        assert!(matches!(error, ContentServiceError::ConnectionFailed(_)));
+        assert!(error.is_transient());
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.0,
+        };
+
+        assert_eq!(policy.delay(1, None), Duration::from_millis(100));
+        assert_eq!(policy.delay(3, None), Duration::from_millis(400));
+        assert_eq!(policy.delay(10, None), Duration::from_secs(1));
+        assert_eq!(policy.delay(1, Some(Duration::from_millis(700))), Duration::from_millis(700));
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(5))), Duration::from_secs(1));
+
+        let jittered = RetryPolicy { jitter: 0.5, ..policy }.delay(2, None);
+        assert!(jittered >= Duration::from_millis(100) && jittered <= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = 784_111_777;
+
+        assert_eq!(parse_retry_after(" 120 ", now), Some(120));
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now), Some(30));
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now), Some(0));
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        // A date far in the future is not waited for longer than the policy allows
+        let retry_after = parse_retry_after("Fri, 01 Jan 2100 00:00:00 GMT", now).map(Duration::from_secs);
+        assert_eq!(RetryPolicy::default().delay(1, retry_after), RetryPolicy::default().max_backoff);
+    }
+
+    #[tokio::test]
+    async fn test_download_package_verifies_hash() {
+        let service = MockContentService::start(MockOptions::default()).await;
+        let (path, data) = write_test_file("download-source", 3000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+        let client = test_client(&service);
+
+        client.upload_package(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let package_uri = format!("/files/{}", escape_base64(&key.hash));
+        let destination = std::env::temp_dir().join(format!("sigame-download-{}.siq", std::process::id()));
+
+        let size = client.download_package(&package_uri, &key, &destination, |_, _| {}).await.unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(std::fs::read(&destination).unwrap(), data);
+        std::fs::remove_file(&destination).unwrap();
+
+        let wrong_key = FileKey { name: key.name.clone(), hash: calculate_sha1_base64(b"other") };
+        let result = client.download_package(&package_uri, &wrong_key, &destination, |_, _| {}).await;
+        assert!(matches!(result, Err(ContentServiceError::HashError(_))));
+        assert!(!destination.exists());
+        assert!(partial_downloads(&destination).is_empty());
+    }
+
+    /// Temporary files of downloads into the destination
+    fn partial_downloads(destination: &Path) -> Vec<std::path::PathBuf> {
+        let stem = destination.file_stem().unwrap().to_string_lossy().to_string();
+
+        std::fs::read_dir(destination.parent().unwrap())
+            .unwrap()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                name.starts_with(&format!("{}.", stem)) && name.ends_with(".part")
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_downloads_of_same_package_do_not_collide() {
+        let service = MockContentService::start(MockOptions::default()).await;
+        let (path, data) = write_test_file("concurrent-download-source", 200_000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+        let client = test_client(&service);
+
+        client.upload_package(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let package_uri = format!("/files/{}", escape_base64(&key.hash));
+        let destination = std::env::temp_dir().join(format!("sigame-concurrent-download-{}.siq", std::process::id()));
+
+        let (first, second) = tokio::join!(
+            client.download_package(&package_uri, &key, &destination, |_, _| {}),
+            client.download_package(&package_uri, &key, &destination, |_, _| {}),
+        );
+
+        assert_eq!(first.unwrap(), data.len() as u64);
+        assert_eq!(second.unwrap(), data.len() as u64);
+        assert_eq!(std::fs::read(&destination).unwrap(), data);
+        assert!(partial_downloads(&destination).is_empty());
+        std::fs::remove_file(&destination).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_media_if_not_exists() {
+        let service = MockContentService::start(MockOptions::default()).await;
+        let path = std::env::temp_dir().join(format!("sigame-media-upload-{}.png", std::process::id()));
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nimage data").unwrap();
+
+        let (_, hash) = calculate_file_sha1_base64(&path).await.unwrap();
+        let key = FileKey { name: "image.png".to_string(), hash };
+        let client = test_client(&service);
+
+        let first = client.upload_media_if_not_exists(MediaKind::Image, &key, &path, |_, _| {}).await.unwrap();
+        let second = client.upload_media_if_not_exists(MediaKind::Image, &key, &path, |_, _| {}).await.unwrap();
+        let wrong_kind = client.upload_media(MediaKind::Audio, &key, &path, |_, _| {}).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!first.already_existed);
+        assert!(second.already_existed);
+        assert_eq!(first.uri, second.uri);
+        assert!(matches!(wrong_kind, Err(ContentServiceError::UnsupportedMedia(_))));
+
+        let state = service.state.lock().unwrap();
+        assert_eq!(state.multipart_uploads, 1);
+        assert_eq!(state.media_types.get("images").map(String::as_str), Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_service_info() {
+        let info = r#"{"serverVersion":"1.2.0","apiVersion":"1","maxPackageSize":1000}"#;
+        let service = MockContentService::start(MockOptions { info: Some(info), ..MockOptions::default() }).await;
+
+        let probe = test_client(&service).probe().await;
+
+        assert!(probe.reachable);
+        assert!(probe.latency_ms.is_some());
+        let info = probe.info.clone().unwrap();
+        assert_eq!(info.server_version.as_deref(), Some("1.2.0"));
+        assert_eq!(info.max_package_size, Some(1000));
+        assert!(!info.supports_chunked_upload);
+
+        let payload = serde_json::to_value(&probe).unwrap();
+        assert!(payload["latency_ms"].is_u64());
+        assert_eq!(payload["info"]["server_version"], "1.2.0");
+        assert_eq!(payload["info"]["max_package_size"], 1000);
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_unreachable_service() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let probe = SIContentServiceClient::new(&uri).probe().await;
+
+        assert!(!probe.reachable);
+        assert!(probe.latency_ms.is_none());
+        assert_eq!(probe.error_kind.as_deref(), Some("connection_failed"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_refuses_package_over_advertised_limit() {
+        let info = r#"{"maxPackageSize":1000}"#;
+        let service = MockContentService::start(MockOptions { info: Some(info), ..MockOptions::default() }).await;
+        let (path, data) = write_test_file("too-large-test", 1001);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = test_client(&service).upload_package(&key, &path, |_, _| {}).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(ContentServiceError::FileTooLarge { size: 1001, limit: 1000 })));
+        assert_eq!(service.state.lock().unwrap().multipart_uploads, 0);
+    }
+
+    #[tokio::test]
+    async fn test_upload_skips_chunked_session_when_not_advertised() {
+        let options = MockOptions {
+            info: Some(r#"{"supportsChunkedUpload":false}"#),
+            supports_chunked: true,
+            ..MockOptions::default()
+        };
+
+        let service = MockContentService::start(options).await;
+        let (path, data) = write_test_file("not-advertised-test", 3000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        test_client(&service).upload_package(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let state = service.state.lock().unwrap();
+        assert_eq!(state.multipart_uploads, 1);
+        assert_eq!(state.chunk_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_primary_is_skipped() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let primary_uri = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mirror = MockContentService::start(MockOptions::default()).await;
+        let client = SIContentServiceClient::with_mirrors(&primary_uri, &[mirror.uri()], RetryPolicy::default());
+
+        let (path, data) = write_test_file("mirror-selection-test", 2000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = client.upload_package_if_not_exists(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.service_uri, mirror.uri());
+        assert_eq!(mirror.state.lock().unwrap().multipart_uploads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_server_failure_switches_to_mirror() {
+        // The primary answers the probe but fails every request after it
+        let options = MockOptions { failures: vec![0, 500, 500, 500], ..MockOptions::default() };
+        let primary = MockContentService::start(options).await;
+        let mirror = MockContentService::start(MockOptions::default()).await;
+
+        let retry_policy = RetryPolicy { initial_backoff: Duration::ZERO, jitter: 0.0, ..RetryPolicy::default() };
+        let client = SIContentServiceClient::with_mirrors(&primary.uri(), &[mirror.uri()], retry_policy);
+
+        let (path, data) = write_test_file("mirror-failover-test", 2000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = client.upload_package_if_not_exists(&key, &path, |_, _| {}).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.service_uri, mirror.uri());
+        assert_eq!(client.service_uri(), mirror.uri());
+        assert_eq!(primary.state.lock().unwrap().multipart_uploads, 0);
+        assert_eq!(mirror.state.lock().unwrap().multipart_uploads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejection_does_not_switch_mirror() {
+        let primary = MockContentService::start(MockOptions { reject_uploads: true, ..MockOptions::default() }).await;
+        let mirror = MockContentService::start(MockOptions::default()).await;
+        let client = SIContentServiceClient::with_mirrors(&primary.uri(), &[mirror.uri()], RetryPolicy::default());
+
+        let (path, data) = write_test_file("mirror-rejection-test", 2000);
+        let key = FileKey { name: "package.siq".to_string(), hash: calculate_sha1_base64(&data) };
+
+        let result = client.upload_package(&key, &path, |_, _| {}).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(ContentServiceError::Rejected { status: 413, .. })));
+        assert_eq!(mirror.state.lock().unwrap().requests, 1);
+    }
 }
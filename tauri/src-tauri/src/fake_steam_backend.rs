@@ -0,0 +1,407 @@
+//! Deterministic in-memory Steam backend for tests.
+//!
+//! Installed items, slow and failing downloads, query results, auth tickets and publishing
+//! failures are configured up front. A slow download finishes after its download progress
+//! has been polled a given number of times, so tests do not depend on timing. Download listeners
+//! are notified when a slow download finishes or when a test finishes a download explicitly.
+
+use crate::steam_backend::{
+    DownloadListener, InstallInfo, ItemVote, SteamBackend, UpdateProgress, UpdateStage, WorkshopItemDetails,
+    WorkshopItemUpdate, WorkshopPage, WorkshopQuery, WorkshopSortOrder,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of items on a query page
+const PAGE_SIZE: usize = 50;
+
+/// Id of the first created item
+const FIRST_CREATED_ITEM_ID: u64 = 3_000_000_000;
+
+/// Bytes downloaded per progress poll of a slow download
+const DOWNLOAD_CHUNK_SIZE: u64 = 1024;
+
+/// Download that finishes after its progress has been polled a number of times
+struct SlowDownload {
+    polls: u32,
+    remaining_polls: u32,
+    /// Install information of a successful download or the error of a failed one
+    result: Result<InstallInfo, String>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    installed: HashMap<u64, InstallInfo>,
+    slow_downloads: HashMap<u64, SlowDownload>,
+    started_downloads: HashSet<u64>,
+    failing_downloads: HashSet<u64>,
+    subscribed: Vec<WorkshopItemDetails>,
+    /// All Workshop items
+    workshop_items: Vec<WorkshopItemDetails>,
+    query_error: Option<String>,
+    ticket: Option<Result<Vec<u8>, String>>,
+    created_items: u64,
+    submit_error: Option<String>,
+    /// Submitted updates with the package content uploaded with them
+    submitted: Vec<(u64, WorkshopItemUpdate, Option<Vec<u8>>)>,
+    votes: HashMap<u64, bool>,
+    favorites: HashSet<u64>,
+}
+
+/// Fake Steam backend
+#[derive(Default)]
+pub struct FakeSteamBackend {
+    state: Mutex<FakeState>,
+    download_listeners: Mutex<Vec<DownloadListener>>,
+}
+
+impl FakeSteamBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn install_info(folder: &str, timestamp: u32) -> InstallInfo {
+        InstallInfo {
+            folder: folder.to_string(),
+            timestamp,
+        }
+    }
+
+    /// Add an installed item
+    pub fn with_installed_item(self, item_id: u64, folder: &str, timestamp: u32) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .installed
+            .insert(item_id, Self::install_info(folder, timestamp));
+
+        self
+    }
+
+    /// Add an item that is installed after `polls` download progress requests once its download starts
+    pub fn with_slow_download(self, item_id: u64, folder: &str, polls: u32) -> Self {
+        self.with_download_result(item_id, polls, Ok(Self::install_info(folder, 1)))
+    }
+
+    /// Add an item whose download fails after `polls` download progress requests
+    pub fn with_broken_download(self, item_id: u64, polls: u32, error: &str) -> Self {
+        self.with_download_result(item_id, polls, Err(error.to_string()))
+    }
+
+    fn with_download_result(self, item_id: u64, polls: u32, result: Result<InstallInfo, String>) -> Self {
+        self.state.lock().unwrap().slow_downloads.insert(
+            item_id,
+            SlowDownload {
+                polls,
+                remaining_polls: polls,
+                result,
+            },
+        );
+
+        self
+    }
+
+    /// Add an item whose download cannot be started
+    pub fn with_failing_download(self, item_id: u64) -> Self {
+        self.state.lock().unwrap().failing_downloads.insert(item_id);
+        self
+    }
+
+    /// Add a subscribed item
+    pub fn with_subscribed_item(self, details: WorkshopItemDetails) -> Self {
+        self.state.lock().unwrap().subscribed.push(details);
+        self
+    }
+
+    /// Add an item to the Workshop
+    pub fn with_workshop_item(self, details: WorkshopItemDetails) -> Self {
+        self.state.lock().unwrap().workshop_items.push(details);
+        self
+    }
+
+    /// Make Workshop queries fail
+    pub fn with_query_error(self, error: &str) -> Self {
+        self.state.lock().unwrap().query_error = Some(error.to_string());
+        self
+    }
+
+    /// Set the result of auth ticket requests (requests time out without it)
+    pub fn with_ticket(self, ticket: Result<Vec<u8>, String>) -> Self {
+        self.state.lock().unwrap().ticket = Some(ticket);
+        self
+    }
+
+    /// Make item update submissions fail
+    pub fn with_submit_error(self, error: &str) -> Self {
+        self.state.lock().unwrap().submit_error = Some(error.to_string());
+        self
+    }
+
+    /// Get the submitted item updates with the package content uploaded with them
+    pub fn submitted_updates(&self) -> Vec<(u64, WorkshopItemUpdate, Option<Vec<u8>>)> {
+        self.state.lock().unwrap().submitted.clone()
+    }
+
+    /// Finish the download of an item and notify the download listeners
+    /// A successful download installs the item into `folder`
+    pub fn finish_download(&self, item_id: u64, result: Result<&str, &str>) {
+        if let Ok(folder) = result {
+            let mut state = self.state.lock().unwrap();
+            state.slow_downloads.remove(&item_id);
+            state.installed.insert(item_id, Self::install_info(folder, 1));
+        }
+
+        self.notify_download_listeners(item_id, result.map(|_| ()).map_err(str::to_string));
+    }
+
+    fn notify_download_listeners(&self, item_id: u64, result: Result<(), String>) {
+        for listener in self.download_listeners.lock().unwrap().iter() {
+            listener(item_id, result.clone());
+        }
+    }
+
+    /// Get whether the download of an item was started
+    pub fn download_started(&self, item_id: u64) -> bool {
+        self.state.lock().unwrap().started_downloads.contains(&item_id)
+    }
+}
+
+/// Details of a subscribed item for tests
+pub fn item_details(id: u64, title: &str) -> WorkshopItemDetails {
+    WorkshopItemDetails {
+        id,
+        title: title.to_string(),
+        description: String::new(),
+        created_time: 1_700_000_000,
+        updated_time: 1_700_000_000,
+        creator_id: 76_561_197_960_265_728,
+        file_size: 1024,
+        tags: Vec::new(),
+        score: 0.5,
+        preview_url: None,
+        votes_up: 0,
+        votes_down: 0,
+        subscriptions: 0,
+    }
+}
+
+/// Get a page (starting from 1) of items
+fn page_of(items: Vec<WorkshopItemDetails>, page: u32) -> WorkshopPage {
+    let start = (page.max(1) as usize - 1) * PAGE_SIZE;
+
+    WorkshopPage {
+        total: items.len() as u32,
+        items: items.into_iter().skip(start).take(PAGE_SIZE).collect(),
+    }
+}
+
+impl SteamBackend for FakeSteamBackend {
+    fn item_install_info(&self, item_id: u64) -> Option<InstallInfo> {
+        self.state.lock().unwrap().installed.get(&item_id).cloned()
+    }
+
+    fn subscribed_items(&self) -> Vec<u64> {
+        self.state.lock().unwrap().subscribed.iter().map(|item| item.id).collect()
+    }
+
+    fn download_item(&self, item_id: u64, _high_priority: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.failing_downloads.contains(&item_id) {
+            return false;
+        }
+
+        state.started_downloads.insert(item_id);
+        true
+    }
+
+    fn item_download_info(&self, item_id: u64) -> Option<(u64, u64)> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.started_downloads.contains(&item_id) {
+            return None;
+        }
+
+        let download = state.slow_downloads.get_mut(&item_id)?;
+        download.remaining_polls = download.remaining_polls.saturating_sub(1);
+
+        let total = u64::from(download.polls) * DOWNLOAD_CHUNK_SIZE;
+        let downloaded = u64::from(download.polls - download.remaining_polls) * DOWNLOAD_CHUNK_SIZE;
+
+        if download.remaining_polls > 0 {
+            return Some((downloaded, total));
+        }
+
+        let result = state.slow_downloads.remove(&item_id)?.result.map(|info| {
+            state.installed.insert(item_id, info);
+        });
+
+        // Listeners may call back into the backend
+        drop(state);
+        self.notify_download_listeners(item_id, result);
+
+        Some((downloaded, total))
+    }
+
+    fn query_subscribed_items(&self, page: u32) -> Result<WorkshopPage, String> {
+        let state = self.state.lock().unwrap();
+
+        if let Some(error) = &state.query_error {
+            return Err(error.clone());
+        }
+
+        Ok(page_of(state.subscribed.clone(), page))
+    }
+
+    fn query_items(&self, query: &WorkshopQuery) -> Result<WorkshopPage, String> {
+        let state = self.state.lock().unwrap();
+
+        if let Some(error) = &state.query_error {
+            return Err(error.clone());
+        }
+
+        let search_text = query.search_text.as_deref().unwrap_or_default().trim().to_lowercase();
+
+        let mut items: Vec<WorkshopItemDetails> = state
+            .workshop_items
+            .iter()
+            .filter(|item| item.title.to_lowercase().contains(&search_text))
+            .filter(|item| query.required_tags.iter().all(|tag| item.tags.contains(tag)))
+            .filter(|item| !query.excluded_tags.iter().any(|tag| item.tags.contains(tag)))
+            .cloned()
+            .collect();
+
+        // Time windows are not simulated: trending items are ranked by their score
+        match query.sort_order {
+            WorkshopSortOrder::Trend => items.sort_by(|a, b| b.score.total_cmp(&a.score)),
+            WorkshopSortOrder::Votes => items.sort_by_key(|item| std::cmp::Reverse(item.votes_up)),
+            WorkshopSortOrder::Recent => items.sort_by_key(|item| std::cmp::Reverse(item.created_time)),
+            WorkshopSortOrder::Subscriptions => items.sort_by_key(|item| std::cmp::Reverse(item.subscriptions)),
+        }
+
+        Ok(page_of(items, query.page))
+    }
+
+    fn web_api_ticket(&self, identity: &str, _timeout: Duration) -> Result<Vec<u8>, String> {
+        match &self.state.lock().unwrap().ticket {
+            Some(ticket) => ticket.clone(),
+            None => Err(format!("Timed out waiting for Steam Web API ticket for identity '{identity}'")),
+        }
+    }
+
+    fn persona_name(&self) -> String {
+        "Player".to_string()
+    }
+
+    fn large_avatar(&self) -> Option<Vec<u8>> {
+        Some(vec![255; 184 * 184 * 4])
+    }
+
+    fn create_item(&self) -> Result<u64, String> {
+        let mut state = self.state.lock().unwrap();
+        let item_id = FIRST_CREATED_ITEM_ID + state.created_items;
+        state.created_items += 1;
+
+        Ok(item_id)
+    }
+
+    fn submit_item_update(
+        &self,
+        item_id: u64,
+        update: &WorkshopItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> Result<bool, String> {
+        if let Some(error) = &self.state.lock().unwrap().submit_error {
+            return Err(error.clone());
+        }
+
+        // Steam reads the content folder while the update is submitted
+        let content = update
+            .content_folder
+            .as_ref()
+            .map(|folder| std::fs::read(folder.join("package.siq")).map_err(|e| e.to_string()))
+            .transpose()?;
+
+        let total = content.as_ref().map_or(0, |content| content.len() as u64);
+
+        for (stage, processed) in [
+            (UpdateStage::PreparingContent, 0),
+            (UpdateStage::UploadingContent, total / 2),
+            (UpdateStage::UploadingContent, total),
+            (UpdateStage::CommittingChanges, 0),
+        ] {
+            on_progress(UpdateProgress { stage, processed, total });
+        }
+
+        self.state
+            .lock()
+            .unwrap()
+            .submitted
+            .push((item_id, update.clone(), content));
+
+        Ok(false)
+    }
+
+    fn subscribe_item(&self, item_id: u64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+
+        let details = state
+            .workshop_items
+            .iter()
+            .find(|item| item.id == item_id)
+            .cloned()
+            .ok_or_else(|| format!("Workshop item {} not found", item_id))?;
+
+        if !state.subscribed.iter().any(|item| item.id == item_id) {
+            state.subscribed.push(details);
+        }
+
+        Ok(())
+    }
+
+    fn unsubscribe_item(&self, item_id: u64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.subscribed.len();
+        state.subscribed.retain(|item| item.id != item_id);
+
+        if state.subscribed.len() == count {
+            return Err(format!("Not subscribed to Workshop item {}", item_id));
+        }
+
+        Ok(())
+    }
+
+    fn set_item_vote(&self, item_id: u64, vote_up: bool) -> Result<(), String> {
+        self.state.lock().unwrap().votes.insert(item_id, vote_up);
+        Ok(())
+    }
+
+    fn item_vote(&self, item_id: u64) -> Result<ItemVote, String> {
+        Ok(match self.state.lock().unwrap().votes.get(&item_id) {
+            Some(true) => ItemVote::Up,
+            Some(false) => ItemVote::Down,
+            None => ItemVote::NotVoted,
+        })
+    }
+
+    fn set_item_favorite(&self, item_id: u64, favorite: bool) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+
+        if favorite {
+            state.favorites.insert(item_id);
+        } else {
+            state.favorites.remove(&item_id);
+        }
+
+        Ok(())
+    }
+
+    fn add_download_listener(&self, listener: DownloadListener) {
+        self.download_listeners.lock().unwrap().push(listener);
+    }
+
+    fn open_overlay_url(&self, _url: &str) {}
+
+    fn run_callbacks(&self) {}
+}
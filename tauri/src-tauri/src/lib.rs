@@ -1,33 +1,90 @@
 #[cfg(feature = "steam_client")]
+mod archive;
+#[cfg(feature = "steam_client")]
 mod content_service;
+#[cfg(all(test, feature = "steam_client"))]
+mod mock_content_service;
+#[cfg(feature = "steam_client")]
+mod media;
+#[cfg(feature = "steam_client")]
+mod package_cache;
+#[cfg(feature = "steam_client")]
+mod package_duplicates;
+#[cfg(feature = "steam_client")]
+mod package_index;
+#[cfg(feature = "steam_client")]
+mod package_optimizer;
+#[cfg(feature = "steam_client")]
+mod package_statistics;
+#[cfg(feature = "steam_client")]
+mod package_validation;
+#[cfg(feature = "steam_client")]
+mod protocol;
+#[cfg(feature = "steam_client")]
+mod siq;
+#[cfg(feature = "steam_client")]
+mod steam_backend;
+#[cfg(all(test, feature = "steam_client"))]
+mod fake_steam_backend;
+#[cfg(feature = "steam_client")]
+mod thumbnails;
+#[cfg(feature = "steam_client")]
+mod upload_cache;
+#[cfg(feature = "steam_client")]
+mod upload_queue;
+#[cfg(feature = "steam_client")]
+mod workshop_downloads;
+#[cfg(feature = "steam_client")]
+mod workshop_publish;
+#[cfg(feature = "steam_client")]
+mod workshop_subscriptions;
 
 #[cfg(feature = "steam_client")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "steam_client")]
 use std::fmt::Write as _;
-#[cfg(feature = "steam_client")]
-use std::fs::File;
 use std::fs::OpenOptions;
-#[cfg(feature = "steam_client")]
-use std::io::Read;
 use std::io::prelude::*;
 #[cfg(feature = "steam_client")]
 use std::path::Path;
 #[cfg(feature = "steam_client")]
-use steamworks::{AppIDs, AppId, Client, PublishedFileId, TicketForWebApiResponse, UGCType, UserList, UserListOrder};
+use steamworks::Client;
+#[cfg(feature = "steam_client")]
+use steam_backend::{SteamBackend, SteamState, SteamworksBackend, UpdateProgress, UpdateStage};
 #[cfg(feature = "steam_client")]
 use base64::{Engine as _, engine::general_purpose};
 #[cfg(feature = "steam_client")]
 use std::io::Cursor;
 #[cfg(feature = "steam_client")]
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "steam_client")]
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tauri::Manager;
 #[cfg(feature = "steam_client")]
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
 #[cfg(feature = "steam_client")]
 use tauri::Emitter;
+#[cfg(feature = "steam_client")]
+use package_cache::{PackageCache, DEFAULT_MAX_CACHE_SIZE};
+#[cfg(feature = "steam_client")]
+use package_duplicates::FingerprintCache;
+#[cfg(feature = "steam_client")]
+use package_index::{InstalledPackage, PackageIndex};
+#[cfg(feature = "steam_client")]
+use package_statistics::StatisticsCache;
+#[cfg(feature = "steam_client")]
+use thumbnails::ThumbnailCache;
+#[cfg(feature = "steam_client")]
+use workshop_downloads::WorkshopDownloads;
+#[cfg(feature = "steam_client")]
+use workshop_subscriptions::WorkshopSubscriptions;
+#[cfg(feature = "steam_client")]
+use upload_cache::UploadCache;
+#[cfg(feature = "steam_client")]
+use upload_queue::{
+    UploadEntry, UploadFailure, UploadQueue, UploadRequest, UploadState, UploadSuccess,
+    DEFAULT_MAX_CONCURRENT_UPLOADS,
+};
 
 #[cfg(feature = "steam_client")]
 #[derive(Serialize, Deserialize)]
@@ -42,6 +99,13 @@ struct WorkshopItem {
     tags: Vec<String>,
     score: f32,
     preview_url: Option<String>,
+    votes_up: u32,
+    votes_down: u32,
+    /// Number of current subscribers
+    subscriptions: u64,
+    /// Statistics of the package; only available for installed items
+    #[serde(skip_deserializing)]
+    statistics: Option<package_statistics::PackageStatistics>,
 }
 
 #[cfg(feature = "steam_client")]
@@ -53,20 +117,22 @@ struct SteamUserInfo {
 
 #[cfg(feature = "steam_client")]
 #[tauri::command]
-fn get_steam_user_info(client_state: tauri::State<Client>) -> Result<SteamUserInfo, String> {
-    let friends = client_state.friends();
-    let name = friends.name();
-    
-    let steam_id = client_state.user().steam_id();
-    let me = friends.get_friend(steam_id);
-    let avatar_data = me.large_avatar();
+fn get_steam_user_info(steam: tauri::State<SteamState>) -> Result<SteamUserInfo, String> {
+    steam_user_info(steam.as_ref())
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the name and the PNG avatar (base64-encoded) of the user
+fn steam_user_info(steam: &dyn SteamBackend) -> Result<SteamUserInfo, String> {
+    let name = steam.persona_name();
+    let avatar_data = steam.large_avatar();
     
     let mut avatar_base64 = None;
     
     if let Some(data) = avatar_data {
         if let Some(img) = image::RgbaImage::from_raw(184, 184, data) {
             let mut cursor = Cursor::new(Vec::new());
-            if let Ok(_) = img.write_to(&mut cursor, image::ImageFormat::Png) {
+            if img.write_to(&mut cursor, image::ImageFormat::Png).is_ok() {
                 let buffer = cursor.into_inner();
                 avatar_base64 = Some(general_purpose::STANDARD.encode(buffer));
             }
@@ -81,56 +147,23 @@ fn get_steam_user_info(client_state: tauri::State<Client>) -> Result<SteamUserIn
 
 #[cfg(feature = "steam_client")]
 #[tauri::command]
-fn get_steam_auth_ticket(client_state: tauri::State<Client>, identity: String) -> Result<String, String> {
+fn get_steam_auth_ticket(steam: tauri::State<SteamState>, identity: String) -> Result<String, String> {
+    steam_auth_ticket(steam.as_ref(), &identity)
+}
+
+#[cfg(feature = "steam_client")]
+/// Get a hex-encoded Steam Web API ticket for the given identity
+fn steam_auth_ticket(steam: &dyn SteamBackend, identity: &str) -> Result<String, String> {
     let identity = identity.trim();
 
     if identity.is_empty() {
         return Err("Steam auth identity is required".to_string());
     }
 
-    let (tx, rx) = mpsc::channel();
-    let callback_handle = client_state.register_callback(move |response: TicketForWebApiResponse| {
-        let _ = tx.send(response);
-    });
-
-    let user = client_state.user();
-    let ticket_handle = user.authentication_session_ticket_for_webapi(identity);
-    let timeout_at = Instant::now() + Duration::from_secs(10);
-
-    let response = loop {
-        let remaining = timeout_at.saturating_duration_since(Instant::now());
-
-        if remaining.is_zero() {
-            drop(callback_handle);
-            return Err(format!("Timed out waiting for Steam Web API ticket for identity '{identity}'"));
-        }
-
-        match rx.recv_timeout(remaining) {
-            Ok(response) if response.ticket_handle == ticket_handle => break response,
-            Ok(_) => continue,
-            Err(mpsc::RecvTimeoutError::Timeout) => {
-                drop(callback_handle);
-                return Err(format!("Timed out waiting for Steam Web API ticket for identity '{identity}'"));
-            }
-            Err(mpsc::RecvTimeoutError::Disconnected) => {
-                drop(callback_handle);
-                return Err("Steam Web API ticket callback channel disconnected".to_string());
-            }
-        }
-    };
-
-    drop(callback_handle);
-
-    response
-        .result
-        .map_err(|error| format!("Steam Web API ticket request failed: {error:?}"))?;
+    let ticket_bytes = steam.web_api_ticket(identity, Duration::from_secs(10))?;
+    let mut ticket = String::with_capacity(ticket_bytes.len() * 2);
 
-    let ticket_len = usize::try_from(response.ticket_len.max(0))
-        .unwrap_or_default()
-        .min(response.ticket.len());
-    let mut ticket = String::with_capacity(ticket_len * 2);
-
-    for byte in response.ticket.iter().take(ticket_len) {
+    for byte in &ticket_bytes {
         let _ = write!(&mut ticket, "{byte:02x}");
     }
 
@@ -145,71 +178,101 @@ struct WorkshopItemsResponse {
 }
 
 #[cfg(feature = "steam_client")]
+/// Get a page of the Workshop items the user is subscribed to
+/// Installed items come with the statistics of their packages
 #[tauri::command]
-fn get_workshop_subscribed_items(
-    client_state: tauri::State<Client>,
+async fn get_workshop_subscribed_items(
+    app_handle: tauri::AppHandle,
     page: u32,
 ) -> Result<WorkshopItemsResponse, String> {
-    let ugc = client_state.ugc();
-    let user = client_state.user();
-    let steam_id = user.steam_id();
-
-    // Create a user query for subscribed items
-    let query = match ugc.query_user(
-        steam_id.account_id(),
-        UserList::Subscribed,                  // Get subscribed items
-        UGCType::All,                          // All types of UGC
-        UserListOrder::CreationOrderDesc,      // Order by creation date (descending)
-        AppIDs::ConsumerAppId(AppId(3553500)), // App ID for the game
-        page,                                  // Page number for pagination
-    ) {
-        Ok(q) => q,
-        Err(e) => return Err(format!("Failed to create query: {:?}", e)),
-    };
+    // Querying Steam and reading packages block, so keep them off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+        subscribed_items_page(steam.as_ref(), &app_handle.state::<StatisticsCache>(), page)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    // Use a channel to receive the processed result
-    let (tx, rx) = std::sync::mpsc::channel();
-
-    // Fetch the query results with a callback
-    query.fetch(move |result| {
-        let response = match result {
-            Ok(result) => {
-                // Get total count of subscribed items
-                let total = result.total_results();
-
-                // Extract item details
-                let mut items = Vec::new();
-                for i in 0..result.returned_results() {
-                    if let Some(detail) = result.get(i) {
-                        let preview_url = result.preview_url(i).map(|url| url.to_string());
-
-                        items.push(WorkshopItem {
-                            id: detail.published_file_id.0,
-                            title: detail.title.clone(),
-                            description: detail.description.clone(),
-                            created_time: detail.time_created,
-                            updated_time: detail.time_updated,
-                            creator_id: detail.owner.raw(),
-                            file_size: detail.file_size,
-                            tags: detail.tags.clone(),
-                            score: detail.score,
-                            preview_url,
-                        });
-                    }
-                }
+#[cfg(feature = "steam_client")]
+/// Query a page of the Workshop items the user is subscribed to, with statistics of installed packages
+fn subscribed_items_page(
+    steam: &dyn SteamBackend,
+    statistics_cache: &StatisticsCache,
+    page: u32,
+) -> Result<WorkshopItemsResponse, String> {
+    let workshop_page = steam.query_subscribed_items(page)?;
 
-                Ok(WorkshopItemsResponse { items, total })
-            }
-            Err(e) => Err(format!("Steam error: {:?}", e)),
-        };
+    Ok(items_response(steam, statistics_cache, workshop_page))
+}
 
-        // Send the processed response through the channel
-        let _ = tx.send(response);
-    });
+#[cfg(feature = "steam_client")]
+/// Search all Workshop items of the game
+/// Installed items come with the statistics of their packages
+#[tauri::command]
+async fn query_workshop_items(
+    app_handle: tauri::AppHandle,
+    query: steam_backend::WorkshopQuery,
+) -> Result<WorkshopItemsResponse, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+        workshop_items_page(steam.as_ref(), &app_handle.state::<StatisticsCache>(), &query)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    // Receive the result from the channel
-    rx.recv()
-        .map_err(|e| format!("Failed to receive query result: {:?}", e))?
+#[cfg(feature = "steam_client")]
+/// Query a page of all Workshop items, with statistics of installed packages
+fn workshop_items_page(
+    steam: &dyn SteamBackend,
+    statistics_cache: &StatisticsCache,
+    query: &steam_backend::WorkshopQuery,
+) -> Result<WorkshopItemsResponse, String> {
+    let workshop_page = steam.query_items(query)?;
+
+    Ok(items_response(steam, statistics_cache, workshop_page))
+}
+
+#[cfg(feature = "steam_client")]
+/// Convert Workshop query results into a response, adding statistics of installed packages
+fn items_response(
+    steam: &dyn SteamBackend,
+    statistics_cache: &StatisticsCache,
+    workshop_page: steam_backend::WorkshopPage,
+) -> WorkshopItemsResponse {
+    let items = workshop_page
+        .items
+        .into_iter()
+        .map(|detail| {
+            let statistics = steam.item_install_info(detail.id).and_then(|info| {
+                let package_path = Path::new(&info.folder).join("package.siq");
+                statistics_cache.get(detail.id, info.timestamp, &package_path)
+            });
+
+            WorkshopItem {
+                id: detail.id,
+                title: detail.title,
+                description: detail.description,
+                created_time: detail.created_time,
+                updated_time: detail.updated_time,
+                creator_id: detail.creator_id,
+                file_size: detail.file_size,
+                tags: detail.tags,
+                score: detail.score,
+                preview_url: detail.preview_url,
+                votes_up: detail.votes_up,
+                votes_down: detail.votes_down,
+                subscriptions: detail.subscriptions,
+                statistics,
+            }
+        })
+        .collect();
+
+    WorkshopItemsResponse {
+        items,
+        total: workshop_page.total,
+    }
 }
 
 #[cfg(feature = "steam_client")]
@@ -223,238 +286,399 @@ struct SteamWorkshopFileInfo {
 
 #[cfg(feature = "steam_client")]
 // Generate a custom protocol URL for a workshop file
+// The item is downloaded if necessary; see `workshop_package_path`
 #[tauri::command]
-fn get_workshop_file_url(
-    client_state: tauri::State<Client>,
+async fn get_workshop_file_url(
+    app_handle: tauri::AppHandle,
     item_id: u64,
 ) -> Result<SteamWorkshopFileInfo, String> {
     log::info!("Getting workshop file URL for item: {}", item_id);
 
-    let ugc = client_state.ugc();
-    let workshop_id = PublishedFileId(item_id);
-
-    // Check if the item is already downloaded
-    match ugc.item_install_info(workshop_id) {
-        Some(info) => {
-            let mut package_path = info.folder.clone();
-            package_path.push_str("/package.siq");
-
-            // Verify file exists and get metadata
-            log::info!("Checking file at path: {}", package_path);
-
-            match std::fs::metadata(&package_path) {
-                Ok(metadata) => {
-                    let size = metadata.len();
-                    log::info!("File size: {}", size);
+    let package_path = workshop_package_path(&app_handle, item_id).await?;
 
-                    // Create a custom protocol URL
-                    // The protocol will be registered as "sigame-workshop" and we'll include the item_id
-                    let file_url = format!("http://sigame.localhost/file?id={}", item_id);
-
-                    Ok(SteamWorkshopFileInfo {
-                        file_url,
-                        size,
-                        file_id: item_id,
-                    })
-                }
-                Err(e) => {
-                    log::error!("Failed to get file metadata: {}", e);
-                    Err(format!("Failed to get file metadata: {}", e))
-                }
-            }
-        }
-        None => {
-            log::info!("Downloading Workshop item: {}", item_id);
-
-            // Not downloaded yet, try to download
-            if ugc.download_item(workshop_id, true) {
-                // Wait for download to complete
-                let mut retries = 0;
-                while retries < 3000 {
-                    // Wait up to 300 seconds
-                    match ugc.item_install_info(workshop_id) {
-                        Some(info) => {
-                            let mut package_path = info.folder.clone();
-                            package_path.push_str("/package.siq");
-
-                            // Verify the file exists
-                            if std::path::Path::new(&package_path).exists() {
-                                match std::fs::metadata(&package_path) {
-                                    Ok(metadata) => {
-                                        let size = metadata.len();
-
-                                        // Create a custom protocol URL
-                                        let file_url =
-                                            format!("http://sigame.localhost/file?id={}", item_id);
-
-                                        return Ok(SteamWorkshopFileInfo {
-                                            file_url,
-                                            size,
-                                            file_id: item_id,
-                                        });
-                                    }
-                                    Err(e) => {
-                                        return Err(format!("Failed to get file metadata: {}", e))
-                                    }
-                                }
-                            }
-                        }
-                        None => {}
-                    }
-                    retries += 1;
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                }
+    workshop_file_info(item_id, &package_path)
+}
 
-                Err("Timed out waiting for Workshop item to download".to_string())
-            } else {
-                Err("Failed to download Workshop item".to_string())
-            }
-        }
-    }
+#[cfg(feature = "steam_client")]
+/// Get the protocol URL and size of a Workshop package
+fn workshop_file_info(item_id: u64, package_path: &Path) -> Result<SteamWorkshopFileInfo, String> {
+    // Verify file exists and get metadata
+    log::info!("Checking file at path: {}", package_path.display());
+
+    let metadata = std::fs::metadata(package_path).map_err(|e| {
+        log::error!("Failed to get file metadata: {}", e);
+        format!("Failed to get file metadata: {}", e)
+    })?;
+
+    log::info!("File size: {}", metadata.len());
+
+    Ok(SteamWorkshopFileInfo {
+        file_url: format!("http://sigame.localhost/file?id={}", item_id),
+        size: metadata.len(),
+        file_id: item_id,
+    })
 }
 
 #[cfg(feature = "steam_client")]
 /// Payload for upload progress events
 #[derive(Clone, Serialize)]
 struct UploadProgressPayload {
+    upload_id: u64,
+    item_id: u64,
     loaded: u64,
     total: u64,
     progress: f64,
 }
 
+#[cfg(feature = "steam_client")]
+/// Payload for Workshop publishing progress events
+#[derive(Clone, Serialize)]
+struct WorkshopPublishProgressPayload {
+    item_id: u64,
+    stage: UpdateStage,
+    /// Bytes processed in the current stage
+    processed: u64,
+    total: u64,
+    progress: f64,
+}
+
+#[cfg(feature = "steam_client")]
+/// Payload for events about installed Workshop items
+#[derive(Clone, Serialize)]
+struct WorkshopItemInstalledPayload {
+    item_id: u64,
+}
+
 #[cfg(feature = "steam_client")]
 /// Payload for upload result events
 #[derive(Clone, Serialize)]
 struct UploadResultPayload {
+    upload_id: u64,
+    item_id: u64,
     success: bool,
+    cancelled: bool,
     uri: Option<String>,
     error: Option<String>,
+    /// Machine-readable error kind (see `ContentServiceError::kind`)
+    error_kind: Option<String>,
     already_existed: bool,
+    /// Content service (primary or mirror) the package was uploaded to
+    service_uri: Option<String>,
 }
 
 #[cfg(feature = "steam_client")]
-/// Get the file path for a workshop item, downloading if necessary
-/// This function is synchronous to avoid Send issues with Steam client
-fn get_workshop_file_path_sync(
-    client_state: &tauri::State<'_, Client>,
-    item_id: u64,
-) -> Result<String, String> {
-    let ugc = client_state.ugc();
-    let workshop_id = PublishedFileId(item_id);
-
-    // Check if item is already installed
-    if let Some(info) = ugc.item_install_info(workshop_id) {
-        let mut path = info.folder.clone();
-        path.push_str("/package.siq");
-
-        if std::path::Path::new(&path).exists() {
-            return Ok(path);
+impl From<&UploadEntry> for UploadResultPayload {
+    fn from(entry: &UploadEntry) -> Self {
+        Self {
+            upload_id: entry.upload_id,
+            item_id: entry.item_id,
+            success: entry.state == UploadState::Done,
+            cancelled: entry.state == UploadState::Cancelled,
+            uri: entry.uri.clone(),
+            error: entry.error.clone(),
+            error_kind: entry.error_kind.clone(),
+            already_existed: entry.already_existed,
+            service_uri: entry.service_uri.clone(),
         }
     }
+}
 
-    log::info!("Workshop item not installed, attempting to download: {}", item_id);
-
-    // Try to download the item
-    if !ugc.download_item(workshop_id, true) {
-        return Err("Failed to start download of Workshop item".to_string());
-    }
-
-    // Wait for download to complete (synchronously)
-    let mut retries = 0;
-    loop {
-        if retries >= 3000 {
-            // 300 seconds timeout
-            return Err("Timed out waiting for Workshop item to download".to_string());
-        }
+#[cfg(feature = "steam_client")]
+/// Queue of uploads started by `upload_workshop_package`
+type UploadQueueState = Mutex<UploadQueue<tauri::async_runtime::JoinHandle<()>>>;
 
-        if let Some(info) = ugc.item_install_info(workshop_id) {
-            let mut path = info.folder.clone();
-            path.push_str("/package.siq");
+#[cfg(feature = "steam_client")]
+/// Get the package file of a Workshop item, downloading the item if necessary
+/// Download progress is reported through the `workshop-download-progress` event
+/// Waiting can be cancelled with `cancel_workshop_download`
+async fn workshop_package_path(app_handle: &tauri::AppHandle, item_id: u64) -> Result<std::path::PathBuf, String> {
+    let app_handle = app_handle.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+
+        app_handle
+            .state::<WorkshopDownloads>()
+            .wait_for_package(steam.as_ref(), item_id, &mut |progress| {
+                let _ = app_handle.emit("workshop-download-progress", progress);
+            })
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-            if std::path::Path::new(&path).exists() {
-                return Ok(path);
-            }
-        }
+#[cfg(feature = "steam_client")]
+/// Stop waiting for the download of a Workshop item
+/// Commands waiting for the item fail; Steam keeps downloading it in the background
+#[tauri::command]
+fn cancel_workshop_download(downloads: tauri::State<WorkshopDownloads>, item_id: u64) -> bool {
+    downloads.cancel(item_id)
+}
 
-        retries += 1;
-        std::thread::sleep(std::time::Duration::from_millis(100));
-    }
+#[cfg(feature = "steam_client")]
+/// Set the time to wait for Workshop item downloads
+#[tauri::command]
+fn set_workshop_download_timeout(downloads: tauri::State<WorkshopDownloads>, timeout_seconds: u64) {
+    downloads.set_timeout(Duration::from_secs(timeout_seconds));
 }
 
 #[cfg(feature = "steam_client")]
 /// Upload a workshop package directly to the content service
 /// This avoids transferring the file through the webview
+/// The upload is queued and runs in the background; its id is returned immediately
+/// Every state change is reported through the `upload-state` event
+/// Mirrors are used in the given order when the primary content service is unavailable
 #[tauri::command]
-async fn upload_workshop_package(
+fn upload_workshop_package(
     app_handle: tauri::AppHandle,
-    client_state: tauri::State<'_, Client>,
+    uploads: tauri::State<UploadQueueState>,
     item_id: u64,
     content_service_uri: String,
+    mirror_uris: Option<Vec<String>>,
     package_name: String,
+) -> Result<u64, String> {
+    let entry = uploads.lock().map_err(|e| e.to_string())?.enqueue(UploadRequest {
+        item_id,
+        content_service_uri,
+        mirror_uris: mirror_uris.unwrap_or_default(),
+        package_name,
+    });
+
+    log::info!(
+        "Queued upload {} of workshop item {} to content service: {}",
+        entry.upload_id,
+        item_id,
+        entry.content_service_uri
+    );
+
+    let _ = app_handle.emit("upload-state", &entry);
+    start_pending_uploads(&app_handle);
+
+    Ok(entry.upload_id)
+}
+
+#[cfg(feature = "steam_client")]
+/// Cancel a queued or running upload
+/// Returns false if the upload has already finished
+#[tauri::command]
+fn cancel_upload(
+    app_handle: tauri::AppHandle,
+    uploads: tauri::State<UploadQueueState>,
+    upload_id: u64,
+) -> Result<bool, String> {
+    let cancelled = uploads.lock().map_err(|e| e.to_string())?.cancel(upload_id);
+
+    let Some((entry, task)) = cancelled else {
+        return Ok(false);
+    };
+
+    // Dropping the upload future aborts the underlying HTTP request
+    if let Some(task) = task {
+        task.abort();
+    }
+
+    log::info!("Upload {} cancelled", upload_id);
+
+    let _ = app_handle.emit("upload-state", &entry);
+    let _ = app_handle.emit("upload-result", UploadResultPayload::from(&entry));
+    start_pending_uploads(&app_handle);
+
+    Ok(true)
+}
+
+#[cfg(feature = "steam_client")]
+/// Get all queued, running and recently finished uploads
+#[tauri::command]
+fn list_uploads(uploads: tauri::State<UploadQueueState>) -> Result<Vec<UploadEntry>, String> {
+    Ok(uploads.lock().map_err(|e| e.to_string())?.list())
+}
+
+#[cfg(feature = "steam_client")]
+/// Set the number of uploads running at the same time
+#[tauri::command]
+fn set_upload_concurrency(
+    app_handle: tauri::AppHandle,
+    uploads: tauri::State<UploadQueueState>,
+    max_concurrent: usize,
 ) -> Result<(), String> {
-    use content_service::{FileKey, SIContentServiceClient, read_file_with_hash};
+    uploads.lock().map_err(|e| e.to_string())?.set_max_concurrent(max_concurrent);
+    start_pending_uploads(&app_handle);
+    Ok(())
+}
+
+#[cfg(feature = "steam_client")]
+/// Start as many queued uploads as the concurrency limit allows
+fn start_pending_uploads(app_handle: &tauri::AppHandle) {
+    let uploads = app_handle.state::<UploadQueueState>();
+
+    // Keep the lock while spawning so a task cannot finish before it is attached
+    let Ok(mut queue) = uploads.lock() else {
+        return;
+    };
+
+    for (entry, request) in queue.start_next() {
+        let _ = app_handle.emit("upload-state", &entry);
+
+        let task_app_handle = app_handle.clone();
+        let upload_id = entry.upload_id;
+
+        let task = tauri::async_runtime::spawn(async move {
+            let outcome = run_workshop_upload(&task_app_handle, upload_id, request).await;
+            finish_upload(&task_app_handle, upload_id, outcome);
+        });
+
+        if let Some(task) = queue.attach(upload_id, task) {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(feature = "steam_client")]
+/// Record the outcome of an upload task and start the next queued uploads
+fn finish_upload(
+    app_handle: &tauri::AppHandle,
+    upload_id: u64,
+    outcome: Result<UploadSuccess, UploadFailure>,
+) {
+    let uploads = app_handle.state::<UploadQueueState>();
+    let entry = uploads.lock().ok().and_then(|mut queue| queue.finish(upload_id, outcome));
+
+    // A cancelled upload has already reported its result
+    if let Some(entry) = entry {
+        let _ = app_handle.emit("upload-state", &entry);
+        let _ = app_handle.emit("upload-result", UploadResultPayload::from(&entry));
+    }
+
+    start_pending_uploads(app_handle);
+}
+
+#[cfg(feature = "steam_client")]
+/// Upload a workshop package to the content service
+async fn run_workshop_upload(
+    app_handle: &tauri::AppHandle,
+    upload_id: u64,
+    request: UploadRequest,
+) -> Result<UploadSuccess, UploadFailure> {
+    use content_service::{FileKey, SIContentServiceClient, calculate_file_sha1_base64};
+
+    let item_id = request.item_id;
 
     log::info!(
-        "Starting upload of workshop item {} to content service: {}",
+        "Starting upload {} of workshop item {} to content service: {}",
+        upload_id,
         item_id,
-        content_service_uri
+        request.content_service_uri
     );
 
-    // Get the file path synchronously to avoid Send issues with Steam client
-    let package_path = get_workshop_file_path_sync(&client_state, item_id)
-        .map_err(|e| {
-            let _ = app_handle.emit("upload-result", UploadResultPayload {
-                success: false,
-                uri: None,
-                error: Some(e.clone()),
-                already_existed: false,
+    let package_path = workshop_package_path(app_handle, item_id)
+        .await
+        .map_err(|error| UploadFailure { error, error_kind: "workshop".to_string() })?;
+
+    let (modified, file_size) = upload_cache::file_stamp(&package_path).map_err(|e| UploadFailure {
+        error: format!("Failed to read package file: {}", e),
+        error_kind: "io".to_string(),
+    })?;
+
+    let upload_cache = app_handle.state::<Mutex<UploadCache>>();
+    let is_validated = upload_cache
+        .lock()
+        .is_ok_and(|cache| cache.is_validated(item_id, modified, file_size));
+
+    // Broken packages are refused before anything is sent to the content service
+    if !is_validated {
+        let validation_path = package_path.clone();
+        let report = tauri::async_runtime::spawn_blocking(move || package_validation::validate_file(&validation_path))
+            .await
+            .map_err(|e| UploadFailure { error: e.to_string(), error_kind: "io".to_string() })?;
+
+        if report.has_errors() {
+            log::error!("Upload {}: package is invalid: {}", upload_id, report.error_summary());
+
+            return Err(UploadFailure {
+                error: format!("Package is invalid: {}", report.error_summary()),
+                error_kind: "invalid_package".to_string(),
             });
-            e
-        })?;
+        }
 
-    log::info!("Reading package file from: {}", package_path);
+        update_upload_cache(app_handle, |cache| cache.set_validated(item_id, modified, file_size));
+    }
 
-    // Read file and calculate hash
-    let (file_data, hash) = match read_file_with_hash(std::path::Path::new(&package_path)).await {
-        Ok(result) => result,
-        Err(e) => {
-            let error_msg = format!("Failed to read package file: {}", e);
-            let _ = app_handle.emit("upload-result", UploadResultPayload {
-                success: false,
-                uri: None,
-                error: Some(error_msg.clone()),
-                already_existed: false,
-            });
-            return Err(error_msg);
+    let cached_hash = upload_cache
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get_hash(item_id, modified, file_size));
+
+    let hash = match cached_hash {
+        Some(hash) => hash,
+        None => {
+            log::info!("Hashing package file: {}", package_path.display());
+
+            // Calculate hash by streaming the file from disk
+            let (_, hash) = calculate_file_sha1_base64(&package_path)
+                .await
+                .map_err(|e| UploadFailure {
+                    error: format!("Failed to read package file: {}", e),
+                    error_kind: e.kind().to_string(),
+                })?;
+
+            update_upload_cache(app_handle, |cache| cache.set_hash(item_id, modified, file_size, &hash));
+            hash
         }
     };
 
-    let file_size = file_data.len() as u64;
-    log::info!("Package size: {} bytes, MD5 hash: {}", file_size, hash);
+    log::info!("Package size: {} bytes, SHA-1 hash: {}", file_size, hash);
 
     let package_key = FileKey {
-        name: package_name,
+        name: request.package_name,
         hash,
     };
 
+    // The package may have been uploaded to any of the services
+    let cached_uri = upload_cache.lock().ok().and_then(|cache| {
+        std::iter::once(&request.content_service_uri)
+            .chain(&request.mirror_uris)
+            .find_map(|service_uri| Some((cache.get_uri(service_uri, &package_key)?, service_uri.clone())))
+    });
+
+    if let Some((uri, service_uri)) = cached_uri {
+        log::info!("Upload {}: package is known to exist at {}", upload_id, uri);
+
+        return Ok(UploadSuccess {
+            uri,
+            already_existed: true,
+            service_uri,
+        });
+    }
+
     // Create content service client
-    let content_client = SIContentServiceClient::new(&content_service_uri);
+    let content_client = SIContentServiceClient::with_mirrors(
+        &request.content_service_uri,
+        &request.mirror_uris,
+        content_service::RetryPolicy::default(),
+    );
 
     // Clone app_handle for the progress callback
     let app_handle_progress = app_handle.clone();
 
     // Upload the package with progress reporting
     let result = content_client
-        .upload_package_if_not_exists(&package_key, file_data, move |loaded, total| {
+        .upload_package_if_not_exists(&package_key, &package_path, move |loaded, total| {
             let progress = if total > 0 {
                 (loaded as f64) / (total as f64)
             } else {
                 0.0
             };
 
+            if let Ok(mut queue) = app_handle_progress.state::<UploadQueueState>().lock() {
+                queue.update_progress(upload_id, loaded, total);
+            }
+
             let _ = app_handle_progress.emit(
                 "upload-progress",
                 UploadProgressPayload {
+                    upload_id,
+                    item_id,
                     loaded,
                     total,
                     progress,
@@ -466,113 +690,638 @@ async fn upload_workshop_package(
     match result {
         Ok(upload_result) => {
             log::info!(
-                "Upload completed successfully: {} (already existed: {})",
+                "Upload {} completed successfully: {} (already existed: {}, service: {})",
+                upload_id,
                 upload_result.uri,
-                upload_result.already_existed
+                upload_result.already_existed,
+                upload_result.service_uri
             );
 
-            let _ = app_handle.emit("upload-result", UploadResultPayload {
-                success: true,
-                uri: Some(upload_result.uri),
-                error: None,
-                already_existed: upload_result.already_existed,
+            update_upload_cache(app_handle, |cache| {
+                cache.set_uri(&upload_result.service_uri, &package_key, &upload_result.uri)
             });
 
-            Ok(())
+            Ok(UploadSuccess {
+                uri: upload_result.uri,
+                already_existed: upload_result.already_existed,
+                service_uri: upload_result.service_uri,
+            })
         }
         Err(e) => {
-            let error_msg = format!("Upload failed: {}", e);
-            log::error!("{}", error_msg);
-
-            let _ = app_handle.emit("upload-result", UploadResultPayload {
-                success: false,
-                uri: None,
-                error: Some(error_msg.clone()),
-                already_existed: false,
-            });
+            let error = format!("Upload failed: {}", e);
+            log::error!("Upload {}: {}", upload_id, error);
 
-            Err(error_msg)
+            Err(UploadFailure {
+                error,
+                error_kind: e.kind().to_string(),
+            })
         }
     }
 }
 
 #[cfg(feature = "steam_client")]
-// Handle custom protocol for workshop files
-fn handle_workshop_protocol(
-    app: tauri::AppHandle,
-    request: tauri::http::Request<Vec<u8>>,
-) -> tauri::http::Response<Vec<u8>> {
-    // Parse the URI to extract the file ID
-    let uri = request.uri().to_string();
+/// Update the upload cache and persist it
+fn update_upload_cache(app_handle: &tauri::AppHandle, update: impl FnOnce(&mut UploadCache)) {
+    let upload_cache = app_handle.state::<Mutex<UploadCache>>();
 
-    log::info!("Received custom protocol request: {}", uri);
+    let Ok(mut cache) = upload_cache.lock() else {
+        return;
+    };
 
-    let error_response = tauri::http::Response::builder()
-        .status(404)
-        .body(Vec::new())
-        .unwrap();
+    update(&mut cache);
 
-    // Extract file ID from query params
-    if !uri.contains("?id=") {
-        return error_response;
+    if let Err(e) = cache.save() {
+        log::warn!("Failed to save upload cache: {}", e);
     }
+}
 
-    let id_param = uri.split("?id=").nth(1).unwrap_or("");
-    let item_id = match id_param.parse::<u64>() {
-        Ok(id) => id,
-        Err(_) => return error_response,
-    };
+#[cfg(feature = "steam_client")]
+/// Package downloaded to the local package cache
+#[derive(Serialize)]
+struct CachedPackageInfo {
+    file_url: String,
+    size: u64,
+    hash: String,
+}
+
+#[cfg(feature = "steam_client")]
+/// Payload for package download progress events
+#[derive(Clone, Serialize)]
+struct DownloadProgressPayload {
+    hash: String,
+    loaded: u64,
+    total: u64,
+    progress: f64,
+}
+
+#[cfg(feature = "steam_client")]
+/// Download a package from the content service into the local package cache
+/// The package is verified against its SHA-1 hash and served via the `sigame` protocol
+#[tauri::command]
+async fn download_content_package(
+    app_handle: tauri::AppHandle,
+    content_service_uri: String,
+    package_uri: String,
+    package_name: String,
+    package_hash: String,
+) -> Result<CachedPackageInfo, String> {
+    use content_service::{FileKey, SIContentServiceClient};
 
-    log::info!("Custom protocol request for file ID: {}", item_id);
+    let package_cache = app_handle.state::<PackageCache>();
+    let cache_key = PackageCache::key(&package_hash);
+    let file_url = format!("http://sigame.localhost/cache/{}", cache_key);
 
-    // Get the client from app state
-    let client_state = app.state::<Client>();
-    let ugc = client_state.ugc();
-    let workshop_id = PublishedFileId(item_id);
+    let destination = package_cache
+        .path(&cache_key)
+        .ok_or_else(|| format!("Invalid package hash: {}", package_hash))?;
 
-    // Get file path
-    let file_path = match ugc.item_install_info(workshop_id) {
-        Some(info) => {
-            let mut package_path = info.folder.clone();
-            package_path.push_str("/package.siq");
-            package_path
-        }
-        None => return error_response,
+    if let Ok(metadata) = std::fs::metadata(&destination) {
+        log::info!("Package {} is already cached", package_hash);
+
+        return Ok(CachedPackageInfo {
+            file_url,
+            size: metadata.len(),
+            hash: package_hash,
+        });
+    }
+
+    package_cache
+        .ensure_root()
+        .map_err(|e| format!("Failed to create package cache: {}", e))?;
+
+    let package_key = FileKey {
+        name: package_name,
+        hash: package_hash.clone(),
     };
 
-    // Check if file exists
-    if !Path::new(&file_path).exists() {
-        log::error!("File not found: {}", file_path);
-        return error_response;
+    let content_client = SIContentServiceClient::new(&content_service_uri);
+    let app_handle_progress = app_handle.clone();
+    let progress_hash = package_hash.clone();
+
+    let size = content_client
+        .download_package(&package_uri, &package_key, &destination, move |loaded, total| {
+            let progress = if total > 0 {
+                (loaded as f64) / (total as f64)
+            } else {
+                0.0
+            };
+
+            let _ = app_handle_progress.emit(
+                "download-progress",
+                DownloadProgressPayload {
+                    hash: progress_hash.clone(),
+                    loaded,
+                    total,
+                    progress,
+                },
+            );
+        })
+        .await
+        .map_err(|e| format!("Package download failed: {}", e))?;
+
+    if let Err(e) = package_cache.prune(&cache_key) {
+        log::warn!("Failed to prune package cache: {}", e);
     }
 
-    // Read file
-    let mut file = match File::open(&file_path) {
-        Ok(file) => file,
-        Err(e) => {
-            log::error!("Failed to open file: {}", e);
-            return error_response;
+    Ok(CachedPackageInfo {
+        file_url,
+        size,
+        hash: package_hash,
+    })
+}
+
+#[cfg(feature = "steam_client")]
+/// Payload for media upload progress events
+#[derive(Clone, Serialize)]
+struct MediaUploadProgressPayload {
+    file_path: String,
+    loaded: u64,
+    total: u64,
+    progress: f64,
+}
+
+#[cfg(feature = "steam_client")]
+/// Build the content service key of a local media file
+async fn media_file_key(file_path: &Path) -> Result<content_service::FileKey, String> {
+    let name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid media file path: {}", file_path.display()))?
+        .to_string();
+
+    let (_, hash) = content_service::calculate_file_sha1_base64(file_path)
+        .await
+        .map_err(|e| format!("Failed to read media file: {}", e))?;
+
+    Ok(content_service::FileKey { name, hash })
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the content service URI of a local media file if it has already been uploaded
+/// The file path is expected to come from the dialog plugin
+#[tauri::command]
+async fn get_media_uri(
+    file_path: String,
+    media_kind: media::MediaKind,
+    content_service_uri: String,
+) -> Result<Option<String>, String> {
+    let media_key = media_file_key(Path::new(&file_path)).await?;
+
+    content_service::SIContentServiceClient::new(&content_service_uri)
+        .try_get_media_uri(media_kind, &media_key)
+        .await
+        .map_err(|e| format!("Media lookup failed: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+/// Upload a local media file (image, audio, video or avatar) to the content service
+/// The file path is expected to come from the dialog plugin
+/// Progress is reported through the `media-upload-progress` event
+#[tauri::command]
+async fn upload_media_file(
+    app_handle: tauri::AppHandle,
+    file_path: String,
+    media_kind: media::MediaKind,
+    content_service_uri: String,
+) -> Result<content_service::UploadResult, String> {
+    let path = Path::new(&file_path);
+    let media_key = media_file_key(path).await?;
+
+    log::info!("Uploading media file {} as {:?}", file_path, media_kind);
+
+    let app_handle_progress = app_handle.clone();
+    let progress_path = file_path.clone();
+
+    content_service::SIContentServiceClient::new(&content_service_uri)
+        .upload_media_if_not_exists(media_kind, &media_key, path, move |loaded, total| {
+            let progress = if total > 0 {
+                (loaded as f64) / (total as f64)
+            } else {
+                0.0
+            };
+
+            let _ = app_handle_progress.emit(
+                "media-upload-progress",
+                MediaUploadProgressPayload {
+                    file_path: progress_path.clone(),
+                    loaded,
+                    total,
+                    progress,
+                },
+            );
+        })
+        .await
+        .map_err(|e| format!("Media upload failed: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the path of a package given either as a file path or as a Workshop item id
+/// Workshop items that are not installed yet are downloaded
+async fn resolve_package_path(
+    app_handle: &tauri::AppHandle,
+    path: Option<String>,
+    item_id: Option<u64>,
+) -> Result<std::path::PathBuf, String> {
+    match (path, item_id) {
+        (Some(path), None) => Ok(std::path::PathBuf::from(path)),
+        (None, Some(item_id)) => workshop_package_path(app_handle, item_id).await,
+        _ => Err("Either a package path or a Workshop item id must be given".to_string()),
+    }
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the hash of a package file, hashing it only if it has changed since it was last hashed
+/// Workshop packages are cached by item id, other packages by their path
+async fn package_hash(
+    app_handle: &tauri::AppHandle,
+    package_path: &std::path::Path,
+    item_id: Option<u64>,
+) -> Result<String, String> {
+    let (modified, size) =
+        upload_cache::file_stamp(package_path).map_err(|e| format!("Failed to read package: {}", e))?;
+
+    let cached_hash = app_handle.state::<Mutex<UploadCache>>().lock().ok().and_then(|cache| match item_id {
+        Some(item_id) => cache.get_hash(item_id, modified, size),
+        None => cache.get_path_hash(package_path, modified, size),
+    });
+
+    if let Some(hash) = cached_hash {
+        return Ok(hash);
+    }
+
+    let (_, hash) = content_service::calculate_file_sha1_base64(package_path)
+        .await
+        .map_err(|e| format!("Failed to read package: {}", e))?;
+
+    update_upload_cache(app_handle, |cache| match item_id {
+        Some(item_id) => cache.set_hash(item_id, modified, size, &hash),
+        None => cache.set_path_hash(package_path, modified, size, &hash),
+    });
+
+    Ok(hash)
+}
+
+#[cfg(feature = "steam_client")]
+/// Read the description and media list of a package without loading it into the webview
+#[tauri::command]
+async fn read_package_info(
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    item_id: Option<u64>,
+) -> Result<siq::PackageInfo, String> {
+    let package_path = resolve_package_path(&app_handle, path, item_id).await?;
+
+    log::info!("Reading package info: {}", package_path.display());
+
+    tauri::async_runtime::spawn_blocking(move || siq::SiqPackage::open(&package_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(siq::SiqPackage::into_info)
+        .map_err(|e| format!("Failed to read package: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+/// Check a package for problems that would show up during a game
+/// Packages with errors are refused by `upload_workshop_package`
+#[tauri::command]
+async fn validate_package(
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    item_id: Option<u64>,
+) -> Result<package_validation::ValidationReport, String> {
+    let package_path = resolve_package_path(&app_handle, path, item_id).await?;
+
+    log::info!("Validating package: {}", package_path.display());
+
+    tauri::async_runtime::spawn_blocking(move || package_validation::validate_file(&package_path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the packages of the subscribed Workshop items that are installed
+fn installed_workshop_packages(steam: &dyn SteamBackend) -> Vec<InstalledPackage> {
+    steam
+        .subscribed_items()
+        .into_iter()
+        .filter_map(|item_id| {
+            let info = steam.item_install_info(item_id)?;
+
+            Some(InstalledPackage {
+                item_id,
+                updated_time: info.timestamp,
+                path: Path::new(&info.folder).join("package.siq"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "steam_client")]
+/// Find installed Workshop packages that are identical or have mostly the same questions and media
+/// `threshold` is the minimal content similarity (0.0 - 1.0) of duplicates
+#[tauri::command]
+async fn find_duplicate_packages(
+    app_handle: tauri::AppHandle,
+    threshold: Option<f64>,
+) -> Result<Vec<package_duplicates::DuplicateCluster>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let installed = installed_workshop_packages(app_handle.state::<SteamState>().as_ref());
+        let fingerprint_cache = app_handle.state::<FingerprintCache>();
+
+        log::info!("Looking for duplicates among {} installed packages", installed.len());
+
+        let fingerprints: Vec<_> = installed
+            .iter()
+            .filter_map(|package| {
+                let fingerprint = fingerprint_cache.get(package.item_id, package.updated_time, &package.path)?;
+                Some((package.item_id, fingerprint))
+            })
+            .collect();
+
+        package_duplicates::find_duplicates(
+            &fingerprints,
+            threshold.unwrap_or(package_duplicates::DEFAULT_SIMILARITY_THRESHOLD),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "steam_client")]
+/// Search question texts, answers, theme and round names, authors and tags of installed Workshop packages
+/// New and updated packages are indexed before searching
+#[tauri::command]
+async fn search_local_packages(
+    app_handle: tauri::AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<package_index::SearchHit>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let installed = installed_workshop_packages(app_handle.state::<SteamState>().as_ref());
+
+        let package_index = app_handle.state::<Mutex<PackageIndex>>();
+        let mut index = package_index.lock().map_err(|e| e.to_string())?;
+
+        if index.update(&installed) {
+            if let Err(e) = index.save() {
+                log::warn!("Failed to save package index: {}", e);
+            }
         }
+
+        Ok(index.search(&query, limit.unwrap_or(package_index::DEFAULT_SEARCH_LIMIT)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Downscale oversized images of a package to make it smaller to upload and download
+/// The optimized package is written to `output_path`, a `.siq` file which may be the package file itself
+/// for local packages; Workshop packages must be written outside the install folder of the item
+#[tauri::command]
+async fn optimize_package(
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    item_id: Option<u64>,
+    output_path: String,
+    options: Option<package_optimizer::OptimizeOptions>,
+) -> Result<package_optimizer::OptimizationReport, String> {
+    let package_path = resolve_package_path(&app_handle, path, item_id).await?;
+    let install_folder = item_id.and_then(|_| package_path.parent());
+
+    package_optimizer::check_destination(Path::new(&output_path), install_folder).map_err(|e| e.to_string())?;
+
+    let options = options.unwrap_or_default();
+
+    log::info!("Optimizing package {} into {}", package_path.display(), output_path);
+
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        package_optimizer::optimize_file(&package_path, Path::new(&output_path), &options)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to optimize package: {}", e))?;
+
+    log::info!(
+        "Package optimized: {} images re-encoded, {} bytes saved",
+        report.images.len(),
+        report.bytes_saved
+    );
+
+    Ok(report)
+}
+
+#[cfg(feature = "steam_client")]
+/// Subscribe to a Workshop item and start downloading it
+/// Returns true if the item is already installed; otherwise the `workshop-item-installed` event
+/// is emitted once it is downloaded
+#[tauri::command]
+async fn subscribe_workshop_item(app_handle: tauri::AppHandle, item_id: u64) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+        app_handle.state::<WorkshopSubscriptions>().subscribe(steam.as_ref(), item_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Unsubscribe from a Workshop item
+#[tauri::command]
+async fn unsubscribe_workshop_item(app_handle: tauri::AppHandle, item_id: u64) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+        app_handle.state::<WorkshopSubscriptions>().unsubscribe(steam.as_ref(), item_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Upvote or downvote a Workshop item
+#[tauri::command]
+async fn vote_workshop_item(app_handle: tauri::AppHandle, item_id: u64, vote_up: bool) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<SteamState>().set_item_vote(item_id, vote_up))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Get the vote of the user for a Workshop item
+#[tauri::command]
+async fn get_workshop_item_vote(
+    app_handle: tauri::AppHandle,
+    item_id: u64,
+) -> Result<steam_backend::ItemVote, String> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<SteamState>().item_vote(item_id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Add a Workshop item to the favourites of the user or remove it from them
+#[tauri::command]
+async fn set_workshop_item_favorite(app_handle: tauri::AppHandle, item_id: u64, favorite: bool) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app_handle.state::<SteamState>().set_item_favorite(item_id, favorite)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(feature = "steam_client")]
+/// Pass finished Workshop item downloads to the commands waiting for them
+/// Newly subscribed items are reported through the `workshop-item-installed` event
+fn handle_finished_downloads(steam: &dyn SteamBackend, app_handle: tauri::AppHandle) {
+    steam.add_download_listener(Box::new(move |item_id, result| {
+        app_handle.state::<WorkshopDownloads>().download_finished(item_id, &result);
+
+        if app_handle.state::<WorkshopSubscriptions>().download_finished(item_id, &result) {
+            log::info!("Subscribed Workshop item {} installed", item_id);
+            let _ = app_handle.emit("workshop-item-installed", WorkshopItemInstalledPayload { item_id });
+        }
+    }));
+}
+
+#[cfg(feature = "steam_client")]
+/// Publish a package as a new Workshop item
+/// Upload progress is reported through the `workshop-publish-progress` event
+#[tauri::command]
+async fn publish_workshop_package(
+    app_handle: tauri::AppHandle,
+    path: String,
+    options: workshop_publish::PublishOptions,
+) -> Result<workshop_publish::PublishResult, String> {
+    let staging_root = app_handle.path().app_cache_dir().map_err(|e| e.to_string())?.join("workshop-publish");
+
+    log::info!("Publishing package {} to the Workshop", path);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+
+        workshop_publish::publish(steam.as_ref(), &staging_root, Path::new(&path), &options, &mut |item_id, progress| {
+            emit_publish_progress(&app_handle, item_id, progress)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to publish package: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+/// Update the properties of a Workshop item and optionally replace its package
+/// Upload progress is reported through the `workshop-publish-progress` event
+#[tauri::command]
+async fn update_workshop_package(
+    app_handle: tauri::AppHandle,
+    item_id: u64,
+    path: Option<String>,
+    options: Option<workshop_publish::PublishOptions>,
+) -> Result<workshop_publish::PublishResult, String> {
+    let staging_root = app_handle.path().app_cache_dir().map_err(|e| e.to_string())?.join("workshop-publish");
+    let options = options.unwrap_or_default();
+
+    log::info!("Updating Workshop item {}", item_id);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let steam = app_handle.state::<SteamState>();
+
+        workshop_publish::update(
+            steam.as_ref(),
+            &staging_root,
+            item_id,
+            path.as_deref().map(Path::new),
+            &options,
+            &mut |item_id, progress| emit_publish_progress(&app_handle, item_id, progress),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to update Workshop item: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+fn emit_publish_progress(app_handle: &tauri::AppHandle, item_id: u64, progress: UpdateProgress) {
+    let _ = app_handle.emit(
+        "workshop-publish-progress",
+        WorkshopPublishProgressPayload {
+            item_id,
+            stage: progress.stage,
+            processed: progress.processed,
+            total: progress.total,
+            progress: if progress.total > 0 {
+                progress.processed as f64 / progress.total as f64
+            } else {
+                0.0
+            },
+        },
+    );
+}
+
+#[cfg(feature = "steam_client")]
+/// Generate previews of the package logo, question images and rounds
+/// Thumbnails are cached per package hash and served via the `sigame` protocol
+#[tauri::command]
+async fn get_package_thumbnails(
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    item_id: Option<u64>,
+) -> Result<thumbnails::PackageThumbnails, String> {
+    let package_path = resolve_package_path(&app_handle, path, item_id).await?;
+    let package_hash = package_hash(&app_handle, &package_path, item_id).await?;
+
+    log::info!("Getting thumbnails of package {} ({})", package_path.display(), package_hash);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        app_handle
+            .state::<ThumbnailCache>()
+            .get_or_create(&package_hash, &package_path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to generate thumbnails: {}", e))
+}
+
+#[cfg(feature = "steam_client")]
+// Handle custom protocol for workshop files
+fn handle_workshop_protocol(
+    app: tauri::AppHandle,
+    request: tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    log::info!("Received custom protocol request: {}", request.uri());
+
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let protocol_request = protocol::ProtocolRequest {
+        path: request.uri().path(),
+        query: request.uri().query(),
+        headers: protocol::RequestHeaders {
+            range: header("Range"),
+            if_range: header("If-Range"),
+            if_none_match: header("If-None-Match"),
+            if_modified_since: header("If-Modified-Since"),
+        },
     };
 
-    // Read file data
-    let mut data = Vec::new();
-    if let Err(e) = file.read_to_end(&mut data) {
-        log::error!("Failed to read file: {}", e);
-        return error_response;
+    let response = protocol::handle_request(
+        app.state::<SteamState>().as_ref(),
+        &app.state::<PackageCache>(),
+        &app.state::<ThumbnailCache>(),
+        &protocol_request,
+    );
+
+    into_http_response(response)
+}
+
+#[cfg(feature = "steam_client")]
+/// Convert a protocol response into an HTTP response
+fn into_http_response(response: protocol::ProtocolResponse) -> tauri::http::Response<Vec<u8>> {
+    let mut builder = tauri::http::Response::builder().status(response.status);
+
+    for (name, value) in response.headers {
+        builder = builder.header(name, value);
     }
 
-    // Build response with correct MIME type
-    tauri::http::Response::builder()
-        .header("Access-Control-Allow-Origin", "*")
-        .header("Content-Type", "application/x-zip-compressed")
-        .header(
-            "Content-Disposition",
-            format!("attachment; filename=\"package.siq\""),
-        )
-        .status(200)
-        .body(data)
-        .unwrap()
+    builder.body(response.body).unwrap()
 }
 
 #[tauri::command]
@@ -582,10 +1331,8 @@ fn greet(name: &str) -> String {
 
 #[cfg(feature = "steam_client")]
 #[tauri::command]
-fn open_url_in_steam_overlay(client_state: tauri::State<Client>, url: String) {
-    client_state
-        .friends()
-        .activate_game_overlay_to_web_page(&url);
+fn open_url_in_steam_overlay(steam: tauri::State<SteamState>, url: String) {
+    steam.open_overlay_url(&url);
 }
 
 #[tauri::command]
@@ -638,19 +1385,40 @@ pub fn run() {
         builder = builder
             .setup(|app| {
                 // Initialize Steam (must have steam_appid.txt or app ID passed)
-                let steam_result = Client::init_app(3553500);
+                let steam_result = Client::init_app(steam_backend::APP_ID);
 
                 match steam_result {
                     Ok(client) => {
-                        let callback_client = client.clone();
+                        let steam: SteamState = Arc::new(SteamworksBackend::new(client));
+                        let callback_steam = steam.clone();
+
+                        app.manage(WorkshopDownloads::default());
+                        app.manage(WorkshopSubscriptions::default());
+                        handle_finished_downloads(steam.as_ref(), app.handle().clone());
 
                         // Store the client in app state for later use
-                        app.manage(client);
+                        app.manage(steam);
+                        app.manage::<UploadQueueState>(Mutex::new(UploadQueue::new(DEFAULT_MAX_CONCURRENT_UPLOADS)));
+
+                        let upload_cache_path = app.path().app_data_dir()?.join("upload-cache.json");
+                        app.manage(Mutex::new(UploadCache::load(upload_cache_path)));
+
+                        let package_cache_path = app.path().app_cache_dir()?.join("packages");
+                        app.manage(PackageCache::new(package_cache_path, DEFAULT_MAX_CACHE_SIZE));
+
+                        app.manage(StatisticsCache::default());
+                        app.manage(FingerprintCache::default());
+
+                        let package_index_path = app.path().app_cache_dir()?.join("package-index.json");
+                        app.manage(Mutex::new(PackageIndex::load(package_index_path)));
+
+                        let thumbnail_cache_path = app.path().app_cache_dir()?.join("thumbnails");
+                        app.manage(ThumbnailCache::new(thumbnail_cache_path, "http://sigame.localhost/thumbnails"));
 
                         // Keep the client alive
                         std::thread::spawn(move || {
                             loop {
-                                callback_client.run_callbacks();
+                                callback_steam.run_callbacks();
                                 std::thread::sleep(std::time::Duration::from_millis(100));
                             }
                         });
@@ -693,8 +1461,31 @@ pub fn run() {
             greet,
             open_url_in_steam_overlay,
             get_workshop_subscribed_items,
+            query_workshop_items,
             get_workshop_file_url,
+            cancel_workshop_download,
+            set_workshop_download_timeout,
             upload_workshop_package,
+            cancel_upload,
+            list_uploads,
+            set_upload_concurrency,
+            download_content_package,
+            get_media_uri,
+            content_service::probe_content_service,
+            upload_media_file,
+            read_package_info,
+            validate_package,
+            get_package_thumbnails,
+            optimize_package,
+            search_local_packages,
+            find_duplicate_packages,
+            publish_workshop_package,
+            update_workshop_package,
+            subscribe_workshop_item,
+            unsubscribe_workshop_item,
+            vote_workshop_item,
+            get_workshop_item_vote,
+            set_workshop_item_favorite,
             append_text_file,
             get_steam_user_info,
             get_steam_auth_ticket
@@ -716,3 +1507,166 @@ pub fn run() {
             std::process::exit(1);
         });
 }
+
+#[cfg(all(test, feature = "steam_client"))]
+mod tests {
+    use super::*;
+    use fake_steam_backend::{item_details, FakeSteamBackend};
+
+    fn item_folder(name: &str) -> String {
+        let folder = std::env::temp_dir().join(format!("sigame-steam-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("package.siq"), b"package").unwrap();
+        folder.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_installed_item_file_info() {
+        let folder = item_folder("installed");
+        let steam = FakeSteamBackend::new().with_installed_item(1, &folder, 100);
+
+        let package_path = WorkshopDownloads::default().wait_for_package(&steam, 1, &mut |_| {}).unwrap();
+        let info = workshop_file_info(1, &package_path).unwrap();
+
+        assert_eq!(info.file_url, "http://sigame.localhost/file?id=1");
+        assert_eq!(info.size, 7);
+        assert!(!steam.download_started(1));
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn test_subscribed_items_pages() {
+        let steam = (1..=60).fold(FakeSteamBackend::new(), |steam, id| {
+            steam.with_subscribed_item(item_details(id, &format!("Package {}", id)))
+        });
+
+        let response = subscribed_items_page(&steam, &StatisticsCache::default(), 2).unwrap();
+
+        assert_eq!(response.total, 60);
+        assert_eq!(response.items.len(), 10);
+        assert_eq!(response.items[0].title, "Package 51");
+        assert!(response.items.iter().all(|item| item.statistics.is_none()));
+
+        let steam = steam.with_query_error("Steam error: Timeout");
+        assert_eq!(
+            subscribed_items_page(&steam, &StatisticsCache::default(), 1).err().as_deref(),
+            Some("Steam error: Timeout")
+        );
+    }
+
+    #[test]
+    fn test_query_workshop_items() {
+        let item = |id, title: &str, tags: &[&str], votes_up, subscriptions| steam_backend::WorkshopItemDetails {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            votes_up,
+            votes_down: 1,
+            subscriptions,
+            created_time: 1_700_000_000 + id as u32,
+            ..item_details(id, title)
+        };
+
+        let steam = FakeSteamBackend::new()
+            .with_workshop_item(item(1, "Movies", &["Cinema"], 10, 300))
+            .with_workshop_item(item(2, "Movie music", &["Cinema", "Music"], 30, 100))
+            .with_workshop_item(item(3, "Rock music", &["Music"], 20, 200))
+            .with_workshop_item(item(4, "History", &["History", "18+"], 40, 50));
+
+        let query = |query: steam_backend::WorkshopQuery| {
+            let response = workshop_items_page(&steam, &StatisticsCache::default(), &query).unwrap();
+            response.items.iter().map(|item| item.id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            query(steam_backend::WorkshopQuery {
+                sort_order: steam_backend::WorkshopSortOrder::Votes,
+                excluded_tags: vec!["18+".to_string()],
+                ..Default::default()
+            }),
+            vec![2, 3, 1]
+        );
+
+        assert_eq!(
+            query(steam_backend::WorkshopQuery {
+                search_text: Some("music".to_string()),
+                required_tags: vec!["Music".to_string()],
+                sort_order: steam_backend::WorkshopSortOrder::Subscriptions,
+                ..Default::default()
+            }),
+            vec![3, 2]
+        );
+
+        assert_eq!(
+            query(steam_backend::WorkshopQuery {
+                sort_order: steam_backend::WorkshopSortOrder::Recent,
+                time_window: Some(steam_backend::WorkshopTimeWindow::Week),
+                page: 1,
+                ..Default::default()
+            }),
+            vec![4, 3, 2, 1]
+        );
+
+        let response = workshop_items_page(
+            &steam,
+            &StatisticsCache::default(),
+            &steam_backend::WorkshopQuery {
+                page: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(response.items.is_empty());
+        assert_eq!(response.total, 4);
+
+        let steam = steam.with_query_error("Steam error: Timeout");
+        assert!(workshop_items_page(&steam, &StatisticsCache::default(), &Default::default()).is_err());
+    }
+
+    #[test]
+    fn test_subscribed_item_installed_notification() {
+        let steam = FakeSteamBackend::new().with_workshop_item(item_details(1, "Package"));
+        let subscriptions = Arc::new(WorkshopSubscriptions::default());
+        let installed = Arc::new(Mutex::new(Vec::new()));
+
+        let (listener_subscriptions, listener_installed) = (subscriptions.clone(), installed.clone());
+        steam.add_download_listener(Box::new(move |item_id, result| {
+            if listener_subscriptions.download_finished(item_id, &result) {
+                listener_installed.lock().unwrap().push(item_id);
+            }
+        }));
+
+        assert!(!subscriptions.subscribe(&steam, 1).unwrap());
+
+        steam.finish_download(1, Err("k_EResultTimeout"));
+        steam.finish_download(1, Ok("/workshop/1"));
+        steam.finish_download(1, Ok("/workshop/1"));
+
+        assert_eq!(*installed.lock().unwrap(), vec![1]);
+        assert!(steam.item_install_info(1).is_some());
+    }
+
+    #[test]
+    fn test_auth_ticket() {
+        let steam = FakeSteamBackend::new();
+        assert!(steam_auth_ticket(&steam, "  ").is_err());
+        assert!(steam_auth_ticket(&steam, "sigame").unwrap_err().contains("Timed out"));
+
+        let steam = steam.with_ticket(Ok(vec![0x0a, 0xff, 0x10]));
+        assert_eq!(steam_auth_ticket(&steam, " sigame ").unwrap(), "0aff10");
+
+        let steam = steam.with_ticket(Err("Steam Web API ticket request failed".to_string()));
+        assert!(steam_auth_ticket(&steam, "sigame").is_err());
+    }
+
+    #[test]
+    fn test_user_info_encodes_avatar() {
+        let info = steam_user_info(&FakeSteamBackend::new()).unwrap();
+
+        assert_eq!(info.name, "Player");
+
+        let avatar = general_purpose::STANDARD.decode(info.avatar.unwrap()).unwrap();
+        let image = image::load_from_memory(&avatar).unwrap();
+        assert_eq!((image.width(), image.height()), (184, 184));
+    }
+}
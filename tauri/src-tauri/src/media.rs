@@ -0,0 +1,184 @@
+//! Media file types supported by the content service.
+//!
+//! The MIME type of a file is detected from its content signature first and from its
+//! extension second, so renamed files are still uploaded with the correct type.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+/// Kind of media stored in the content service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Avatar,
+    Image,
+    Audio,
+    Video,
+}
+
+impl MediaKind {
+    /// Content service path segment for this kind of media
+    pub fn path_segment(self) -> &'static str {
+        match self {
+            MediaKind::Avatar => "avatars",
+            MediaKind::Image => "images",
+            MediaKind::Audio => "audio",
+            MediaKind::Video => "video",
+        }
+    }
+
+    /// Whether a file of the given MIME type can be stored as this kind of media
+    pub fn accepts(self, mime_type: &str) -> bool {
+        match self {
+            MediaKind::Avatar => mime_type.starts_with("image/") || mime_type.starts_with("video/"),
+            MediaKind::Image => mime_type.starts_with("image/"),
+            MediaKind::Audio => mime_type.starts_with("audio/"),
+            MediaKind::Video => mime_type.starts_with("video/"),
+        }
+    }
+}
+
+/// Detect the MIME type of a file from its content and extension
+pub fn detect_mime_type(path: &Path) -> std::io::Result<&'static str> {
+    let mut header = [0u8; 32];
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let read = file.read(&mut header)?;
+
+    Ok(sniff_mime_type(&header[..read], size)
+        .or_else(|| mime_type_from_extension(path))
+        .unwrap_or("application/octet-stream"))
+}
+
+/// Detect the MIME type from the file signature and the file size
+fn sniff_mime_type(header: &[u8], size: u64) -> Option<&'static str> {
+    let riff_type = |kind: &[u8]| header.starts_with(b"RIFF") && header.get(8..12) == Some(kind);
+
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if riff_type(b"WEBP") {
+        Some("image/webp")
+    } else if is_bmp(header, size) {
+        Some("image/bmp")
+    } else if riff_type(b"WAVE") {
+        Some("audio/wav")
+    } else if header.starts_with(b"ID3") || (header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0) {
+        Some("audio/mpeg")
+    } else if header.starts_with(b"OggS") {
+        Some("audio/ogg")
+    } else if header.starts_with(b"fLaC") {
+        Some("audio/flac")
+    } else if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some("video/webm")
+    } else if header.get(4..8) == Some(b"ftyp") {
+        // ISO base media: audio-only brands are M4A, the rest is treated as video
+        match header.get(8..12) {
+            Some(b"M4A ") => Some("audio/mp4"),
+            Some(b"qt  ") => Some("video/quicktime"),
+            _ => Some("video/mp4"),
+        }
+    } else {
+        None
+    }
+}
+
+/// Check the BMP file header: `BM` alone is too common a prefix (e.g. of text files)
+fn is_bmp(header: &[u8], size: u64) -> bool {
+    let read_u32 = |offset: usize| {
+        header
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    // The declared file size must fit into the file and the DIB header must have a known size
+    header.starts_with(b"BM")
+        && read_u32(2).is_some_and(|file_size| file_size > 0 && u64::from(file_size) <= size)
+        && read_u32(14).is_some_and(|dib_header_size| [12, 40, 56, 108, 124].contains(&dib_header_size))
+}
+
+/// Get the MIME type from the file extension
+pub fn mime_type_from_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "flac" => "audio/flac",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "html" | "htm" => "text/html",
+        _ => return None,
+    };
+
+    Some(mime_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_mime_type() {
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", 1024), Some("image/png"));
+        assert_eq!(sniff_mime_type(&[0xFF, 0xD8, 0xFF, 0xE0], 1024), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 ", 1024), Some("image/webp"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WAVEfmt ", 1024), Some("audio/wav"));
+        assert_eq!(sniff_mime_type(b"ID3\x04\0\0\0\0", 1024), Some("audio/mpeg"));
+        assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypisom", 1024), Some("video/mp4"));
+        assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypM4A ", 1024), Some("audio/mp4"));
+        assert_eq!(sniff_mime_type(b"plain text", 1024), None);
+
+        let bmp = |file_size: u32, dib_header_size: u32| {
+            let mut header = b"BM".to_vec();
+            header.extend_from_slice(&file_size.to_le_bytes());
+            header.extend_from_slice(&[0; 8]);
+            header.extend_from_slice(&dib_header_size.to_le_bytes());
+            header
+        };
+
+        assert_eq!(sniff_mime_type(&bmp(1024, 40), 1024), Some("image/bmp"));
+        assert_eq!(sniff_mime_type(&bmp(900, 124), 1024), Some("image/bmp"));
+        assert_eq!(sniff_mime_type(&bmp(2048, 40), 1024), None);
+        assert_eq!(sniff_mime_type(&bmp(1024, 41), 1024), None);
+        assert_eq!(sniff_mime_type(b"BMW owners club\n", 1024), None);
+    }
+
+    #[test]
+    fn test_detect_mime_type_prefers_content() {
+        let path = std::env::temp_dir().join(format!("sigame-media-{}.mp3", std::process::id()));
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n").unwrap();
+        let sniffed = detect_mime_type(&path).unwrap();
+
+        std::fs::write(&path, b"unknown").unwrap();
+        let from_extension = detect_mime_type(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sniffed, "image/png");
+        assert_eq!(from_extension, "audio/mpeg");
+    }
+
+    #[test]
+    fn test_media_kind_accepts() {
+        assert!(MediaKind::Image.accepts("image/png"));
+        assert!(!MediaKind::Image.accepts("audio/mpeg"));
+        assert!(MediaKind::Avatar.accepts("video/webm"));
+        assert!(!MediaKind::Video.accepts("application/octet-stream"));
+    }
+}
@@ -0,0 +1,392 @@
+//! Minimal in-process SIContentService used by tests.
+//!
+//! Speaks just enough HTTP/1.1 to serve the endpoints used by `content_service`:
+//! - `GET /api/v1/content/packages/{hash}/{name}` package lookup
+//! - `POST /api/v1/content/packages` multipart upload
+//! - `GET /api/v1/content/{media}/{hash}/{name}` and `POST /api/v1/content/{media}` media files
+//! - `POST/PUT/GET /api/v1/content/packages/uploads[/{id}]` chunked upload sessions
+//! - `GET /api/v1/info` service information
+//! - `GET /files/{hash}` download of packages uploaded with multipart upload
+//!
+//! It can be configured to drop connections in the middle of chunk uploads to test resumption,
+//! and to fail requests with given status codes to test retries.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Behaviour of the mock service
+#[derive(Debug, Clone, Default)]
+pub struct MockOptions {
+    /// Whether chunked upload sessions are supported
+    pub supports_chunked: bool,
+    /// Number of chunk requests to drop after reading half of their body
+    pub dropped_chunks: usize,
+    /// Whether new upload sessions report the whole file as received (a resumed, finished upload)
+    pub completed_sessions: bool,
+    /// Status codes returned for the first requests, in order (429 responses carry `Retry-After: 0`,
+    /// 0 lets the request through)
+    pub failures: Vec<u16>,
+    /// Whether multipart uploads are rejected with 413
+    pub reject_uploads: bool,
+    /// Service information JSON (404 if not set)
+    pub info: Option<&'static str>,
+}
+
+/// Recorded state of the mock service
+#[derive(Debug, Default)]
+pub struct MockState {
+    /// Package URIs by escaped hash
+    pub packages: HashMap<String, String>,
+    /// Package contents by escaped hash
+    pub files: HashMap<String, Vec<u8>>,
+    /// Bytes received in the current chunked upload session
+    pub received: Vec<u8>,
+    /// Expected size of the current chunked upload session
+    pub expected_size: u64,
+    /// Number of multipart uploads received
+    pub multipart_uploads: usize,
+    /// MIME type of the last uploaded file by content type (e.g. `images`)
+    pub media_types: HashMap<String, String>,
+    /// Number of chunk requests received (including dropped ones)
+    pub chunk_requests: usize,
+    /// Number of chunk requests dropped so far
+    pub dropped_chunks: usize,
+    /// Number of complete requests received
+    pub requests: usize,
+}
+
+/// Running mock service
+pub struct MockContentService {
+    pub addr: SocketAddr,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockContentService {
+    /// Start the mock service on a random local port
+    pub async fn start(options: MockOptions) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server_state = state.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+
+                let state = server_state.clone();
+                let options = options.clone();
+
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, state, options).await;
+                });
+            }
+        });
+
+        Self { addr, state }
+    }
+
+    /// Base URI of the service
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+enum Outcome {
+    Respond(u16, String),
+    RespondBytes(u16, Vec<u8>),
+    Drop,
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    state: Arc<Mutex<MockState>>,
+    options: MockOptions,
+) -> std::io::Result<()> {
+    let mut buffer = Vec::new();
+
+    loop {
+        let Some(request) = read_request(&mut stream, &mut buffer, &state, &options).await? else {
+            // Connection closed or deliberately dropped
+            return Ok(());
+        };
+
+        let (status, body) = match route(request, &state, &options) {
+            Outcome::Respond(status, body) => (status, body.into_bytes()),
+            Outcome::RespondBytes(status, body) => (status, body),
+            Outcome::Drop => return Ok(()),
+        };
+
+        let retry_after = if status == 429 { "Retry-After: 0\r\n" } else { "" };
+
+        let head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nContent-Type: text/plain\r\n{}\r\n",
+            status,
+            body.len(),
+            retry_after
+        );
+
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&body).await?;
+    }
+}
+
+/// Read a full request; returns None when the connection should be closed
+async fn read_request(
+    stream: &mut TcpStream,
+    buffer: &mut Vec<u8>,
+    state: &Arc<Mutex<MockState>>,
+    options: &MockOptions,
+) -> std::io::Result<Option<Request>> {
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+
+        if !read_more(stream, buffer).await? {
+            return Ok(None);
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    buffer.drain(..header_end + 4);
+
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let is_chunk = method == "PUT" && path.contains("/uploads/");
+
+    let drop_chunk = is_chunk && {
+        let mut state = state.lock().unwrap();
+        state.chunk_requests += 1;
+
+        if state.dropped_chunks < options.dropped_chunks {
+            state.dropped_chunks += 1;
+            true
+        } else {
+            false
+        }
+    };
+
+    if drop_chunk {
+        // Read part of the body, then hang up
+        let length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        while buffer.len() < length / 2 {
+            if !read_more(stream, buffer).await? {
+                break;
+            }
+        }
+
+        return Ok(None);
+    }
+
+    let body = if headers.get("transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        read_chunked_body(stream, buffer).await?
+    } else {
+        let length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+
+        while buffer.len() < length {
+            if !read_more(stream, buffer).await? {
+                return Ok(None);
+            }
+        }
+
+        buffer.drain(..length).collect()
+    };
+
+    Ok(Some(Request { method, path, headers, body }))
+}
+
+async fn read_chunked_body(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+
+    loop {
+        let line_end = loop {
+            if let Some(pos) = buffer.windows(2).position(|w| w == b"\r\n") {
+                break pos;
+            }
+
+            read_required(stream, buffer).await?;
+        };
+
+        let size_line = String::from_utf8_lossy(&buffer[..line_end]).to_string();
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("0").trim(), 16).unwrap_or(0);
+        buffer.drain(..line_end + 2);
+
+        while buffer.len() < size + 2 {
+            read_required(stream, buffer).await?;
+        }
+
+        body.extend(buffer.drain(..size));
+        buffer.drain(..2);
+
+        if size == 0 {
+            return Ok(body);
+        }
+    }
+}
+
+async fn read_more(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> std::io::Result<bool> {
+    let mut chunk = [0u8; 16 * 1024];
+    let read = stream.read(&mut chunk).await?;
+    buffer.extend_from_slice(&chunk[..read]);
+    Ok(read > 0)
+}
+
+async fn read_required(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    if read_more(stream, buffer).await? {
+        Ok(())
+    } else {
+        Err(std::io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+fn route(request: Request, state: &Arc<Mutex<MockState>>, options: &MockOptions) -> Outcome {
+    let mut state = state.lock().unwrap();
+    let path = request.path.split('?').next().unwrap_or_default();
+
+    state.requests += 1;
+
+    if let Some(&status) = options.failures.get(state.requests - 1).filter(|&&status| status != 0) {
+        return Outcome::Respond(status, String::new());
+    }
+
+    match (request.method.as_str(), path) {
+        ("POST", path)
+            if path.starts_with("/api/v1/content/") && !path.starts_with("/api/v1/content/packages/") =>
+        {
+            state.multipart_uploads += 1;
+
+            let content_type = path.trim_start_matches("/api/v1/content/").to_string();
+            state.media_types.insert(content_type, extract_multipart_content_type(&request.body));
+
+            if options.reject_uploads {
+                return Outcome::Respond(413, "Package is too large".to_string());
+            }
+
+            let hash = request.headers.get("content-md5").cloned().unwrap_or_default();
+            let escaped_hash = super::content_service::escape_base64(&hash);
+            let uri = format!("http://mock/packages/{}", escaped_hash);
+            state.packages.insert(escaped_hash.clone(), uri.clone());
+            state.files.insert(escaped_hash, extract_multipart_file(&request.body));
+            Outcome::Respond(200, uri)
+        }
+        ("POST", "/api/v1/content/packages/uploads") => {
+            if !options.supports_chunked {
+                return Outcome::Respond(404, String::new());
+            }
+
+            let session: serde_json::Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            state.expected_size = session["size"].as_u64().unwrap_or(0);
+            state.received.clear();
+
+            if options.completed_sessions {
+                let expected_size = state.expected_size as usize;
+                state.received.resize(expected_size, 0);
+            }
+
+            Outcome::Respond(201, format!(r#"{{"id":"session 1","offset":{}}}"#, state.received.len()))
+        }
+        ("PUT", "/api/v1/content/packages/uploads/session%201") => {
+            let offset: usize = request
+                .path
+                .split("offset=")
+                .nth(1)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+
+            if offset != state.received.len() {
+                return Outcome::Respond(409, format!(r#"{{"offset":{}}}"#, state.received.len()));
+            }
+
+            state.received.extend_from_slice(&request.body);
+            Outcome::Respond(200, upload_status(&state))
+        }
+        ("GET", "/api/v1/content/packages/uploads/session%201") => Outcome::Respond(200, upload_status(&state)),
+        ("GET", "/api/v1/info") => match options.info {
+            Some(info) => Outcome::Respond(200, info.to_string()),
+            None => Outcome::Respond(404, String::new()),
+        },
+        ("GET", path) if path.starts_with("/files/") => {
+            match state.files.get(path.trim_start_matches("/files/")) {
+                Some(data) => Outcome::RespondBytes(200, data.clone()),
+                None => Outcome::Respond(404, String::new()),
+            }
+        }
+        ("GET", path) if path.starts_with("/api/v1/content/") => {
+            // /api/v1/content/{content type}/{hash}/{name}
+            let hash = path.trim_start_matches("/api/v1/content/").split('/').nth(1).unwrap_or_default();
+
+            match state.packages.get(hash) {
+                Some(uri) => Outcome::Respond(200, uri.clone()),
+                None => Outcome::Respond(404, String::new()),
+            }
+        }
+        _ => Outcome::Drop,
+    }
+}
+
+/// Acknowledgement of the current chunked upload session, with the package URI once complete
+fn upload_status(state: &MockState) -> String {
+    let received = state.received.len();
+
+    if received as u64 == state.expected_size {
+        format!(r#"{{"offset":{},"uri":"http://mock/packages/chunked"}}"#, received)
+    } else {
+        format!(r#"{{"offset":{}}}"#, received)
+    }
+}
+
+/// Extract the Content-Type of the single file part of a multipart body
+fn extract_multipart_content_type(body: &[u8]) -> String {
+    let head_end = body.windows(4).position(|w| w == b"\r\n\r\n").unwrap_or(0);
+
+    String::from_utf8_lossy(&body[..head_end])
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Extract the content of the single file part of a multipart body
+fn extract_multipart_file(body: &[u8]) -> Vec<u8> {
+    let Some(boundary_end) = body.windows(2).position(|w| w == b"\r\n") else {
+        return Vec::new();
+    };
+
+    let boundary = &body[..boundary_end];
+
+    let Some(content_start) = body.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| pos + 4) else {
+        return Vec::new();
+    };
+
+    // The content ends with CRLF followed by the closing boundary
+    let closing = [b"\r\n".as_slice(), boundary].concat();
+
+    let content_end = body[content_start..]
+        .windows(closing.len())
+        .rposition(|w| w == closing.as_slice())
+        .map(|pos| content_start + pos)
+        .unwrap_or(body.len());
+
+    body[content_start..content_end].to_vec()
+}
@@ -0,0 +1,128 @@
+//! Local content-addressed cache of packages downloaded from the content service.
+//!
+//! Packages are stored as `{escaped hash}.siq`, where the escaped hash is the URL-safe form
+//! of the package SHA-1 hash. A package is only put into the cache after its hash is verified,
+//! so a cached file can be served without checking it again.
+
+use crate::content_service::escape_base64;
+use std::path::PathBuf;
+
+/// Default limit for the total size of cached packages
+pub const DEFAULT_MAX_CACHE_SIZE: u64 = 4 * 1024 * 1024 * 1024; // 4 GB
+
+/// Package cache
+pub struct PackageCache {
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl PackageCache {
+    /// Create a cache in the given directory
+    pub fn new(root: PathBuf, max_size: u64) -> Self {
+        Self { root, max_size }
+    }
+
+    /// Get the cache key (escaped hash) of a package hash
+    pub fn key(hash: &str) -> String {
+        escape_base64(hash)
+    }
+
+    /// Get the path where a package with the given cache key is stored
+    /// Returns None if the key is not a valid escaped hash
+    pub fn path(&self, key: &str) -> Option<PathBuf> {
+        let is_valid = !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        is_valid.then(|| self.root.join(format!("{}.siq", key)))
+    }
+
+    /// Get the path of a cached package if it exists
+    pub fn find(&self, key: &str) -> Option<PathBuf> {
+        self.path(key).filter(|path| path.is_file())
+    }
+
+    /// Make sure the cache directory exists
+    pub fn ensure_root(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.root)
+    }
+
+    /// Remove the least recently modified packages until the cache fits into its size limit
+    /// The package with the given key is never removed
+    pub fn prune(&self, keep_key: &str) -> std::io::Result<()> {
+        let keep_path = self.path(keep_key);
+        let mut files = Vec::new();
+        let mut total_size = 0u64;
+
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.extension().is_none_or(|extension| extension != "siq") {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            total_size += metadata.len();
+
+            if Some(&path) != keep_path.as_ref() {
+                files.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+
+        files.sort_by_key(|(modified, _, _)| *modified);
+
+        for (_, size, path) in files {
+            if total_size <= self.max_size {
+                break;
+            }
+
+            log::info!("Removing cached package: {}", path.display());
+            std::fs::remove_file(&path)?;
+            total_size -= size;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sigame-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_path_rejects_invalid_keys() {
+        let cache = PackageCache::new(cache_dir("cache-keys"), DEFAULT_MAX_CACHE_SIZE);
+        let key = PackageCache::key("ab+c/d==");
+
+        assert_eq!(key, "ab-c_d");
+        assert!(cache.path(&key).unwrap().ends_with("ab-c_d.siq"));
+        assert!(cache.path("../secret").is_none());
+        assert!(cache.path("a/b").is_none());
+        assert!(cache.path("").is_none());
+    }
+
+    #[test]
+    fn test_prune_removes_oldest_packages() {
+        let root = cache_dir("cache-prune");
+        let cache = PackageCache::new(root.clone(), 250);
+        cache.ensure_root().unwrap();
+
+        for key in ["old", "middle", "new"] {
+            std::fs::write(cache.path(key).unwrap(), vec![0u8; 100]).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        // The oldest package survives because it is in use
+        cache.prune("old").unwrap();
+
+        assert!(cache.find("old").is_some());
+        assert!(cache.find("middle").is_none());
+        assert!(cache.find("new").is_some());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,375 @@
+//! Detection of duplicate packages.
+//!
+//! This module provides functionality to:
+//! - Fingerprint a package by its file hash, its questions and the hashes of its media files
+//! - Find packages with identical files and packages with mostly the same content
+//! - Group duplicates into clusters with a similarity score
+//!
+//! Content similarity is the Jaccard index of the fingerprints: the number of questions and
+//! media files two packages share divided by the number of distinct ones they have together.
+
+use crate::archive::{ArchiveError, SafeArchive};
+use crate::content_service::calculate_reader_sha1_base64;
+use crate::siq::{ContentType, SiqError, SiqPackage};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Default similarity above which packages are considered duplicates
+pub const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// Part of a package compared with other packages
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Feature {
+    /// Hash of the normalized question text and answers
+    Question(u64),
+    /// SHA-1 hash of a media file
+    Media(String),
+}
+
+/// Fingerprint of a package
+#[derive(Debug, Clone)]
+pub struct PackageFingerprint {
+    pub package_name: String,
+    /// SHA-1 hash of the package file
+    pub hash: String,
+    features: HashSet<Feature>,
+}
+
+impl PackageFingerprint {
+    /// Read a package file and fingerprint it
+    pub fn read(path: &Path) -> Result<Self, SiqError> {
+        let hash = std::fs::File::open(path)
+            .and_then(calculate_reader_sha1_base64)
+            .map_err(|e| SiqError::Archive(ArchiveError::IoError(e.to_string())))?;
+
+        let mut archive = SafeArchive::open(path)?;
+        let siq = SiqPackage::from_archive(&mut archive)?;
+        let mut features = HashSet::new();
+
+        let questions = siq
+            .package
+            .rounds
+            .iter()
+            .flat_map(|round| &round.themes)
+            .flat_map(|theme| &theme.questions);
+
+        for question in questions {
+            let text: Vec<&str> = question
+                .content_items()
+                .into_iter()
+                .filter(|item| item.content_type == ContentType::Text && !item.is_ref)
+                .map(|item| item.value.as_str())
+                .chain(question.right.iter().map(String::as_str))
+                .collect();
+
+            if let Some(key) = question_key(&text) {
+                features.insert(Feature::Question(key));
+            }
+        }
+
+        for media in &siq.media {
+            let media_hash = archive
+                .entry_reader(&media.path)
+                .and_then(|reader| calculate_reader_sha1_base64(reader).map_err(|e| ArchiveError::IoError(e.to_string())));
+
+            match media_hash {
+                Ok(media_hash) => {
+                    features.insert(Feature::Media(media_hash));
+                }
+                Err(e) => log::warn!("Failed to read media file {} of {}: {}", media.path, path.display(), e),
+            }
+        }
+
+        Ok(Self {
+            package_name: siq.package.name,
+            hash,
+            features,
+        })
+    }
+
+    /// Jaccard index of the package contents
+    fn similarity(&self, other: &Self, shared: usize) -> f64 {
+        let total = self.features.len() + other.features.len() - shared;
+
+        if total == 0 {
+            0.0
+        } else {
+            shared as f64 / total as f64
+        }
+    }
+}
+
+/// Hash of question texts ignoring case, punctuation and spacing
+/// Returns None for questions without text
+fn question_key(texts: &[&str]) -> Option<u64> {
+    let normalized: String = texts
+        .iter()
+        .flat_map(|text| text.chars())
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    if normalized.is_empty() {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    normalized.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Package in a duplicate cluster
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateItem {
+    pub item_id: u64,
+    pub package_name: String,
+    pub hash: String,
+}
+
+/// Pair of duplicate packages
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicatePair {
+    pub first_item_id: u64,
+    pub second_item_id: u64,
+    /// 1.0 for identical files
+    pub score: f64,
+    pub exact: bool,
+}
+
+/// Group of packages that are duplicates of each other
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    pub items: Vec<DuplicateItem>,
+    /// Whether all package files are identical
+    pub exact: bool,
+    /// Lowest score of the pairs that link the cluster together
+    pub score: f64,
+    pub pairs: Vec<DuplicatePair>,
+}
+
+/// Group packages with identical files or a content similarity of at least `threshold`
+pub fn find_duplicates(fingerprints: &[(u64, PackageFingerprint)], threshold: f64) -> Vec<DuplicateCluster> {
+    // Count shared features through an inverted index instead of comparing every pair of packages
+    let mut packages_by_feature: HashMap<&Feature, Vec<usize>> = HashMap::new();
+
+    for (index, (_, fingerprint)) in fingerprints.iter().enumerate() {
+        for feature in &fingerprint.features {
+            packages_by_feature.entry(feature).or_default().push(index);
+        }
+    }
+
+    let mut shared_counts: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for packages in packages_by_feature.values() {
+        for (position, &first) in packages.iter().enumerate() {
+            for &second in &packages[position + 1..] {
+                *shared_counts.entry((first.min(second), first.max(second))).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut candidates: HashSet<(usize, usize)> = shared_counts.keys().copied().collect();
+
+    let mut packages_by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+
+    for (index, (_, fingerprint)) in fingerprints.iter().enumerate() {
+        packages_by_hash.entry(&fingerprint.hash).or_default().push(index);
+    }
+
+    for packages in packages_by_hash.values() {
+        for (position, &first) in packages.iter().enumerate() {
+            for &second in &packages[position + 1..] {
+                candidates.insert((first.min(second), first.max(second)));
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize, DuplicatePair)> = candidates
+        .into_iter()
+        .filter_map(|(first, second)| {
+            let (first_id, first_print) = &fingerprints[first];
+            let (second_id, second_print) = &fingerprints[second];
+
+            let exact = first_print.hash == second_print.hash;
+            let shared = shared_counts.get(&(first, second)).copied().unwrap_or(0);
+
+            let score = if exact {
+                1.0
+            } else {
+                first_print.similarity(second_print, shared)
+            };
+
+            (exact || score >= threshold).then_some((
+                first,
+                second,
+                DuplicatePair {
+                    first_item_id: *first_id,
+                    second_item_id: *second_id,
+                    score,
+                    exact,
+                },
+            ))
+        })
+        .collect();
+
+    pairs.sort_by_key(|(first, second, _)| (*first, *second));
+
+    // Link the pairs into clusters
+    let mut parents: Vec<usize> = (0..fingerprints.len()).collect();
+
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+
+        index
+    }
+
+    for (first, second, _) in &pairs {
+        let (first_root, second_root) = (root(&mut parents, *first), root(&mut parents, *second));
+        parents[second_root] = first_root;
+    }
+
+    let mut clusters: HashMap<usize, (Vec<usize>, Vec<DuplicatePair>)> = HashMap::new();
+
+    for (first, _, pair) in pairs {
+        let cluster_root = root(&mut parents, first);
+        clusters.entry(cluster_root).or_default().1.push(pair);
+    }
+
+    for index in 0..fingerprints.len() {
+        let cluster_root = root(&mut parents, index);
+
+        if let Some((members, _)) = clusters.get_mut(&cluster_root) {
+            members.push(index);
+        }
+    }
+
+    let mut clusters: Vec<DuplicateCluster> = clusters
+        .into_values()
+        .map(|(members, pairs)| {
+            let items: Vec<DuplicateItem> = members
+                .iter()
+                .map(|&index| {
+                    let (item_id, fingerprint) = &fingerprints[index];
+
+                    DuplicateItem {
+                        item_id: *item_id,
+                        package_name: fingerprint.package_name.clone(),
+                        hash: fingerprint.hash.clone(),
+                    }
+                })
+                .collect();
+
+            DuplicateCluster {
+                exact: items.iter().all(|item| item.hash == items[0].hash),
+                score: pairs.iter().map(|pair| pair.score).fold(1.0, f64::min),
+                items,
+                pairs,
+            }
+        })
+        .collect();
+
+    clusters.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.items[0].item_id.cmp(&b.items[0].item_id))
+    });
+
+    clusters
+}
+
+/// Fingerprints of installed Workshop items
+/// An entry is valid as long as the item has not been updated
+#[derive(Default)]
+pub struct FingerprintCache {
+    entries: Mutex<HashMap<u64, (u32, PackageFingerprint)>>,
+}
+
+impl FingerprintCache {
+    /// Get the fingerprint of a Workshop item package, reading the package if needed
+    /// Returns None if the package cannot be read
+    pub fn get(&self, item_id: u64, updated_time: u32, package_path: &Path) -> Option<PackageFingerprint> {
+        if let Some((cached_time, fingerprint)) = self.entries.lock().ok()?.get(&item_id) {
+            if *cached_time == updated_time {
+                return Some(fingerprint.clone());
+            }
+        }
+
+        let fingerprint = PackageFingerprint::read(package_path)
+            .map_err(|e| log::warn!("Failed to fingerprint package {}: {}", package_path.display(), e))
+            .ok()?;
+
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(item_id, (updated_time, fingerprint.clone()));
+        }
+
+        Some(fingerprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(hash: &str, questions: &[&str], media: &[&str]) -> PackageFingerprint {
+        PackageFingerprint {
+            package_name: format!("Package {}", hash),
+            hash: hash.to_string(),
+            features: questions
+                .iter()
+                .filter_map(|text| question_key(&[text]).map(Feature::Question))
+                .chain(media.iter().map(|hash| Feature::Media(hash.to_string())))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_find_duplicates_groups_exact_and_similar_packages() {
+        let questions = ["Capital of France? Paris", "Largest planet? Jupiter", "H2O? Water", "Fastest animal? Cheetah"];
+
+        let fingerprints = vec![
+            (1, fingerprint("a", &questions, &["m1"])),
+            (2, fingerprint("a", &questions, &["m1"])),
+            // Re-upload with a retyped question and one more media file
+            (3, fingerprint("b", &["capital of france  paris!", "Largest planet? Jupiter", "H2O? Water", "Fastest animal? Cheetah"], &["m1", "m2"])),
+            (4, fingerprint("c", &["Capital of France? Paris", "Something else"], &["m3"])),
+            (5, fingerprint("d", &["Unrelated"], &[])),
+        ];
+
+        let clusters = find_duplicates(&fingerprints, DEFAULT_SIMILARITY_THRESHOLD);
+
+        assert_eq!(clusters.len(), 1);
+
+        let cluster = &clusters[0];
+        let mut item_ids: Vec<u64> = cluster.items.iter().map(|item| item.item_id).collect();
+        item_ids.sort();
+
+        assert_eq!(item_ids, vec![1, 2, 3]);
+        assert!(!cluster.exact);
+        assert_eq!(cluster.score, 5.0 / 6.0);
+        assert_eq!(cluster.pairs.len(), 3);
+        assert!(cluster.pairs.iter().any(|pair| pair.exact && pair.score == 1.0));
+
+        let clusters = find_duplicates(&fingerprints, 0.1);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].items.len(), 4);
+    }
+
+    #[test]
+    fn test_identical_files_without_content_are_duplicates() {
+        let fingerprints = vec![(1, fingerprint("a", &[], &[])), (2, fingerprint("a", &[], &[])), (3, fingerprint("b", &[], &[]))];
+
+        let clusters = find_duplicates(&fingerprints, DEFAULT_SIMILARITY_THRESHOLD);
+
+        assert_eq!(clusters.len(), 1);
+        assert!(clusters[0].exact);
+        assert_eq!(clusters[0].score, 1.0);
+        assert_eq!(clusters[0].items.len(), 2);
+    }
+}
@@ -0,0 +1,471 @@
+//! Full-text index of installed packages.
+//!
+//! This module provides functionality to:
+//! - Index package names, authors and tags, round and theme names, question texts and answers
+//! - Update the index incrementally when installed Workshop items are added, updated or removed
+//! - Search the index and return package, round, theme and question hits with snippets
+//!
+//! The index is stored as JSON in the app cache directory. A missing, corrupt or outdated
+//! index file is treated as empty, so every package is indexed again.
+
+use crate::siq::{ContentType, Package, SiqPackage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Version of the index file format
+const INDEX_VERSION: u32 = 1;
+
+/// Number of characters shown before and after a match in a snippet
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Default number of returned hits
+pub const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+/// Kind of an indexed document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitKind {
+    Package,
+    Round,
+    Theme,
+    Question,
+}
+
+/// Searchable part of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Document {
+    kind: HitKind,
+    round: Option<usize>,
+    theme: Option<usize>,
+    question: Option<usize>,
+    text: String,
+    /// Lowercase text with the same characters as `text`
+    #[serde(skip)]
+    normalized: String,
+}
+
+impl Document {
+    fn new(kind: HitKind, location: (Option<usize>, Option<usize>, Option<usize>), parts: &[&str]) -> Option<Self> {
+        let text = parts
+            .iter()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" · ");
+
+        if text.is_empty() {
+            return None;
+        }
+
+        let (round, theme, question) = location;
+
+        Some(Self {
+            kind,
+            round,
+            theme,
+            question,
+            normalized: normalize(&text),
+            text,
+        })
+    }
+}
+
+/// Indexed package of an installed Workshop item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedPackage {
+    /// Time the Workshop item was last updated
+    updated_time: u32,
+    name: String,
+    round_names: Vec<String>,
+    /// Theme names by round
+    theme_names: Vec<Vec<String>>,
+    /// Question prices by round and theme
+    question_prices: Vec<Vec<Vec<i32>>>,
+    documents: Vec<Document>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexData {
+    version: u32,
+    /// Indexed packages by Workshop item id
+    packages: HashMap<u64, IndexedPackage>,
+}
+
+/// Package of an installed Workshop item
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub item_id: u64,
+    pub updated_time: u32,
+    pub path: PathBuf,
+}
+
+/// Search result
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub item_id: u64,
+    pub package_name: String,
+    pub kind: HitKind,
+    pub round: Option<usize>,
+    pub round_name: Option<String>,
+    pub theme: Option<usize>,
+    pub theme_name: Option<String>,
+    pub question: Option<usize>,
+    pub question_price: Option<i32>,
+    /// Matched text with some context around the first match
+    pub snippet: String,
+    pub score: usize,
+}
+
+/// Local package index
+pub struct PackageIndex {
+    path: PathBuf,
+    data: IndexData,
+}
+
+impl PackageIndex {
+    /// Load the index from a file
+    pub fn load(path: PathBuf) -> Self {
+        let mut data = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<IndexData>(&bytes).ok())
+            .filter(|data| data.version == INDEX_VERSION)
+            .unwrap_or_else(|| IndexData {
+                version: INDEX_VERSION,
+                ..IndexData::default()
+            });
+
+        for document in data.packages.values_mut().flat_map(|package| &mut package.documents) {
+            document.normalized = normalize(&document.text);
+        }
+
+        Self { path, data }
+    }
+
+    /// Save the index to its file
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let bytes = serde_json::to_vec(&self.data)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated index
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, bytes)?;
+        std::fs::rename(&temp_path, &self.path)
+    }
+
+    /// Bring the index up to date with the installed packages
+    /// Only new and updated packages are read; returns whether the index has changed
+    pub fn update(&mut self, installed: &[InstalledPackage]) -> bool {
+        let installed_ids: HashSet<u64> = installed.iter().map(|package| package.item_id).collect();
+        let package_count = self.data.packages.len();
+
+        self.data.packages.retain(|item_id, _| installed_ids.contains(item_id));
+
+        let mut changed = self.data.packages.len() != package_count;
+
+        for package in installed {
+            let is_current = self
+                .data
+                .packages
+                .get(&package.item_id)
+                .is_some_and(|indexed| indexed.updated_time == package.updated_time);
+
+            if is_current {
+                continue;
+            }
+
+            match SiqPackage::open(&package.path) {
+                Ok(siq) => {
+                    log::info!("Indexing package {}", package.path.display());
+
+                    self.data
+                        .packages
+                        .insert(package.item_id, index_package(&siq.package, package.updated_time));
+                }
+                Err(e) => {
+                    log::warn!("Failed to index package {}: {}", package.path.display(), e);
+
+                    // Remember the unreadable package, so it is not read again until it is updated
+                    self.data.packages.insert(
+                        package.item_id,
+                        index_package(&Package::default(), package.updated_time),
+                    );
+                }
+            }
+
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Find documents containing every word of the query
+    /// Hits are ordered by the number of matches
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let terms: Vec<String> = normalize(query)
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits = Vec::new();
+
+        for (item_id, package) in &self.data.packages {
+            for document in &package.documents {
+                let mut score = 0;
+
+                for term in &terms {
+                    match document.normalized.matches(term.as_str()).count() {
+                        0 => {
+                            score = 0;
+                            break;
+                        }
+                        count => score += count,
+                    }
+                }
+
+                if score > 0 {
+                    hits.push(package.hit(*item_id, document, &terms[0], score));
+                }
+            }
+        }
+
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.package_name.cmp(&b.package_name))
+                .then_with(|| (a.round, a.theme, a.question).cmp(&(b.round, b.theme, b.question)))
+        });
+
+        hits.truncate(limit);
+        hits
+    }
+}
+
+impl IndexedPackage {
+    fn hit(&self, item_id: u64, document: &Document, term: &str, score: usize) -> SearchHit {
+        let round_name = document.round.and_then(|round| self.round_names.get(round)).cloned();
+
+        let theme_name = document
+            .round
+            .zip(document.theme)
+            .and_then(|(round, theme)| self.theme_names.get(round)?.get(theme))
+            .cloned();
+
+        let question_price = document
+            .round
+            .zip(document.theme)
+            .zip(document.question)
+            .and_then(|((round, theme), question)| self.question_prices.get(round)?.get(theme)?.get(question))
+            .copied();
+
+        SearchHit {
+            item_id,
+            package_name: self.name.clone(),
+            kind: document.kind,
+            round: document.round,
+            round_name,
+            theme: document.theme,
+            theme_name,
+            question: document.question,
+            question_price,
+            snippet: snippet(document, term),
+            score,
+        }
+    }
+}
+
+/// Build the searchable documents of a package
+fn index_package(package: &Package, updated_time: u32) -> IndexedPackage {
+    let mut documents = Vec::new();
+
+    let package_parts: Vec<&str> = std::iter::once(package.name.as_str())
+        .chain(package.info.authors.iter().map(String::as_str))
+        .chain(package.tags.iter().map(String::as_str))
+        .collect();
+
+    documents.extend(Document::new(HitKind::Package, (None, None, None), &package_parts));
+
+    for (round_index, round) in package.rounds.iter().enumerate() {
+        documents.extend(Document::new(HitKind::Round, (Some(round_index), None, None), &[&round.name]));
+
+        for (theme_index, theme) in round.themes.iter().enumerate() {
+            let theme_parts: Vec<&str> = std::iter::once(theme.name.as_str())
+                .chain(theme.info.authors.iter().map(String::as_str))
+                .collect();
+
+            documents.extend(Document::new(
+                HitKind::Theme,
+                (Some(round_index), Some(theme_index), None),
+                &theme_parts,
+            ));
+
+            for (question_index, question) in theme.questions.iter().enumerate() {
+                let question_parts: Vec<&str> = question
+                    .content_items()
+                    .into_iter()
+                    .filter(|item| item.content_type == ContentType::Text && !item.is_ref)
+                    .map(|item| item.value.as_str())
+                    .chain(question.right.iter().map(String::as_str))
+                    .chain(question.wrong.iter().map(String::as_str))
+                    .chain(question.info.authors.iter().map(String::as_str))
+                    .collect();
+
+                documents.extend(Document::new(
+                    HitKind::Question,
+                    (Some(round_index), Some(theme_index), Some(question_index)),
+                    &question_parts,
+                ));
+            }
+        }
+    }
+
+    IndexedPackage {
+        updated_time,
+        name: package.name.clone(),
+        round_names: package.rounds.iter().map(|round| round.name.clone()).collect(),
+        theme_names: package
+            .rounds
+            .iter()
+            .map(|round| round.themes.iter().map(|theme| theme.name.clone()).collect())
+            .collect(),
+        question_prices: package
+            .rounds
+            .iter()
+            .map(|round| {
+                round
+                    .themes
+                    .iter()
+                    .map(|theme| theme.questions.iter().map(|question| question.price).collect())
+                    .collect()
+            })
+            .collect(),
+        documents,
+    }
+}
+
+/// Lowercase a text character by character, so positions in the result match the original
+/// `ё` is treated as `е`, as it is often written without the dots
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c.to_lowercase().next().unwrap_or(c) {
+            'ё' => 'е',
+            lower if lower.len_utf8() == c.len_utf8() => lower,
+            _ => c,
+        })
+        .collect()
+}
+
+/// Cut the text around the first match of a term
+fn snippet(document: &Document, term: &str) -> String {
+    let Some(byte_index) = document.normalized.find(term) else {
+        return document.text.chars().take(2 * SNIPPET_CONTEXT).collect();
+    };
+
+    let chars: Vec<char> = document.text.chars().collect();
+    let match_start = document.normalized[..byte_index].chars().count();
+    let match_end = match_start + term.chars().count();
+
+    let start = match_start.saturating_sub(SNIPPET_CONTEXT);
+    let end = (match_end + SNIPPET_CONTEXT).min(chars.len());
+
+    let mut snippet = String::new();
+
+    if start > 0 {
+        snippet.push('…');
+    }
+
+    snippet.extend(&chars[start..end]);
+
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::siq::parse_content;
+
+    const CONTENT: &str = r#"<package name="Science" version="5"><tags><tag>Physics</tag></tags><rounds>
+        <round name="First round"><themes>
+          <theme name="Planets"><questions>
+            <question price="100"><params><param name="question" type="content"><item>The largest planet of the Solar System</item></param></params><right><answer>Jupiter</answer></right></question>
+            <question price="200"><params><param name="question" type="content"><item>Ёлка растёт в лесу</item></param></params><right><answer>Ель</answer></right></question>
+          </questions></theme>
+        </themes></round>
+    </rounds></package>"#;
+
+    fn index(packages: &[(u64, u32)]) -> PackageIndex {
+        let mut index = PackageIndex {
+            path: PathBuf::new(),
+            data: IndexData::default(),
+        };
+
+        for (item_id, updated_time) in packages {
+            index
+                .data
+                .packages
+                .insert(*item_id, index_package(&parse_content(CONTENT).unwrap(), *updated_time));
+        }
+
+        index
+    }
+
+    #[test]
+    fn test_search_returns_located_hits_with_snippets() {
+        let index = index(&[(1, 10)]);
+
+        let hits = index.search("LARGEST planet", DEFAULT_SEARCH_LIMIT);
+        assert_eq!(hits.len(), 1);
+
+        let hit = &hits[0];
+        assert_eq!(hit.kind, HitKind::Question);
+        assert_eq!((hit.round, hit.theme, hit.question), (Some(0), Some(0), Some(0)));
+        assert_eq!(hit.round_name.as_deref(), Some("First round"));
+        assert_eq!(hit.theme_name.as_deref(), Some("Planets"));
+        assert_eq!(hit.question_price, Some(100));
+        assert!(hit.snippet.contains("largest planet of the Solar System · Jupiter"));
+
+        // Themes and package tags are searched as well; "planet" is also part of the theme name
+        let kinds: Vec<HitKind> = index.search("planet", DEFAULT_SEARCH_LIMIT).iter().map(|hit| hit.kind).collect();
+        assert_eq!(kinds, vec![HitKind::Theme, HitKind::Question]);
+        assert_eq!(index.search("physics", DEFAULT_SEARCH_LIMIT)[0].kind, HitKind::Package);
+
+        let hits = index.search("елка", DEFAULT_SEARCH_LIMIT);
+        assert_eq!(hits[0].snippet, "Ёлка растёт в лесу · Ель");
+
+        assert!(index.search("jupiter saturn", DEFAULT_SEARCH_LIMIT).is_empty());
+        assert!(index.search("  ", DEFAULT_SEARCH_LIMIT).is_empty());
+    }
+
+    #[test]
+    fn test_update_reindexes_changed_packages_only() {
+        let path = std::env::temp_dir().join(format!("sigame-index-{}.siq", std::process::id()));
+        std::fs::write(&path, b"not a zip").unwrap();
+
+        let mut index = index(&[(1, 10), (2, 20)]);
+        let installed = |item_id, updated_time| InstalledPackage { item_id, updated_time, path: path.clone() };
+
+        // Unchanged packages are not read again, removed ones are dropped
+        assert!(index.update(&[installed(1, 10)]));
+        assert!(!index.update(&[installed(1, 10)]));
+        assert_eq!(index.search("jupiter", DEFAULT_SEARCH_LIMIT).len(), 1);
+
+        // An updated package is read again (and is empty here, as the file is not a package)
+        assert!(index.update(&[installed(1, 11)]));
+        assert!(!index.update(&[installed(1, 11)]));
+        assert!(index.search("jupiter", DEFAULT_SEARCH_LIMIT).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,472 @@
+//! Optimization of package media.
+//!
+//! This module provides functionality to:
+//! - Downscale JPEG and PNG images that exceed the configured dimensions
+//! - Rewrite the SIQ archive with the re-encoded images
+//! - Report the number of bytes saved
+//!
+//! Entry names and all other entries (including `content.xml`) are copied unchanged and images keep
+//! their format, so every reference in `content.xml` stays valid. A re-encoded image only replaces
+//! the original if it is smaller. The EXIF orientation is applied to the pixels before downscaling,
+//! because re-encoding drops it; images with an ICC color profile are kept unchanged for the same reason.
+
+use crate::archive::{ArchiveError, SafeArchive};
+use crate::siq::{ContentType, SiqError, SiqPackage};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+/// Images with a larger width or height are not decoded
+const MAX_IMAGE_DIMENSION: u32 = 16_384;
+
+/// Optimization settings
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct OptimizeOptions {
+    /// Maximum width of an image in pixels
+    pub max_width: u32,
+    /// Maximum height of an image in pixels
+    pub max_height: u32,
+    /// Quality of re-encoded JPEG images (1-100)
+    pub jpeg_quality: u8,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 1920,
+            max_height: 1080,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/// Error optimizing a package
+#[derive(Debug)]
+pub enum OptimizeError {
+    /// The package cannot be read
+    Package(SiqError),
+    IoError(String),
+    /// The optimized archive cannot be written
+    ZipError(String),
+    /// The optimized package must not be written to the destination
+    InvalidDestination(String),
+}
+
+impl std::fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizeError::Package(e) => write!(f, "{}", e),
+            OptimizeError::IoError(msg) => write!(f, "IO error: {}", msg),
+            OptimizeError::ZipError(msg) => write!(f, "Failed to write package: {}", msg),
+            OptimizeError::InvalidDestination(msg) => write!(f, "Invalid destination: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {}
+
+impl From<SiqError> for OptimizeError {
+    fn from(error: SiqError) -> Self {
+        OptimizeError::Package(error)
+    }
+}
+
+impl From<ArchiveError> for OptimizeError {
+    fn from(error: ArchiveError) -> Self {
+        OptimizeError::Package(SiqError::Archive(error))
+    }
+}
+
+impl From<std::io::Error> for OptimizeError {
+    fn from(error: std::io::Error) -> Self {
+        OptimizeError::IoError(error.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for OptimizeError {
+    fn from(error: zip::result::ZipError) -> Self {
+        OptimizeError::ZipError(error.to_string())
+    }
+}
+
+/// Re-encoded image
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizedImage {
+    /// Path of the image in the archive
+    pub path: String,
+    pub original_size: u64,
+    pub optimized_size: u64,
+}
+
+/// Result of a package optimization
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizationReport {
+    /// Size of the package file before the optimization
+    pub original_size: u64,
+    /// Size of the package file after the optimization
+    pub optimized_size: u64,
+    pub bytes_saved: u64,
+    pub images: Vec<OptimizedImage>,
+}
+
+/// Check the destination of an optimized package
+/// The destination must be a `.siq` file outside the install folder of a Workshop item,
+/// because Steam owns that folder and would overwrite or report the changed package
+pub fn check_destination(destination: &Path, install_folder: Option<&Path>) -> Result<(), OptimizeError> {
+    let is_siq = destination
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("siq"));
+
+    if !is_siq {
+        return Err(OptimizeError::InvalidDestination(format!("{} is not a .siq file", destination.display())));
+    }
+
+    if let Some(install_folder) = install_folder {
+        if resolve(destination).starts_with(resolve(install_folder)) {
+            return Err(OptimizeError::InvalidDestination(format!(
+                "{} is inside the install folder of the Workshop item",
+                destination.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve links and relative components of a path that may not exist yet
+fn resolve(path: &Path) -> std::path::PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+
+    match (path.parent().and_then(|parent| parent.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(file_name)) => parent.join(file_name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Optimize a package file
+/// The destination may be the source itself; it is only replaced once the optimized package is complete
+pub fn optimize_file(source: &Path, destination: &Path, options: &OptimizeOptions) -> Result<OptimizationReport, OptimizeError> {
+    let original_size = std::fs::metadata(source)?.len();
+    let temp_path = destination.with_extension("siq.optimizing");
+
+    let result = std::fs::File::create(&temp_path)
+        .map_err(OptimizeError::from)
+        .and_then(|file| {
+            let mut archive = SafeArchive::open(source)?;
+            let mut writer = std::io::BufWriter::new(file);
+
+            let images = optimize(&mut archive, &mut writer, options)?;
+            writer.flush()?;
+
+            Ok(images)
+        });
+
+    let images = match result {
+        Ok(images) => images,
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+
+    std::fs::rename(&temp_path, destination)?;
+
+    let optimized_size = std::fs::metadata(destination)?.len();
+
+    Ok(OptimizationReport {
+        original_size,
+        optimized_size,
+        bytes_saved: original_size.saturating_sub(optimized_size),
+        images,
+    })
+}
+
+/// Copy a package into a new archive, downscaling oversized images
+pub fn optimize<R: Read + Seek, W: Write + Seek>(
+    archive: &mut SafeArchive<R>,
+    writer: W,
+    options: &OptimizeOptions,
+) -> Result<Vec<OptimizedImage>, OptimizeError> {
+    // Only valid packages are optimized
+    let siq = SiqPackage::from_archive(archive)?;
+
+    let mut zip = zip::ZipWriter::new(writer);
+    let mut images = Vec::new();
+
+    for entry in archive.entries().to_vec() {
+        let media = siq.media.iter().find(|media| media.path == entry.name);
+
+        // Audio and video formats are already compressed
+        let compression = match media.map(|media| media.content_type) {
+            Some(ContentType::Audio | ContentType::Video) => CompressionMethod::Stored,
+            _ => CompressionMethod::Deflated,
+        };
+
+        let file_options = SimpleFileOptions::default()
+            .compression_method(compression)
+            .large_file(entry.size >= u64::from(u32::MAX));
+
+        if entry.is_dir {
+            zip.add_directory(entry.name.as_str(), file_options)?;
+            continue;
+        }
+
+        if media.is_some_and(|media| media.content_type == ContentType::Image) {
+            let data = archive.read_entry(&entry.name)?;
+
+            let optimized = optimize_image(&data, options)
+                .inspect_err(|e| log::warn!("Keeping image {} unchanged: {}", entry.name, e))
+                .ok()
+                .flatten()
+                .filter(|optimized| optimized.len() < data.len());
+
+            zip.start_file(entry.name.as_str(), file_options)?;
+
+            match optimized {
+                Some(optimized) => {
+                    zip.write_all(&optimized)?;
+
+                    images.push(OptimizedImage {
+                        path: entry.name.clone(),
+                        original_size: data.len() as u64,
+                        optimized_size: optimized.len() as u64,
+                    });
+                }
+                None => zip.write_all(&data)?,
+            }
+
+            continue;
+        }
+
+        zip.start_file(entry.name.as_str(), file_options)?;
+        std::io::copy(&mut archive.entry_reader(&entry.name)?, &mut zip)?;
+    }
+
+    zip.finish()?;
+
+    Ok(images)
+}
+
+/// Downscale an image that exceeds the maximum dimensions, keeping its format
+/// Returns None for images that fit, have a color profile, or are not JPEG or PNG
+fn optimize_image(data: &[u8], options: &OptimizeOptions) -> Result<Option<Vec<u8>>, image::ImageError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+
+    let format = match reader.format() {
+        Some(format @ (ImageFormat::Jpeg | ImageFormat::Png)) => format,
+        _ => return Ok(None),
+    };
+
+    let mut decoder = reader.into_decoder()?;
+
+    // The encoders would drop the profile and shift the colors
+    if decoder.icc_profile()?.is_some() {
+        return Ok(None);
+    }
+
+    let orientation = decoder.orientation()?;
+    let (width, height) = match orientation {
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH => {
+            let (width, height) = decoder.dimensions();
+            (height, width)
+        }
+        _ => decoder.dimensions(),
+    };
+
+    if width <= options.max_width && height <= options.max_height {
+        return Ok(None);
+    }
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let image = image.resize(options.max_width, options.max_height, FilterType::Lanczos3);
+
+    let mut optimized = Vec::new();
+
+    if format == ImageFormat::Jpeg {
+        let quality = options.jpeg_quality.clamp(1, 100);
+        image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut optimized, quality))?;
+    } else {
+        let encoder = PngEncoder::new_with_quality(&mut optimized, CompressionType::Best, PngFilterType::Adaptive);
+        image.write_with_encoder(encoder)?;
+    }
+
+    Ok(Some(optimized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ExtendedColorType, ImageEncoder, Rgb, RgbImage};
+
+    const CONTENT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package name="P" version="5"><rounds><round name="R"><themes><theme name="T"><questions>
+  <question price="100"><params><param name="question" type="content"><item type="image" isRef="True">big photo.jpg</item></param></params><right><answer>A</answer></right></question>
+  <question price="200"><params><param name="question" type="content"><item type="image" isRef="True">small.png</item></param></params><right><answer>B</answer></right></question>
+</questions></theme></themes></round></rounds></package>"#;
+
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        // A noisy image, so that it cannot be compressed much without downscaling
+        let mut seed = 12345u32;
+        let image = RgbImage::from_fn(width, height, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_be_bytes();
+            Rgb([r, g, b])
+        });
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    fn read_entries(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+
+        (0..archive.len())
+            .map(|index| {
+                let mut file = archive.by_index(index).unwrap();
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).unwrap();
+                (file.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_optimize_downscales_oversized_images_and_keeps_references() {
+        let small = encode(64, 64, ImageFormat::Png);
+        let mut source = Vec::new();
+
+        {
+            let mut writer = zip::ZipWriter::new(Cursor::new(&mut source));
+
+            for (name, data) in [
+                ("content.xml", CONTENT.as_bytes().to_vec()),
+                ("Images/big%20photo.jpg", encode(1600, 1200, ImageFormat::Jpeg)),
+                ("Images/small.png", small.clone()),
+            ] {
+                writer.start_file(name, SimpleFileOptions::default()).unwrap();
+                writer.write_all(&data).unwrap();
+            }
+
+            writer.finish().unwrap();
+        }
+
+        let mut archive = SafeArchive::new(Cursor::new(source.clone()), &Default::default()).unwrap();
+        let mut optimized = Cursor::new(Vec::new());
+        let options = OptimizeOptions { max_width: 400, max_height: 400, jpeg_quality: 80 };
+
+        let images = optimize(&mut archive, &mut optimized, &options).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].path, "Images/big%20photo.jpg");
+        assert!(images[0].optimized_size < images[0].original_size);
+
+        let entries = read_entries(optimized.get_ref());
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, read_entries(&source).iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>());
+
+        assert_eq!(entries[0].1, CONTENT.as_bytes());
+        assert_eq!(entries[2].1, small);
+
+        let photo = image::load_from_memory_with_format(&entries[1].1, ImageFormat::Jpeg).unwrap();
+        assert_eq!((photo.width(), photo.height()), (400, 300));
+    }
+
+    /// EXIF block with a single orientation tag
+    fn exif_orientation(orientation: u16) -> Vec<u8> {
+        let mut exif = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        exif.extend_from_slice(&orientation.to_be_bytes());
+        exif.extend_from_slice(&[0; 6]);
+        exif
+    }
+
+    fn encode_jpeg(width: u32, height: u32, metadata: impl FnOnce(&mut JpegEncoder<&mut Vec<u8>>)) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, _| if x < width / 2 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+        let mut data = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut data, 90);
+        metadata(&mut encoder);
+        encoder.write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_optimize_image_applies_orientation_and_keeps_color_profiles() {
+        let options = OptimizeOptions { max_width: 100, max_height: 100, jpeg_quality: 90 };
+
+        // Stored as 400x200 with the left half red, displayed rotated by 90 degrees clockwise
+        let rotated = encode_jpeg(400, 200, |encoder| encoder.set_exif_metadata(exif_orientation(6)).unwrap());
+        let optimized = optimize_image(&rotated, &options).unwrap().unwrap();
+        let image = image::load_from_memory_with_format(&optimized, ImageFormat::Jpeg).unwrap().to_rgb8();
+
+        assert_eq!(image.dimensions(), (50, 100));
+        assert!(image.get_pixel(25, 10)[0] > 200, "the top should be red");
+        assert!(image.get_pixel(25, 90)[2] > 200, "the bottom should be blue");
+
+        // Fits once rotated
+        let options = OptimizeOptions { max_width: 200, max_height: 400, jpeg_quality: 90 };
+        assert_eq!(optimize_image(&rotated, &options).unwrap(), None);
+
+        let profiled = encode_jpeg(400, 200, |encoder| encoder.set_icc_profile(vec![0; 128]).unwrap());
+        assert_eq!(optimize_image(&profiled, &OptimizeOptions { max_width: 100, ..options }).unwrap(), None);
+    }
+
+    #[test]
+    fn test_destination_must_be_siq_file_outside_install_folder() {
+        let install_folder = std::env::temp_dir().join(format!("sigame-optimize-item-{}", std::process::id()));
+        std::fs::create_dir_all(&install_folder).unwrap();
+
+        let inside = check_destination(&install_folder.join("package.siq"), Some(&install_folder));
+        let nested = check_destination(&install_folder.join("../").join(install_folder.file_name().unwrap()).join("a.siq"), Some(&install_folder));
+        let outside = check_destination(&std::env::temp_dir().join("optimized.siq"), Some(&install_folder));
+        std::fs::remove_dir_all(&install_folder).unwrap();
+
+        assert!(matches!(inside, Err(OptimizeError::InvalidDestination(_))));
+        assert!(matches!(nested, Err(OptimizeError::InvalidDestination(_))));
+        assert!(outside.is_ok());
+
+        // Local packages may be optimized in place
+        assert!(check_destination(Path::new("/packages/package.SIQ"), None).is_ok());
+        assert!(matches!(check_destination(Path::new("/packages/package.zip"), None), Err(OptimizeError::InvalidDestination(_))));
+        assert!(matches!(check_destination(Path::new("/packages/siq"), None), Err(OptimizeError::InvalidDestination(_))));
+    }
+
+    #[test]
+    fn test_optimize_file_replaces_package_in_place() {
+        let path = std::env::temp_dir().join(format!("sigame-optimize-{}.siq", std::process::id()));
+
+        {
+            let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            writer.start_file("content.xml", SimpleFileOptions::default()).unwrap();
+            writer.write_all(CONTENT.as_bytes()).unwrap();
+            writer.start_file("Images/small.png", SimpleFileOptions::default()).unwrap();
+            writer.write_all(&encode(1200, 300, ImageFormat::Png)).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let options = OptimizeOptions { max_width: 400, ..Default::default() };
+        let report = optimize_file(&path, &path, &options).unwrap();
+        let optimized_size = std::fs::metadata(&path).unwrap().len();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.images.len(), 1);
+        assert_eq!(report.optimized_size, optimized_size);
+        assert_eq!(report.bytes_saved, report.original_size - report.optimized_size);
+        assert!(report.bytes_saved > 0);
+    }
+}
@@ -0,0 +1,182 @@
+//! Statistics of package contents.
+//!
+//! This module provides functionality to:
+//! - Count rounds, themes and questions of a package
+//! - Count question content items by type (text, image, audio, video, html)
+//! - Sum the durations declared for media content
+//! - Cache the statistics of installed Workshop items until they are updated
+
+use crate::siq::{ContentType, Package, SiqPackage};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Statistics of a round
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundStatistics {
+    pub name: String,
+    pub theme_names: Vec<String>,
+    pub question_count: usize,
+}
+
+/// Statistics of a package
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageStatistics {
+    pub rounds: Vec<RoundStatistics>,
+    pub question_count: usize,
+    /// Number of question content items of every type
+    pub content_type_statistic: BTreeMap<ContentType, usize>,
+    /// Total duration of media content in seconds
+    /// Only durations declared in the package are counted
+    pub media_duration: f64,
+}
+
+/// Collect the statistics of a package
+pub fn collect(package: &Package) -> PackageStatistics {
+    let mut content_type_statistic = BTreeMap::new();
+    let mut media_duration = 0.0;
+
+    let rounds: Vec<RoundStatistics> = package
+        .rounds
+        .iter()
+        .map(|round| RoundStatistics {
+            name: round.name.clone(),
+            theme_names: round.themes.iter().map(|theme| theme.name.clone()).collect(),
+            question_count: round.themes.iter().map(|theme| theme.questions.len()).sum(),
+        })
+        .collect();
+
+    let questions = package
+        .rounds
+        .iter()
+        .flat_map(|round| &round.themes)
+        .flat_map(|theme| &theme.questions);
+
+    for question in questions {
+        for item in question.content_items() {
+            *content_type_statistic.entry(item.content_type).or_insert(0) += 1;
+
+            if item.content_type != ContentType::Text {
+                media_duration += item.duration.as_deref().and_then(parse_duration).unwrap_or_default();
+            }
+        }
+    }
+
+    PackageStatistics {
+        question_count: rounds.iter().map(|round| round.question_count).sum(),
+        rounds,
+        content_type_statistic,
+        media_duration,
+    }
+}
+
+/// Parse a duration in `hh:mm:ss`, `mm:ss` or `ss` format into seconds
+/// Seconds may have a fractional part
+fn parse_duration(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+
+    for (index, part) in value.trim().rsplit(':').enumerate() {
+        let multiplier = match index {
+            0 => 1.0,
+            1 => 60.0,
+            2 => 3600.0,
+            _ => return None,
+        };
+
+        let part: f64 = part.trim().parse().ok().filter(|part: &f64| part.is_finite() && *part >= 0.0)?;
+        seconds += part * multiplier;
+    }
+
+    Some(seconds)
+}
+
+/// Statistics of installed Workshop items
+/// An entry is valid as long as the item has not been updated
+#[derive(Default)]
+pub struct StatisticsCache {
+    entries: Mutex<HashMap<u64, (u32, Option<PackageStatistics>)>>,
+}
+
+impl StatisticsCache {
+    /// Get the statistics of a Workshop item package, reading the package if needed
+    /// Returns None if the package cannot be read
+    pub fn get(&self, item_id: u64, updated_time: u32, package_path: &Path) -> Option<PackageStatistics> {
+        if let Some((cached_time, statistics)) = self.entries.lock().ok()?.get(&item_id) {
+            if *cached_time == updated_time {
+                return statistics.clone();
+            }
+        }
+
+        // Unreadable packages are cached too, so they are not read again on every request
+        let statistics = match SiqPackage::open(package_path) {
+            Ok(siq) => Some(collect(&siq.package)),
+            Err(e) => {
+                log::warn!("Failed to read statistics of package {}: {}", package_path.display(), e);
+                None
+            }
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(item_id, (updated_time, statistics.clone()));
+        }
+
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::siq::parse_content;
+
+    #[test]
+    fn test_collect_statistics() {
+        let package = parse_content(
+            r#"<package name="P" version="5"><rounds>
+            <round name="R1"><themes>
+              <theme name="T1"><questions>
+                <question price="100"><params><param name="question" type="content">
+                  <item>Listen</item>
+                  <item type="audio" isRef="True" duration="00:01:30">song.mp3</item>
+                </param></params><right><answer>A</answer></right></question>
+                <question price="200"><params><param name="question" type="content"><item type="image" isRef="True">a.png</item></param></params><right><answer>B</answer></right></question>
+              </questions></theme>
+              <theme name="T2"><questions>
+                <question price="100"><params><param name="question" type="content"><item type="video" isRef="True" duration="12.5">v.mp4</item></param></params><right><answer>C</answer></right></question>
+              </questions></theme>
+            </themes></round>
+            <round name="Final" type="final"><themes><theme name="F"><questions /></theme></themes></round>
+        </rounds></package>"#,
+        )
+        .unwrap();
+
+        let statistics = collect(&package);
+
+        assert_eq!(statistics.question_count, 3);
+        assert_eq!(statistics.rounds.len(), 2);
+        assert_eq!(statistics.rounds[0].theme_names, vec!["T1", "T2"]);
+        assert_eq!(statistics.rounds[0].question_count, 3);
+        assert_eq!(statistics.rounds[1].question_count, 0);
+
+        let expected: BTreeMap<ContentType, usize> =
+            [(ContentType::Text, 1), (ContentType::Image, 1), (ContentType::Audio, 1), (ContentType::Video, 1)].into();
+        assert_eq!(statistics.content_type_statistic, expected);
+        assert_eq!(statistics.media_duration, 102.5);
+
+        assert_eq!(
+            serde_json::to_value(&statistics.content_type_statistic).unwrap(),
+            serde_json::json!({ "text": 1, "image": 1, "audio": 1, "video": 1 })
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("01:02:03"), Some(3723.0));
+        assert_eq!(parse_duration("02:03.5"), Some(123.5));
+        assert_eq!(parse_duration("7"), Some(7.0));
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+}
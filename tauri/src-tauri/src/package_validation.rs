@@ -0,0 +1,302 @@
+//! Validation of SIQ packages.
+//!
+//! This module provides functionality to:
+//! - Check that a package can be opened and its `content.xml` is well-formed
+//! - Check that every media file referenced from `content.xml` is stored in the package
+//! - Detect empty rounds and themes, duplicated question prices and questions without answers
+//!
+//! Every problem is reported as a diagnostic with a severity, a machine-readable code
+//! and the location (round, theme and question indices) it was found at.
+
+use crate::siq::{ContentType, SiqError, SiqPackage};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The package cannot be played correctly
+    Error,
+    /// The package can be played, but probably not as intended
+    Warning,
+}
+
+/// Location of a diagnostic in the package (indices are zero-based)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub round: Option<usize>,
+    pub theme: Option<usize>,
+    pub question: Option<usize>,
+}
+
+impl Location {
+    fn round(round: usize) -> Self {
+        Self { round: Some(round), ..Self::default() }
+    }
+
+    fn theme(round: usize, theme: usize) -> Self {
+        Self { theme: Some(theme), ..Self::round(round) }
+    }
+
+    fn question(round: usize, theme: usize, question: usize) -> Self {
+        Self { question: Some(question), ..Self::theme(round, theme) }
+    }
+}
+
+/// Single validation problem
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Machine-readable problem code, e.g. `missing_media`
+    pub code: &'static str,
+    pub message: String,
+    pub location: Location,
+}
+
+/// Result of a package validation
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    fn new(diagnostics: Vec<Diagnostic>) -> Self {
+        let error_count = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+
+        Self {
+            error_count,
+            warning_count: diagnostics.len() - error_count,
+            diagnostics,
+        }
+    }
+
+    /// Whether the package has problems that prevent playing it
+    pub fn has_errors(&self) -> bool {
+        self.error_count > 0
+    }
+
+    /// Describe the errors of the package in a single line
+    pub fn error_summary(&self) -> String {
+        let errors: Vec<&str> = self
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+
+        match errors.as_slice() {
+            [] => "Package is valid".to_string(),
+            [error] => error.to_string(),
+            [first, rest @ ..] => format!("{} (and {} more errors)", first, rest.len()),
+        }
+    }
+}
+
+/// Open and validate a package file
+pub fn validate_file(path: &Path) -> ValidationReport {
+    match SiqPackage::open(path) {
+        Ok(siq) => ValidationReport::new(validate(&siq)),
+        Err(e) => {
+            let code = match &e {
+                SiqError::Archive(archive_error) => archive_error.kind(),
+                SiqError::MissingContent => "missing_content",
+                SiqError::XmlError(_) => "malformed_xml",
+            };
+
+            ValidationReport::new(vec![Diagnostic {
+                severity: Severity::Error,
+                code,
+                message: e.to_string(),
+                location: Location::default(),
+            }])
+        }
+    }
+}
+
+/// Validate a parsed package
+pub fn validate(siq: &SiqPackage) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |severity, code, location, message: String| {
+        diagnostics.push(Diagnostic { severity, code, message, location });
+    };
+
+    let package = &siq.package;
+
+    if let Some(logo) = package.logo.as_deref().and_then(|logo| logo.strip_prefix('@')) {
+        if siq.find_media(ContentType::Image, logo).is_none() {
+            report(
+                Severity::Warning,
+                "missing_logo",
+                Location::default(),
+                format!("Package logo {} is missing", logo),
+            );
+        }
+    }
+
+    if package.rounds.is_empty() {
+        report(Severity::Error, "no_rounds", Location::default(), "Package has no rounds".to_string());
+    }
+
+    for (round_index, round) in package.rounds.iter().enumerate() {
+        let is_final = round.round_type == "final";
+
+        if round.themes.is_empty() {
+            report(
+                Severity::Warning,
+                "empty_round",
+                Location::round(round_index),
+                format!("Round \"{}\" has no themes", round.name),
+            );
+        }
+
+        for (theme_index, theme) in round.themes.iter().enumerate() {
+            if theme.questions.is_empty() {
+                report(
+                    Severity::Warning,
+                    "empty_theme",
+                    Location::theme(round_index, theme_index),
+                    format!("Theme \"{}\" has no questions", theme.name),
+                );
+            }
+
+            let mut prices = HashSet::new();
+
+            for (question_index, question) in theme.questions.iter().enumerate() {
+                let location = Location::question(round_index, theme_index, question_index);
+
+                // Negative prices mark empty cells, and final round prices are not shown
+                if !is_final && question.price >= 0 && !prices.insert(question.price) {
+                    report(
+                        Severity::Warning,
+                        "duplicate_price",
+                        location,
+                        format!("Theme \"{}\" has several questions priced {}", theme.name, question.price),
+                    );
+                }
+
+                if question.content_items().is_empty() {
+                    report(
+                        Severity::Warning,
+                        "empty_question",
+                        location,
+                        format!("Question {} of theme \"{}\" has no content", question.price, theme.name),
+                    );
+                }
+
+                if question.right.iter().all(|answer| answer.trim().is_empty()) {
+                    report(
+                        Severity::Warning,
+                        "missing_answer",
+                        location,
+                        format!("Question {} of theme \"{}\" has no right answer", question.price, theme.name),
+                    );
+                }
+
+                for item in question.content_items() {
+                    if !item.is_ref || item.content_type == ContentType::Text {
+                        continue;
+                    }
+
+                    if siq.find_media(item.content_type, &item.value).is_none() {
+                        report(
+                            Severity::Error,
+                            "missing_media",
+                            location,
+                            format!(
+                                "Media file {} used in question {} of theme \"{}\" is missing",
+                                item.value, question.price, theme.name
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::siq::{parse_content, MediaFile};
+
+    fn package(xml: &str, media: &[(ContentType, &str)]) -> SiqPackage {
+        SiqPackage {
+            package: parse_content(xml).unwrap(),
+            media: media
+                .iter()
+                .map(|(content_type, name)| MediaFile {
+                    content_type: *content_type,
+                    name: name.to_string(),
+                    path: name.to_string(),
+                    size: 1,
+                })
+                .collect(),
+            is_quality_marked: false,
+        }
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&'static str> {
+        diagnostics.iter().map(|d| d.code).collect()
+    }
+
+    const CONTENT: &str = r#"<package name="P" version="5"><rounds>
+        <round name="R1"><themes>
+          <theme name="T1"><questions>
+            <question price="100"><params><param name="question" type="content"><item type="image" isRef="True">a.png</item></param></params><right><answer>A</answer></right></question>
+            <question price="100"><params><param name="question" type="content"><item type="video" isRef="True">b.mp4</item></param></params><right><answer>B</answer></right></question>
+          </questions></theme>
+          <theme name="T2"><questions /></theme>
+        </themes></round>
+        <round name="Final" type="final"><themes><theme name="F"><questions>
+          <question price="0"><params><param name="question" type="content"><item>Text</item></param></params><right><answer /></right></question>
+        </questions></theme></themes></round>
+    </rounds></package>"#;
+
+    #[test]
+    fn test_validate_reports_problems_with_locations() {
+        let diagnostics = validate(&package(CONTENT, &[(ContentType::Image, "a.png")]));
+
+        assert_eq!(codes(&diagnostics), vec!["duplicate_price", "missing_media", "empty_theme", "missing_answer"]);
+
+        let missing_media = &diagnostics[1];
+        assert_eq!(missing_media.severity, Severity::Error);
+        assert_eq!(missing_media.location, Location::question(0, 0, 1));
+        assert!(missing_media.message.contains("b.mp4"));
+
+        assert_eq!(diagnostics[2].location, Location::theme(0, 1));
+        assert_eq!(diagnostics[3].location, Location::question(1, 0, 0));
+    }
+
+    #[test]
+    fn test_report_counts_errors() {
+        let media = [(ContentType::Image, "a.png"), (ContentType::Video, "b.mp4")];
+        let report = ValidationReport::new(validate(&package(CONTENT, &media)));
+
+        assert!(!report.has_errors());
+        assert_eq!(report.warning_count, 3);
+
+        let report = ValidationReport::new(validate(&package(CONTENT, &[])));
+
+        assert!(report.has_errors());
+        assert_eq!(report.error_count, 2);
+        assert!(report.error_summary().ends_with("(and 1 more errors)"));
+    }
+
+    #[test]
+    fn test_validate_file_reports_unreadable_package() {
+        let path = std::env::temp_dir().join(format!("sigame-invalid-{}.siq", std::process::id()));
+        std::fs::write(&path, b"not a zip").unwrap();
+
+        let report = validate_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(codes(&report.diagnostics), vec!["invalid_archive"]);
+        assert!(report.has_errors());
+    }
+}
@@ -0,0 +1,759 @@
+//! Responses of the `sigame` protocol.
+//!
+//! This module provides functionality to:
+//! - Route protocol requests to installed Workshop items, cached packages and thumbnails
+//! - Serve package files, individual media files stored inside packages and generated thumbnails
+//! - Answer HTTP `Range` requests with partial content, reading only the requested bytes
+//! - Answer conditional requests with `304 Not Modified` based on the package update time
+//!
+//! Responses are built without depending on Tauri, so they can be tested directly;
+//! `lib.rs` only converts them into protocol responses.
+
+use crate::archive::{self, ArchiveError, ArchiveLimits, SafeArchive};
+use crate::media::mime_type_from_extension;
+use crate::package_cache::PackageCache;
+use crate::siq::{decode_media_file_name, ContentType};
+use crate::steam_backend::SteamBackend;
+use crate::thumbnails::ThumbnailCache;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Maximum number of bytes served for an open-ended range (`bytes=N-`)
+/// Clients continue with the next range, so large files are never read at once
+pub const MAX_RANGE_CHUNK: u64 = 8 * 1024 * 1024;
+
+/// Path and ETag of the last package that passed the archive checks
+/// Large packages are loaded with many range requests, so each version of a package is only checked once
+static CHECKED_PACKAGE: Mutex<Option<(PathBuf, String)>> = Mutex::new(None);
+
+/// Request headers the protocol responses depend on
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestHeaders<'a> {
+    pub range: Option<&'a str>,
+    /// ETag the range belongs to; the whole content is served if it has changed
+    pub if_range: Option<&'a str>,
+    pub if_none_match: Option<&'a str>,
+    pub if_modified_since: Option<&'a str>,
+}
+
+/// Protocol request
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProtocolRequest<'a> {
+    /// Path of the request URI, e.g. `/package/42/media/Images/cat.png`
+    pub path: &'a str,
+    /// Query of the request URI without the leading `?`
+    pub query: Option<&'a str>,
+    pub headers: RequestHeaders<'a>,
+}
+
+/// Protocol response
+#[derive(Debug)]
+pub struct ProtocolResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl ProtocolResponse {
+    fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![("Access-Control-Allow-Origin", "*".to_string())],
+            body: Vec::new(),
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Response for a request that cannot be served
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::new(status).body(message.into().into_bytes())
+    }
+}
+
+/// Inclusive byte range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Number of bytes in the range
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Range requested by a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// The whole content (no range, or a range the server may ignore)
+    Full,
+    Partial(ByteRange),
+    /// The range lies outside the content
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for content of the given size
+/// Only single ranges are supported; requests for several ranges get the whole content
+pub fn parse_range(header: Option<&str>, size: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        // Suffix range: the last N bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(suffix) => ByteRange { start: size.saturating_sub(suffix), end: size.saturating_sub(1) },
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+
+            let end = match end {
+                "" => size.saturating_sub(1).min(start.saturating_add(MAX_RANGE_CHUNK - 1)),
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+                    _ => return RangeRequest::Full,
+                },
+            };
+
+            ByteRange { start, end }
+        }
+    };
+
+    if size == 0 || range.start >= size {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(range)
+    }
+}
+
+/// Answer a protocol request
+pub fn handle_request(
+    steam: &dyn SteamBackend,
+    package_cache: &PackageCache,
+    thumbnail_cache: &ThumbnailCache,
+    request: &ProtocolRequest,
+) -> ProtocolResponse {
+    let not_found = || ProtocolResponse::error(404, "Not found");
+
+    // Packages downloaded from the content service: /cache/{escaped hash}
+    if let Some(cache_key) = request.path.strip_prefix("/cache/") {
+        return match package_cache.find(cache_key) {
+            Some(file_path) => package_response(&file_path, None, &request.headers),
+            None => not_found(),
+        };
+    }
+
+    // Generated package thumbnails: /thumbnails/{escaped hash}/{file}
+    if let Some(rest) = request.path.strip_prefix("/thumbnails/") {
+        let file_path = rest
+            .split_once('/')
+            .and_then(|(key, file_name)| thumbnail_cache.file_path(key, file_name));
+
+        return match file_path {
+            Some(file_path) => immutable_file_response(&file_path, "image/jpeg"),
+            None => not_found(),
+        };
+    }
+
+    // Media files inside a package: /package/{id}/media/{folder}/{file}
+    if let Some(rest) = request.path.strip_prefix("/package/") {
+        let package_path = rest.split_once("/media/").and_then(|(package_id, media_path)| {
+            find_package_file(steam, package_cache, package_id).map(|package_path| (package_path, media_path))
+        });
+
+        return match package_path {
+            Some((package_path, media_path)) => media_response(&package_path, media_path, request.headers.range),
+            None => not_found(),
+        };
+    }
+
+    // Installed Workshop items: ?id={item id}
+    let Some(item_id) = request
+        .query
+        .and_then(|query| query.strip_prefix("id="))
+        .and_then(|id| id.parse::<u64>().ok())
+    else {
+        return not_found();
+    };
+
+    log::info!("Custom protocol request for file ID: {}", item_id);
+
+    match steam.item_install_info(item_id) {
+        Some(info) => package_response(
+            &Path::new(&info.folder).join("package.siq"),
+            Some(u64::from(info.timestamp)),
+            &request.headers,
+        ),
+        None => not_found(),
+    }
+}
+
+/// Find the file of a package addressed by a Workshop item ID or a package cache key
+fn find_package_file(steam: &dyn SteamBackend, package_cache: &PackageCache, package_id: &str) -> Option<PathBuf> {
+    match package_id.parse::<u64>() {
+        Ok(item_id) => steam
+            .item_install_info(item_id)
+            .map(|info| Path::new(&info.folder).join("package.siq")),
+        Err(_) => package_cache.find(package_id),
+    }
+}
+
+/// Serve a package file
+/// `updated_time` is the Unix time the package was last updated; the file modification time is used without it
+pub fn package_response(path: &Path, updated_time: Option<u64>, request: &RequestHeaders) -> ProtocolResponse {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => {
+            log::error!("File not found: {}", path.display());
+            return ProtocolResponse::error(404, "Package not found");
+        }
+    };
+
+    let size = metadata.len();
+    let updated_time = updated_time.or_else(|| {
+        let modified = metadata.modified().ok()?;
+        modified.duration_since(UNIX_EPOCH).ok().map(|duration| duration.as_secs())
+    });
+
+    let etag = format!("\"{:x}-{:x}\"", updated_time.unwrap_or_default(), size);
+
+    // Packages are untrusted, so never hand a malicious archive to the webview
+    if let Err(e) = check_package(path, &etag) {
+        log::error!("Refusing to serve package {}: {}", path.display(), e);
+        return ProtocolResponse::error(422, e.to_string()).header("X-Error-Kind", e.kind());
+    }
+
+    let last_modified = updated_time.map(http_date);
+
+    let validators = |response: ProtocolResponse| {
+        let response = response
+            .header("ETag", etag.clone())
+            // Let the webview keep the package, but revalidate it on every load
+            .header("Cache-Control", "no-cache");
+
+        match &last_modified {
+            Some(last_modified) => response.header("Last-Modified", last_modified.clone()),
+            None => response,
+        }
+    };
+
+    if is_not_modified(request, &etag, last_modified.as_deref()) {
+        return validators(ProtocolResponse::new(304));
+    }
+
+    // A range of an outdated version of the package would corrupt the client's copy
+    let range_header = request.range.filter(|_| request.if_range.is_none_or(|if_range| if_range.trim() == etag));
+
+    let (status, range) = match parse_range(range_header, size) {
+        RangeRequest::Full => (200, None),
+        RangeRequest::Partial(range) => (206, Some(range)),
+        RangeRequest::Unsatisfiable => {
+            return ProtocolResponse::new(416).header("Content-Range", format!("bytes */{}", size));
+        }
+    };
+
+    let (start, length) = range.map_or((0, size), |range| (range.start, range.length()));
+
+    let body = File::open(path).and_then(|mut file| {
+        file.seek(SeekFrom::Start(start))?;
+
+        let mut body = Vec::with_capacity(length as usize);
+        file.take(length).read_to_end(&mut body)?;
+        Ok(body)
+    });
+
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to read file {}: {}", path.display(), e);
+            return ProtocolResponse::error(500, e.to_string());
+        }
+    };
+
+    let mut response = validators(ProtocolResponse::new(status))
+        .header("Content-Type", "application/x-zip-compressed")
+        .header("Content-Disposition", "attachment; filename=\"package.siq\"")
+        .header("Content-Length", body.len().to_string())
+        .header("Accept-Ranges", "bytes");
+
+    if let Some(range) = range {
+        response = response.header("Content-Range", format!("bytes {}-{}/{}", range.start, range.end, size));
+    }
+
+    response.body(body)
+}
+
+/// Serve a generated file that never changes at its URI
+pub fn immutable_file_response(path: &Path, content_type: &str) -> ProtocolResponse {
+    match std::fs::read(path) {
+        Ok(body) => ProtocolResponse::new(200)
+            .header("Content-Type", content_type)
+            .header("Content-Length", body.len().to_string())
+            .header("Cache-Control", "max-age=31536000, immutable")
+            .body(body),
+        Err(e) => {
+            log::error!("Failed to read file {}: {}", path.display(), e);
+            ProtocolResponse::error(404, "File not found")
+        }
+    }
+}
+
+/// Check a package archive unless this version of it has already been checked
+fn check_package(path: &Path, etag: &str) -> Result<(), archive::ArchiveError> {
+    let is_checked = |checked: &Option<(PathBuf, String)>| {
+        checked.as_ref().is_some_and(|(checked_path, checked_etag)| checked_path == path && checked_etag == etag)
+    };
+
+    if CHECKED_PACKAGE.lock().is_ok_and(|checked| is_checked(&checked)) {
+        return Ok(());
+    }
+
+    archive::check_file(path)?;
+
+    if let Ok(mut checked) = CHECKED_PACKAGE.lock() {
+        *checked = Some((path.to_path_buf(), etag.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Whether the client already has the current version of the content
+fn is_not_modified(request: &RequestHeaders, etag: &str, last_modified: Option<&str>) -> bool {
+    // If-Modified-Since is only considered without If-None-Match
+    if let Some(if_none_match) = request.if_none_match {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    // Clients send back the Last-Modified value they received, so an exact match is enough
+    matches!((request.if_modified_since, last_modified), (Some(since), Some(modified)) if since.trim() == modified)
+}
+
+/// Format a Unix time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let days = time / 86_400;
+    let seconds = time % 86_400;
+
+    // Civil date from the number of days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// Parse an HTTP date in the format produced by `http_date` into a Unix time
+/// The obsolete RFC 850 and asctime formats are not supported
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let mut parts = value.split_whitespace();
+    let (_weekday, day, month, year, time, zone) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+
+    if parts.next().is_some() || zone != "GMT" || day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+
+    let day = day.parse::<u64>().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let year = year.parse::<u64>().ok().filter(|year| *year >= 1970)?;
+
+    let mut time_parts = time.split(':').map(|part| part.parse::<u64>().ok().filter(|_| part.len() == 2));
+    let (hours, minutes, seconds) = (time_parts.next()??, time_parts.next()??, time_parts.next()??);
+
+    if time_parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Number of days since 1970-01-01 from the civil date (Howard Hinnant's algorithm)
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/// Serve a media file stored in a package
+/// `media_path` is the URL path of the file in the package, e.g. `Video/clip%20one.mp4`
+pub fn media_response(package_path: &Path, media_path: &str, range_header: Option<&str>) -> ProtocolResponse {
+    media_response_with_limits(package_path, media_path, range_header, &ArchiveLimits::default())
+}
+
+fn media_response_with_limits(
+    package_path: &Path,
+    media_path: &str,
+    range_header: Option<&str>,
+    limits: &ArchiveLimits,
+) -> ProtocolResponse {
+    let Some((folder, encoded_name)) = media_path.split_once('/') else {
+        return ProtocolResponse::error(404, "Media file not found");
+    };
+
+    if ContentType::from_media_folder(folder).is_none() {
+        return ProtocolResponse::error(404, "Media file not found");
+    }
+
+    let archive = File::open(package_path)
+        .map_err(|e| ArchiveError::IoError(e.to_string()))
+        .and_then(|file| SafeArchive::new(BufReader::new(file), limits));
+
+    let mut archive = match archive {
+        Ok(archive) => archive,
+        Err(e) => {
+            log::error!("Failed to open package {}: {}", package_path.display(), e);
+            return ProtocolResponse::error(422, e.to_string()).header("X-Error-Kind", e.kind());
+        }
+    };
+
+    // Archive names are escaped by the package editor, so compare the decoded names
+    let name = decode_media_file_name(encoded_name);
+
+    let entry = archive.entries().iter().find(|entry| {
+        !entry.is_dir
+            && entry
+                .name
+                .strip_prefix(folder)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .is_some_and(|file_name| decode_media_file_name(file_name) == name)
+    });
+
+    let Some(entry) = entry.cloned() else {
+        return ProtocolResponse::error(404, "Media file not found");
+    };
+
+    let content_type = mime_type_from_extension(Path::new(&name)).unwrap_or("application/octet-stream");
+
+    let (status, range) = match parse_range(range_header, entry.size) {
+        // Open-ended ranges are capped, so only whole entries can exceed the memory limit
+        RangeRequest::Full if entry.size > archive.max_in_memory_entry_size() => {
+            let error = ArchiveError::EntryTooLarge {
+                name: entry.name.clone(),
+                size: entry.size,
+                limit: archive.max_in_memory_entry_size(),
+            };
+
+            log::error!("Refusing to serve media file {}: {}", entry.name, error);
+            return ProtocolResponse::error(413, format!("{}; request it in ranges", error))
+                .header("X-Error-Kind", error.kind())
+                .header("Accept-Ranges", "bytes");
+        }
+        RangeRequest::Full => (200, None),
+        RangeRequest::Partial(range) => (206, Some(range)),
+        RangeRequest::Unsatisfiable => {
+            return ProtocolResponse::new(416).header("Content-Range", format!("bytes */{}", entry.size));
+        }
+    };
+
+    let (start, length) = range.map_or((0, entry.size), |range| (range.start, range.length()));
+
+    let body = archive
+        .entry_reader(&entry.name)
+        .map_err(|e| e.to_string())
+        .and_then(|mut reader| {
+            // Compressed entries cannot be seeked, so the bytes before the range are skipped
+            std::io::copy(&mut reader.by_ref().take(start), &mut std::io::sink())
+                .and_then(|_| {
+                    // The declared size is untrusted, so the buffer only grows with the bytes actually read
+                    let mut body = Vec::new();
+                    reader.take(length).read_to_end(&mut body).map(|_| body)
+                })
+                .map_err(|e| e.to_string())
+        });
+
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to read media file {}: {}", entry.name, e);
+            return ProtocolResponse::error(500, e.to_string());
+        }
+    };
+
+    let mut response = ProtocolResponse::new(status)
+        .header("Content-Type", content_type)
+        .header("Content-Length", body.len().to_string())
+        .header("Accept-Ranges", "bytes");
+
+    if let Some(range) = range {
+        response = response.header("Content-Range", format!("bytes {}-{}/{}", range.start, range.end, entry.size));
+    }
+
+    response.body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_steam_backend::FakeSteamBackend;
+    use crate::package_cache::DEFAULT_MAX_CACHE_SIZE;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn header<'a>(response: &'a ProtocolResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn write_package(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("sigame-{}-{}.siq", name, std::process::id()));
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+
+        writer.start_file("content.xml", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"<package />").unwrap();
+        writer.start_file("Video/clip%20one.mp4", SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.finish().unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let partial = |start, end| RangeRequest::Partial(ByteRange { start, end });
+
+        assert_eq!(parse_range(None, 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), partial(0, 9));
+        assert_eq!(parse_range(Some("bytes=90-"), 100), partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=0-"), u64::MAX), partial(0, MAX_RANGE_CHUNK - 1));
+        assert_eq!(parse_range(Some("bytes=90-200"), 100), partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-200"), 100), partial(0, 99));
+        assert_eq!(parse_range(Some("bytes=100-"), 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=5-1"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn test_media_response_serves_ranges() {
+        let path = write_package("media-response");
+
+        let full = media_response(&path, "Video/clip%20one.mp4", None);
+        let partial = media_response(&path, "Video/clip%20one.mp4", Some("bytes=2-5"));
+        let unsatisfiable = media_response(&path, "Video/clip%20one.mp4", Some("bytes=20-"));
+        let missing = media_response(&path, "Video/other.mp4", None);
+        let outside_media = media_response(&path, "content.xml", None);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(full.status, 200);
+        assert_eq!(full.body, b"0123456789");
+        assert_eq!(header(&full, "Content-Type"), Some("video/mp4"));
+        assert_eq!(header(&full, "Content-Length"), Some("10"));
+
+        assert_eq!(partial.status, 206);
+        assert_eq!(partial.body, b"2345");
+        assert_eq!(header(&partial, "Content-Range"), Some("bytes 2-5/10"));
+
+        assert_eq!(unsatisfiable.status, 416);
+        assert_eq!(header(&unsatisfiable, "Content-Range"), Some("bytes */10"));
+
+        assert_eq!(missing.status, 404);
+        assert_eq!(outside_media.status, 404);
+    }
+
+    #[test]
+    fn test_media_response_refuses_whole_entries_over_memory_limit() {
+        let path = write_package("media-limit");
+        let limits = ArchiveLimits { max_in_memory_entry_size: 4, ..ArchiveLimits::default() };
+
+        let full = media_response_with_limits(&path, "Video/clip%20one.mp4", None, &limits);
+        let partial = media_response_with_limits(&path, "Video/clip%20one.mp4", Some("bytes=6-"), &limits);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(full.status, 413);
+        assert_eq!(header(&full, "X-Error-Kind"), Some("entry_too_large"));
+        assert_eq!(partial.status, 206);
+        assert_eq!(partial.body, b"6789");
+    }
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(http_date(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(http_date(1_709_251_199), "Thu, 29 Feb 2024 23:59:59 GMT");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        for time in [0, 784_111_777, 951_782_400, 1_709_251_199, 4_102_444_800] {
+            assert_eq!(parse_http_date(&http_date(time)), Some(time), "{}", http_date(time));
+        }
+
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse_http_date("120"), None);
+    }
+
+    #[test]
+    fn test_package_response_supports_ranges_and_revalidation() {
+        let path = write_package("package-response");
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let full = package_response(&path, Some(784_111_777), &RequestHeaders::default());
+        let etag = header(&full, "ETag").unwrap().to_string();
+
+        let partial = package_response(&path, Some(784_111_777), &RequestHeaders { range: Some("bytes=0-3"), ..Default::default() });
+        let by_etag = package_response(&path, Some(784_111_777), &RequestHeaders { if_none_match: Some(&etag), ..Default::default() });
+        let by_date = package_response(
+            &path,
+            Some(784_111_777),
+            &RequestHeaders { if_modified_since: Some("Sun, 06 Nov 1994 08:49:37 GMT"), ..Default::default() },
+        );
+        let updated = package_response(&path, Some(784_111_778), &RequestHeaders { if_none_match: Some(&etag), ..Default::default() });
+        let missing = package_response(&path.with_extension("missing"), None, &RequestHeaders::default());
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(full.status, 200);
+        assert_eq!(full.body.len() as u64, size);
+        assert_eq!(header(&full, "Last-Modified"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+
+        assert_eq!(partial.status, 206);
+        assert_eq!(partial.body, b"PK\x03\x04");
+        assert_eq!(header(&partial, "Content-Range"), Some(format!("bytes 0-3/{}", size).as_str()));
+
+        assert_eq!(by_etag.status, 304);
+        assert!(by_etag.body.is_empty());
+        assert_eq!(by_date.status, 304);
+        assert_eq!(updated.status, 200);
+        assert_eq!(missing.status, 404);
+    }
+
+    #[test]
+    fn test_package_response_serves_ranges_of_current_version_only() {
+        let path = write_package("package-if-range");
+        let size = std::fs::metadata(&path).unwrap().len();
+        let request = |if_range| RequestHeaders { range: Some("bytes=0-3"), if_range, ..Default::default() };
+
+        let full = package_response(&path, Some(784_111_777), &RequestHeaders::default());
+        let etag = header(&full, "ETag").unwrap().to_string();
+
+        let current = package_response(&path, Some(784_111_777), &request(Some(&etag)));
+        let outdated = package_response(&path, Some(784_111_777), &request(Some("\"0-0\"")));
+
+        // A changed package is checked again
+        std::fs::write(&path, b"PK\x03\x04 not a zip archive").unwrap();
+        let broken = package_response(&path, Some(784_111_777), &RequestHeaders::default());
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(full.status, 200);
+        assert_eq!(full.body.len() as u64, size);
+        assert_eq!(current.status, 206);
+        assert_eq!(current.body, b"PK\x03\x04");
+        assert_eq!(outdated.status, 200);
+        assert_eq!(outdated.body.len() as u64, size);
+        assert_eq!(broken.status, 422);
+    }
+
+    #[test]
+    fn test_handle_request_routes_to_packages_and_thumbnails() {
+        let root = std::env::temp_dir().join(format!("sigame-protocol-routes-{}", std::process::id()));
+        let item_folder = root.join("workshop").join("42");
+        std::fs::create_dir_all(&item_folder).unwrap();
+        std::fs::create_dir_all(root.join("cache")).unwrap();
+        std::fs::create_dir_all(root.join("thumbnails").join("abc-_")).unwrap();
+        std::fs::write(root.join("thumbnails").join("abc-_").join("logo.jpg"), b"jpeg").unwrap();
+        std::fs::write(root.join("secret.jpg"), b"secret").unwrap();
+
+        let package = write_package("protocol-routes");
+        std::fs::copy(&package, item_folder.join("package.siq")).unwrap();
+        std::fs::copy(&package, root.join("cache").join("abc-_.siq")).unwrap();
+        std::fs::remove_file(&package).unwrap();
+
+        let steam = FakeSteamBackend::new().with_installed_item(42, item_folder.to_str().unwrap(), 784_111_777);
+        let package_cache = PackageCache::new(root.join("cache"), DEFAULT_MAX_CACHE_SIZE);
+        let thumbnail_cache = ThumbnailCache::new(root.join("thumbnails"), "http://sigame.localhost/thumbnails");
+
+        let get = |path, query| {
+            let request = ProtocolRequest { path, query, ..Default::default() };
+            handle_request(&steam, &package_cache, &thumbnail_cache, &request)
+        };
+
+        let installed = get("/", Some("id=42"));
+        assert_eq!(installed.status, 200);
+        assert_eq!(header(&installed, "Last-Modified"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(get("/", Some("id=43")).status, 404);
+        assert_eq!(get("/", Some("id=abc")).status, 404);
+        assert_eq!(get("/", None).status, 404);
+
+        let media = get("/package/42/media/Video/clip%20one.mp4", None);
+        assert_eq!(media.status, 200);
+        assert_eq!(media.body, b"0123456789");
+        assert_eq!(get("/package/abc-_/media/Video/clip%20one.mp4", None).body, b"0123456789");
+        assert_eq!(get("/package/43/media/Video/clip%20one.mp4", None).status, 404);
+        assert_eq!(get("/package/42/Video/clip%20one.mp4", None).status, 404);
+
+        assert_eq!(get("/cache/abc-_", None).status, 200);
+        assert_eq!(get("/cache/missing", None).status, 404);
+        assert_eq!(get("/cache/../secret.jpg", None).status, 404);
+        assert_eq!(get("/cache/..%2Fsecret", None).status, 404);
+
+        let thumbnail = get("/thumbnails/abc-_/logo.jpg", None);
+        assert_eq!(thumbnail.status, 200);
+        assert_eq!(thumbnail.body, b"jpeg");
+        assert_eq!(get("/thumbnails/../secret.jpg", None).status, 404);
+        assert_eq!(get("/thumbnails/abc-_/../../secret.jpg", None).status, 404);
+        assert_eq!(get("/thumbnails/abc-_/..%2F..%2Fsecret.jpg", None).status, 404);
+
+        assert_eq!(get("/unknown", None).status, 404);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,709 @@
+//! Native reader for SIQ packages.
+//!
+//! A SIQ package is a zip archive with the package description in `content.xml`
+//! and media files under `Images/`, `Audio/`, `Video/` and `Html/`.
+//!
+//! This module provides functionality to:
+//! - Parse `content.xml` into typed package structures (rounds, themes, questions, parameters, info)
+//! - Read packages of format version 4 (question scenarios) as well as version 5 (question parameters)
+//! - List the media files stored in the package
+
+use crate::archive::{ArchiveError, SafeArchive};
+use percent_encoding::percent_decode_str;
+use roxmltree::Node;
+use serde::Serialize;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// Name of the package description file
+const CONTENT_FILE_NAME: &str = "content.xml";
+
+/// Maximum size of the package description file in bytes
+/// Descriptions are parsed in memory, so they get a much smaller limit than media files
+const MAX_CONTENT_FILE_SIZE: u64 = 32 * 1024 * 1024; // 32 MB
+
+/// Name of the file marking packages that have passed quality control
+const QUALITY_MARKER_FILE_NAME: &str = "quality.marker";
+
+/// Error reading a SIQ package
+#[derive(Debug)]
+pub enum SiqError {
+    /// The archive cannot be read or violates the archive limits
+    Archive(ArchiveError),
+    /// The archive has no `content.xml`
+    MissingContent,
+    /// `content.xml` is not well-formed XML
+    XmlError(String),
+}
+
+impl std::fmt::Display for SiqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SiqError::Archive(e) => write!(f, "Invalid package archive: {}", e),
+            SiqError::MissingContent => write!(f, "Package has no {}", CONTENT_FILE_NAME),
+            SiqError::XmlError(msg) => write!(f, "Invalid {}: {}", CONTENT_FILE_NAME, msg),
+        }
+    }
+}
+
+impl std::error::Error for SiqError {}
+
+impl From<ArchiveError> for SiqError {
+    fn from(error: ArchiveError) -> Self {
+        SiqError::Archive(error)
+    }
+}
+
+/// Package description
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub id: String,
+    pub restriction: String,
+    pub date: String,
+    pub publisher: String,
+    pub difficulty: i32,
+    pub language: String,
+    pub logo: Option<String>,
+    pub contact_uri: Option<String>,
+    pub tags: Vec<String>,
+    pub info: Info,
+    pub rounds: Vec<Round>,
+}
+
+/// Authors, sources and comments of a package, round, theme or question
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Info {
+    pub authors: Vec<String>,
+    pub sources: Vec<String>,
+    pub comments: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Round {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub round_type: String,
+    pub info: Info,
+    pub themes: Vec<Theme>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Theme {
+    pub name: String,
+    pub info: Info,
+    pub questions: Vec<Question>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Question {
+    pub price: i32,
+    #[serde(rename = "type")]
+    pub question_type: String,
+    pub info: Info,
+    pub params: Vec<QuestionParam>,
+    /// Right answers
+    pub right: Vec<String>,
+    /// Wrong answers
+    pub wrong: Vec<String>,
+}
+
+impl Question {
+    /// Get all content items of the question, including nested ones (e.g. answer options)
+    pub fn content_items(&self) -> Vec<&ContentItem> {
+        let mut items = Vec::new();
+
+        for param in &self.params {
+            param.value.collect_content_items(&mut items);
+        }
+
+        items
+    }
+}
+
+/// Named question parameter
+#[derive(Debug, Clone, Serialize)]
+pub struct QuestionParam {
+    pub name: String,
+    pub value: ParamValue,
+}
+
+/// Question parameter value
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ParamValue {
+    Simple { value: String },
+    Content { items: Vec<ContentItem> },
+    NumberSet { minimum: i32, maximum: i32, step: i32 },
+    Group { params: Vec<QuestionParam> },
+}
+
+impl ParamValue {
+    fn collect_content_items<'a>(&'a self, items: &mut Vec<&'a ContentItem>) {
+        match self {
+            ParamValue::Content { items: content } => items.extend(content),
+            ParamValue::Group { params } => {
+                for param in params {
+                    param.value.collect_content_items(items);
+                }
+            }
+            ParamValue::Simple { .. } | ParamValue::NumberSet { .. } => {}
+        }
+    }
+}
+
+/// Type of question content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    Text,
+    Image,
+    Audio,
+    Video,
+    Html,
+}
+
+impl ContentType {
+    fn parse(value: &str) -> Self {
+        match value {
+            "image" => ContentType::Image,
+            "audio" | "voice" => ContentType::Audio,
+            "video" => ContentType::Video,
+            "html" => ContentType::Html,
+            _ => ContentType::Text,
+        }
+    }
+
+    /// Content type of the media stored in an archive folder
+    pub fn from_media_folder(folder: &str) -> Option<Self> {
+        match folder {
+            "Images" => Some(ContentType::Image),
+            "Audio" => Some(ContentType::Audio),
+            "Video" => Some(ContentType::Video),
+            "Html" => Some(ContentType::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Single item of question content
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentItem {
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
+    /// Text, or media file name if `is_ref` is set
+    pub value: String,
+    pub is_ref: bool,
+    pub placement: String,
+    /// Duration in `hh:mm:ss` format
+    pub duration: Option<String>,
+    pub wait_for_finish: bool,
+}
+
+/// Media file stored in a package
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaFile {
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
+    /// File name as referenced from `content.xml`
+    pub name: String,
+    /// Path of the file in the archive
+    pub path: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+}
+
+/// Parsed SIQ package
+#[derive(Debug, Clone, Serialize)]
+pub struct SiqPackage {
+    pub package: Package,
+    pub media: Vec<MediaFile>,
+    pub is_quality_marked: bool,
+}
+
+/// Package summary returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageInfo {
+    pub round_count: usize,
+    pub theme_count: usize,
+    pub question_count: usize,
+    pub media_count: usize,
+    /// Total size of media files in bytes
+    pub media_size: u64,
+    #[serde(flatten)]
+    pub package: SiqPackage,
+}
+
+impl SiqPackage {
+    /// Read a package file
+    pub fn open(path: &Path) -> Result<Self, SiqError> {
+        Self::from_archive(&mut SafeArchive::open(path)?)
+    }
+
+    /// Read a package from a checked archive
+    pub fn from_archive<R: Read + Seek>(archive: &mut SafeArchive<R>) -> Result<Self, SiqError> {
+        let data = archive.read_entry_with_limit(CONTENT_FILE_NAME, MAX_CONTENT_FILE_SIZE).map_err(|e| match e {
+            ArchiveError::EntryNotFound { .. } => SiqError::MissingContent,
+            e => SiqError::Archive(e),
+        })?;
+
+        let xml = String::from_utf8(data).map_err(|e| SiqError::XmlError(e.to_string()))?;
+
+        // SIQuester writes content.xml with a byte order mark
+        let package = parse_content(xml.trim_start_matches('\u{feff}'))?;
+        let mut media = Vec::new();
+        let mut is_quality_marked = false;
+
+        for entry in archive.entries() {
+            if entry.is_dir {
+                continue;
+            }
+
+            if entry.name == QUALITY_MARKER_FILE_NAME {
+                is_quality_marked = true;
+                continue;
+            }
+
+            let Some((folder, file_name)) = entry.name.split_once('/') else {
+                continue;
+            };
+
+            let Some(content_type) = ContentType::from_media_folder(folder) else {
+                continue;
+            };
+
+            if file_name.is_empty() || file_name.contains('/') {
+                continue;
+            }
+
+            media.push(MediaFile {
+                content_type,
+                name: decode_media_file_name(file_name),
+                path: entry.name.clone(),
+                size: entry.size,
+            });
+        }
+
+        Ok(Self {
+            package,
+            media,
+            is_quality_marked,
+        })
+    }
+
+    /// Find a media file by its type and the name used in `content.xml`
+    pub fn find_media(&self, content_type: ContentType, name: &str) -> Option<&MediaFile> {
+        self.media
+            .iter()
+            .find(|media| media.content_type == content_type && media.name == name)
+    }
+
+    /// Summarize the package
+    pub fn into_info(self) -> PackageInfo {
+        let rounds = &self.package.rounds;
+        let themes = || rounds.iter().flat_map(|round| &round.themes);
+
+        PackageInfo {
+            round_count: rounds.len(),
+            theme_count: themes().count(),
+            question_count: themes().map(|theme| theme.questions.len()).sum(),
+            media_count: self.media.len(),
+            media_size: self.media.iter().map(|media| media.size).sum(),
+            package: self,
+        }
+    }
+}
+
+/// Parse the package description
+pub fn parse_content(xml: &str) -> Result<Package, SiqError> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| SiqError::XmlError(e.to_string()))?;
+    let root = document.root_element();
+
+    if root.tag_name().name() != "package" {
+        return Err(SiqError::XmlError(format!(
+            "Unexpected root element <{}>",
+            root.tag_name().name()
+        )));
+    }
+
+    let version = attribute(root, "version");
+    let is_v4 = version.split('.').next() == Some("4");
+
+    let rounds = children(child(root, "rounds"), "round")
+        .map(|round| Round {
+            name: attribute(round, "name"),
+            round_type: attribute(round, "type"),
+            info: parse_info(round),
+            themes: children(child(round, "themes"), "theme")
+                .map(|theme| Theme {
+                    name: attribute(theme, "name"),
+                    info: parse_info(theme),
+                    questions: children(child(theme, "questions"), "question")
+                        .map(|question| parse_question(question, is_v4))
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Package {
+        name: attribute(root, "name"),
+        id: attribute(root, "id"),
+        restriction: attribute(root, "restriction"),
+        date: attribute(root, "date"),
+        publisher: attribute(root, "publisher"),
+        difficulty: attribute(root, "difficulty").trim().parse().unwrap_or(0),
+        language: attribute(root, "language"),
+        logo: optional_attribute(root, "logo"),
+        contact_uri: optional_attribute(root, "contactUri"),
+        tags: children(child(root, "tags"), "tag").map(text).collect(),
+        info: parse_info(root),
+        rounds,
+        version,
+    })
+}
+
+fn parse_info(node: Node) -> Info {
+    let info = child(node, "info");
+
+    Info {
+        authors: children(info.and_then(|info| child(info, "authors")), "author").map(text).collect(),
+        sources: children(info.and_then(|info| child(info, "sources")), "source").map(text).collect(),
+        comments: info
+            .and_then(|info| child(info, "comments"))
+            .map(text)
+            .filter(|comments| !comments.is_empty()),
+    }
+}
+
+fn parse_question(node: Node, is_v4: bool) -> Question {
+    let mut question = Question {
+        price: attribute(node, "price").trim().parse().unwrap_or(0),
+        question_type: attribute(node, "type"),
+        info: parse_info(node),
+        right: children(child(node, "right"), "answer").map(text).collect(),
+        wrong: children(child(node, "wrong"), "answer").map(text).collect(),
+        params: Vec::new(),
+    };
+
+    if is_v4 {
+        parse_v4_scenario(node, &mut question);
+    } else {
+        question.params = children(child(node, "params"), "param").map(parse_param).collect();
+    }
+
+    question
+}
+
+fn parse_param(node: Node) -> QuestionParam {
+    let value = match attribute(node, "type").as_str() {
+        "content" => ParamValue::Content {
+            items: children(Some(node), "item").map(parse_content_item).collect(),
+        },
+        "numberSet" => match child(node, "numberSet") {
+            Some(number_set) => ParamValue::NumberSet {
+                minimum: attribute(number_set, "minimum").parse().unwrap_or(0),
+                maximum: attribute(number_set, "maximum").parse().unwrap_or(0),
+                step: attribute(number_set, "step").parse().unwrap_or(0),
+            },
+            None => ParamValue::Simple { value: text(node) },
+        },
+        "group" => ParamValue::Group {
+            params: children(Some(node), "param").map(parse_param).collect(),
+        },
+        _ => ParamValue::Simple { value: text(node) },
+    };
+
+    QuestionParam {
+        name: attribute(node, "name"),
+        value,
+    }
+}
+
+fn parse_content_item(node: Node) -> ContentItem {
+    let content_type = ContentType::parse(&attribute(node, "type"));
+    let placement = optional_attribute(node, "placement").unwrap_or_else(|| default_placement(content_type).to_string());
+
+    ContentItem {
+        content_type,
+        value: text(node),
+        is_ref: attribute(node, "isRef").eq_ignore_ascii_case("true"),
+        placement,
+        duration: optional_attribute(node, "duration"),
+        wait_for_finish: !attribute(node, "waitForFinish").eq_ignore_ascii_case("false"),
+    }
+}
+
+/// Convert the version 4 question type and scenario atoms into question parameters
+/// Content after a `marker` atom is the answer content
+fn parse_v4_scenario(node: Node, question: &mut Question) {
+    if let Some(question_type) = child(node, "type") {
+        question.question_type = attribute(question_type, "name");
+
+        for param in children(Some(question_type), "param") {
+            question.params.push(QuestionParam {
+                name: attribute(param, "name"),
+                value: ParamValue::Simple { value: text(param) },
+            });
+        }
+    }
+
+    let mut content = Vec::new();
+    let mut answer_content = Vec::new();
+    let mut after_marker = false;
+
+    for atom in children(child(node, "scenario"), "atom") {
+        let atom_type = attribute(atom, "type");
+
+        if atom_type == "marker" {
+            after_marker = true;
+            continue;
+        }
+
+        let atom_text = text(atom);
+        let is_ref = atom_text.starts_with('@');
+        let content_type = ContentType::parse(&atom_type);
+        let time: i64 = attribute(atom, "time").parse().unwrap_or(0);
+
+        let item = ContentItem {
+            content_type,
+            value: if is_ref { atom_text[1..].to_string() } else { atom_text },
+            is_ref,
+            placement: if atom_type == "say" { "replic" } else { default_placement(content_type) }.to_string(),
+            duration: (time > 0).then(|| format_duration(time as u64)),
+            wait_for_finish: time != -1,
+        };
+
+        if after_marker {
+            answer_content.push(item);
+        } else {
+            content.push(item);
+        }
+    }
+
+    if !content.is_empty() {
+        question.params.push(QuestionParam {
+            name: "question".to_string(),
+            value: ParamValue::Content { items: content },
+        });
+    }
+
+    if !answer_content.is_empty() {
+        question.params.push(QuestionParam {
+            name: "answer".to_string(),
+            value: ParamValue::Content { items: answer_content },
+        });
+    }
+}
+
+fn default_placement(content_type: ContentType) -> &'static str {
+    match content_type {
+        ContentType::Audio => "background",
+        _ => "screen",
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Decode a media file name as stored in the archive (names are URI-escaped)
+pub fn decode_media_file_name(file_name: &str) -> String {
+    percent_decode_str(file_name).decode_utf8_lossy().into_owned()
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn children<'a, 'input: 'a>(
+    node: Option<Node<'a, 'input>>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.into_iter()
+        .flat_map(|node| node.children())
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn attribute(node: Node, name: &str) -> String {
+    node.attribute(name).unwrap_or_default().to_string()
+}
+
+fn optional_attribute(node: Node, name: &str) -> Option<String> {
+    node.attribute(name).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+/// Text of an element including the text of its descendants
+fn text(node: Node) -> String {
+    node.descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveLimits;
+    use std::io::{Cursor, Write};
+
+    fn read(data: Vec<u8>) -> Result<SiqPackage, SiqError> {
+        SiqPackage::from_archive(&mut SafeArchive::new(Cursor::new(data), &ArchiveLimits::default())?)
+    }
+
+    fn param<'a>(question: &'a Question, name: &str) -> Option<&'a ParamValue> {
+        question.params.iter().find(|param| param.name == name).map(|param| &param.value)
+    }
+
+    const CONTENT_V5: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package name="Test &amp; package" version="5" id="pkg" date="01.01.2025" difficulty="5" language="en" logo="@logo.png" xmlns="https://github.com/VladimirKhil/SI/blob/master/assets/siq_5.xsd">
+  <tags><tag>History</tag><tag>Music</tag></tags>
+  <info><authors><author>Author</author></authors><sources><source>Book</source></sources></info>
+  <rounds>
+    <round name="Round 1">
+      <themes>
+        <theme name="Theme 1">
+          <questions>
+            <question price="100">
+              <params>
+                <param name="question" type="content">
+                  <item>What is shown?</item>
+                  <item type="image" isRef="True">cat picture.png</item>
+                </param>
+                <param name="price" type="numberSet"><numberSet minimum="100" maximum="500" step="100" /></param>
+              </params>
+              <right><answer>Cat</answer></right>
+            </question>
+            <question price="200" type="stake">
+              <params>
+                <param name="question" type="content"><item type="audio" isRef="True" duration="00:00:10">song.mp3</item></param>
+              </params>
+              <right><answer>Song</answer></right>
+              <wrong><answer>Noise</answer></wrong>
+            </question>
+          </questions>
+        </theme>
+      </themes>
+    </round>
+    <round name="Final" type="final">
+      <themes><theme name="Final theme"><questions><question price="0"><right><answer>Yes</answer></right></question></questions></theme></themes>
+    </round>
+  </rounds>
+</package>"#;
+
+    /// Build a package archive from (path, content) pairs
+    fn build_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+
+        for (path, content) in files {
+            writer.start_file(*path, options).unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_parse_v5_package() {
+        let package = parse_content(CONTENT_V5).unwrap();
+
+        assert_eq!(package.name, "Test & package");
+        assert_eq!(package.difficulty, 5);
+        assert_eq!(package.logo.as_deref(), Some("@logo.png"));
+        assert_eq!(package.tags, vec!["History", "Music"]);
+        assert_eq!(package.info.authors, vec!["Author"]);
+        assert_eq!(package.rounds.len(), 2);
+        assert_eq!(package.rounds[1].round_type, "final");
+
+        let questions = &package.rounds[0].themes[0].questions;
+        assert_eq!(questions[1].question_type, "stake");
+        assert_eq!(questions[1].wrong, vec!["Noise"]);
+
+        let items = questions[0].content_items();
+        assert_eq!(items.len(), 2);
+        assert!(items[1].is_ref);
+        assert_eq!(items[1].content_type, ContentType::Image);
+        assert_eq!(items[1].value, "cat picture.png");
+
+        assert!(matches!(
+            param(&questions[0], "price"),
+            Some(ParamValue::NumberSet { minimum: 100, maximum: 500, step: 100 })
+        ));
+    }
+
+    #[test]
+    fn test_parse_v4_scenario() {
+        let xml = r#"<package name="Old" version="4"><rounds><round name="R"><themes><theme name="T"><questions>
+            <question price="10">
+              <type name="cat"><param name="theme">Other</param></type>
+              <scenario><atom>Text</atom><atom type="voice" time="5">@sound.mp3</atom><atom type="marker" /><atom type="image">@answer.jpg</atom></scenario>
+              <right><answer>A</answer></right>
+            </question>
+        </questions></theme></themes></round></rounds></package>"#;
+
+        let package = parse_content(xml).unwrap();
+        let question = &package.rounds[0].themes[0].questions[0];
+
+        assert_eq!(question.question_type, "cat");
+        assert!(matches!(param(question, "theme"), Some(ParamValue::Simple { value }) if value == "Other"));
+
+        let Some(ParamValue::Content { items }) = param(question, "question") else {
+            panic!("Question content expected");
+        };
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].content_type, ContentType::Audio);
+        assert_eq!(items[1].value, "sound.mp3");
+        assert_eq!(items[1].duration.as_deref(), Some("00:00:05"));
+
+        let Some(ParamValue::Content { items }) = param(question, "answer") else {
+            panic!("Answer content expected");
+        };
+
+        assert_eq!(items[0].value, "answer.jpg");
+    }
+
+    #[test]
+    fn test_read_package_lists_media() {
+        let content = format!("\u{feff}{}", CONTENT_V5);
+
+        let archive = build_archive(&[
+            ("content.xml", content.as_bytes()),
+            ("Images/cat%20picture.png", b"png"),
+            ("Audio/song.mp3", b"mp3 data"),
+            ("Texts/authors.xml", b"<authors />"),
+            ("quality.marker", b""),
+        ]);
+
+        let siq = read(archive).unwrap();
+
+        assert!(siq.is_quality_marked);
+        assert_eq!(siq.media.len(), 2);
+        assert_eq!(siq.find_media(ContentType::Image, "cat picture.png").unwrap().size, 3);
+        assert!(siq.find_media(ContentType::Audio, "song.mp3").is_some());
+
+        let info = siq.into_info();
+        assert_eq!(info.round_count, 2);
+        assert_eq!(info.theme_count, 2);
+        assert_eq!(info.question_count, 3);
+        assert_eq!(info.media_size, 11);
+    }
+
+    #[test]
+    fn test_read_package_errors() {
+        let without_content = build_archive(&[("Images/a.png", b"png")]);
+        let invalid_xml = build_archive(&[("content.xml", b"<package><rounds></package>")]);
+
+        assert!(matches!(
+            read(without_content),
+            Err(SiqError::MissingContent)
+        ));
+        assert!(matches!(read(invalid_xml), Err(SiqError::XmlError(_))));
+        assert!(matches!(
+            read(b"not a zip".to_vec()),
+            Err(SiqError::Archive(ArchiveError::InvalidArchive(_)))
+        ));
+    }
+}
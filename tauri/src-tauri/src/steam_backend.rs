@@ -0,0 +1,633 @@
+//! Access to the Steam client.
+//!
+//! This module provides functionality to:
+//! - Describe the Steam operations used by the shell (Workshop, user, friends and callbacks) as a trait
+//! - Implement them with the steamworks client
+//! - Create Workshop items and submit their updates
+//! - Search all Workshop items of the game
+//! - Subscribe to, vote for and favourite Workshop items
+//! - Notify listeners when Workshop item downloads finish
+//!
+//! Commands only depend on `SteamBackend`, so they can be tested against a fake backend
+//! without a running Steam client.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use steamworks::{
+    AppIDs, AppId, CallbackHandle, Client, DownloadItemResult, FileType, PublishedFileId, PublishedFileVisibility,
+    QueryHandle, SteamError, TicketForWebApiResponse, UGCQueryType, UGCStatisticType, UGCType, UpdateStatus, UserList,
+    UserListOrder,
+};
+
+/// Steam app ID of the game
+pub const APP_ID: u32 = 3553500;
+
+/// Maximum time to wait for the result of a Steam call or query
+/// Results are delivered by the callback thread, which may stop running
+const CALL_RESULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval of item update progress reports
+const UPDATE_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Steam backend shared by the commands
+pub type SteamState = Arc<dyn SteamBackend>;
+
+/// Installed Workshop item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallInfo {
+    pub folder: String,
+    /// Time the item was last updated
+    pub timestamp: u32,
+}
+
+/// Workshop item details
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkshopItemDetails {
+    pub id: u64,
+    pub title: String,
+    pub description: String,
+    pub created_time: u32,
+    pub updated_time: u32,
+    pub creator_id: u64,
+    pub file_size: u32,
+    pub tags: Vec<String>,
+    pub score: f32,
+    pub preview_url: Option<String>,
+    pub votes_up: u32,
+    pub votes_down: u32,
+    /// Number of current subscribers
+    pub subscriptions: u64,
+}
+
+/// Page of Workshop query results
+#[derive(Debug, Clone)]
+pub struct WorkshopPage {
+    pub items: Vec<WorkshopItemDetails>,
+    /// Number of results of the query on all pages
+    pub total: u32,
+}
+
+/// Order of Workshop query results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkshopSortOrder {
+    /// Most popular items of the time window first
+    #[default]
+    Trend,
+    /// Most upvoted items first
+    Votes,
+    /// Most recently published items first
+    Recent,
+    /// Items with most subscribers first
+    Subscriptions,
+}
+
+/// Period over which trending items are ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkshopTimeWindow {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl WorkshopTimeWindow {
+    pub fn days(self) -> u32 {
+        match self {
+            WorkshopTimeWindow::Day => 1,
+            WorkshopTimeWindow::Week => 7,
+            WorkshopTimeWindow::Month => 30,
+            WorkshopTimeWindow::Year => 365,
+        }
+    }
+}
+
+/// Query of all Workshop items of the game
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WorkshopQuery {
+    pub search_text: Option<String>,
+    /// Tags that every returned item has
+    pub required_tags: Vec<String>,
+    /// Tags that no returned item has
+    pub excluded_tags: Vec<String>,
+    pub sort_order: WorkshopSortOrder,
+    /// Only used with the trend order
+    pub time_window: Option<WorkshopTimeWindow>,
+    /// Page number starting from 1
+    pub page: u32,
+}
+
+/// Visibility of a Workshop item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkshopVisibility {
+    Public,
+    FriendsOnly,
+    Private,
+    Unlisted,
+}
+
+/// Changes of a Workshop item to submit
+/// Properties that are not set keep their current values
+#[derive(Debug, Clone, Default)]
+pub struct WorkshopItemUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub preview_path: Option<PathBuf>,
+    pub visibility: Option<WorkshopVisibility>,
+    /// Folder with the new item content
+    pub content_folder: Option<PathBuf>,
+    pub change_note: Option<String>,
+}
+
+/// Stage of an item update submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStage {
+    Invalid,
+    PreparingConfig,
+    PreparingContent,
+    UploadingContent,
+    UploadingPreviewFile,
+    CommittingChanges,
+}
+
+/// Progress of an item update submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateProgress {
+    pub stage: UpdateStage,
+    /// Bytes processed in the current stage
+    pub processed: u64,
+    pub total: u64,
+}
+
+/// Vote of the user for a Workshop item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemVote {
+    Up,
+    Down,
+    /// The user skipped the item when voting
+    Skipped,
+    NotVoted,
+}
+
+/// Listener of finished Workshop item downloads
+/// Called with the item id and the download result on the thread that dispatches Steam callbacks
+pub type DownloadListener = Box<dyn Fn(u64, Result<(), String>) + Send>;
+
+/// Steam operations used by the shell
+/// Blocking operations wait for their Steam callbacks, which are dispatched by `run_callbacks`
+/// on another thread
+pub trait SteamBackend: Send + Sync {
+    /// Get the install information of a Workshop item if it is installed
+    fn item_install_info(&self, item_id: u64) -> Option<InstallInfo>;
+
+    /// Get the Workshop items the user is subscribed to
+    fn subscribed_items(&self) -> Vec<u64>;
+
+    /// Start downloading a Workshop item
+    /// Returns false if the download cannot be started
+    fn download_item(&self, item_id: u64, high_priority: bool) -> bool;
+
+    /// Get the downloaded and total bytes of a Workshop item that is being downloaded
+    fn item_download_info(&self, item_id: u64) -> Option<(u64, u64)>;
+
+    /// Query a page (starting from 1) of the Workshop items the user is subscribed to
+    fn query_subscribed_items(&self, page: u32) -> Result<WorkshopPage, String>;
+
+    /// Query a page of all Workshop items of the game
+    fn query_items(&self, query: &WorkshopQuery) -> Result<WorkshopPage, String>;
+
+    /// Get a Web API authentication ticket for the given identity
+    fn web_api_ticket(&self, identity: &str, timeout: Duration) -> Result<Vec<u8>, String>;
+
+    /// Get the name of the user
+    fn persona_name(&self) -> String;
+
+    /// Get the large avatar of the user as 184x184 RGBA pixels
+    fn large_avatar(&self) -> Option<Vec<u8>>;
+
+    /// Create an empty Workshop item owned by the user
+    fn create_item(&self) -> Result<u64, String>;
+
+    /// Submit changes of a Workshop item, reporting progress while they are uploaded
+    /// Returns whether the user has to accept the Workshop legal agreement for the item to become visible
+    fn submit_item_update(
+        &self,
+        item_id: u64,
+        update: &WorkshopItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> Result<bool, String>;
+
+    /// Subscribe the user to a Workshop item
+    fn subscribe_item(&self, item_id: u64) -> Result<(), String>;
+
+    /// Unsubscribe the user from a Workshop item
+    fn unsubscribe_item(&self, item_id: u64) -> Result<(), String>;
+
+    /// Vote for a Workshop item
+    fn set_item_vote(&self, item_id: u64, vote_up: bool) -> Result<(), String>;
+
+    /// Get the vote of the user for a Workshop item
+    fn item_vote(&self, item_id: u64) -> Result<ItemVote, String>;
+
+    /// Add a Workshop item to the favourites of the user or remove it from them
+    fn set_item_favorite(&self, item_id: u64, favorite: bool) -> Result<(), String>;
+
+    /// Add a listener of finished Workshop item downloads
+    fn add_download_listener(&self, listener: DownloadListener);
+
+    /// Open a web page in the Steam overlay
+    fn open_overlay_url(&self, url: &str);
+
+    /// Dispatch pending Steam callbacks
+    fn run_callbacks(&self);
+}
+
+/// Steam backend using the steamworks client
+pub struct SteamworksBackend {
+    client: Client,
+    download_listeners: Arc<Mutex<Vec<DownloadListener>>>,
+    /// Keeps the download callback registered
+    _download_callback: CallbackHandle,
+}
+
+impl SteamworksBackend {
+    pub fn new(client: Client) -> Self {
+        let download_listeners: Arc<Mutex<Vec<DownloadListener>>> = Arc::default();
+        let listeners = download_listeners.clone();
+
+        let download_callback = client.register_callback(move |result: DownloadItemResult| {
+            if result.app_id != AppId(APP_ID) {
+                return;
+            }
+
+            let item_id = result.published_file_id.0;
+            let result = match result.error {
+                Some(e) => Err(format!("Failed to download Workshop item: {:?}", e)),
+                None => Ok(()),
+            };
+
+            if let Ok(listeners) = listeners.lock() {
+                for listener in listeners.iter() {
+                    listener(item_id, result.clone());
+                }
+            }
+        });
+
+        Self {
+            client,
+            download_listeners,
+            _download_callback: download_callback,
+        }
+    }
+}
+
+/// Start a Steam call and wait for its result
+fn call_result<T: Send + 'static>(
+    start: impl FnOnce(Box<dyn FnOnce(Result<T, SteamError>) + Send>),
+) -> Result<Result<T, SteamError>, String> {
+    let (tx, rx) = mpsc::channel();
+
+    start(Box::new(move |result| {
+        let _ = tx.send(result);
+    }));
+
+    rx.recv_timeout(CALL_RESULT_TIMEOUT).map_err(|e| match e {
+        mpsc::RecvTimeoutError::Timeout => "Timed out waiting for Steam".to_string(),
+        e => format!("Failed to receive Steam call result: {:?}", e),
+    })
+}
+
+/// Run a query and wait for its results
+fn fetch_page(query: QueryHandle) -> Result<WorkshopPage, String> {
+    // Use a channel to receive the processed result
+    let (tx, rx) = mpsc::channel();
+
+    query.fetch(move |result| {
+        let response = match result {
+            Ok(result) => {
+                let items = (0..result.returned_results())
+                    .filter_map(|i| {
+                        let detail = result.get(i)?;
+
+                        Some(WorkshopItemDetails {
+                            id: detail.published_file_id.0,
+                            title: detail.title.clone(),
+                            description: detail.description.clone(),
+                            created_time: detail.time_created,
+                            updated_time: detail.time_updated,
+                            creator_id: detail.owner.raw(),
+                            file_size: detail.file_size,
+                            tags: detail.tags.clone(),
+                            score: detail.score,
+                            preview_url: result.preview_url(i).map(|url| url.to_string()),
+                            votes_up: detail.num_upvotes,
+                            votes_down: detail.num_downvotes,
+                            subscriptions: result.statistic(i, UGCStatisticType::Subscriptions).unwrap_or_default(),
+                        })
+                    })
+                    .collect();
+
+                Ok(WorkshopPage {
+                    items,
+                    total: result.total_results(),
+                })
+            }
+            Err(e) => Err(format!("Steam error: {:?}", e)),
+        };
+
+        let _ = tx.send(response);
+    });
+
+    rx.recv_timeout(CALL_RESULT_TIMEOUT).map_err(|e| match e {
+        mpsc::RecvTimeoutError::Timeout => "Timed out waiting for Steam".to_string(),
+        e => format!("Failed to receive query result: {:?}", e),
+    })?
+}
+
+impl SteamBackend for SteamworksBackend {
+    fn item_install_info(&self, item_id: u64) -> Option<InstallInfo> {
+        self.client
+            .ugc()
+            .item_install_info(PublishedFileId(item_id))
+            .map(|info| InstallInfo {
+                folder: info.folder,
+                timestamp: info.timestamp,
+            })
+    }
+
+    fn subscribed_items(&self) -> Vec<u64> {
+        self.client
+            .ugc()
+            .subscribed_items()
+            .into_iter()
+            .map(|item_id| item_id.0)
+            .collect()
+    }
+
+    fn download_item(&self, item_id: u64, high_priority: bool) -> bool {
+        self.client.ugc().download_item(PublishedFileId(item_id), high_priority)
+    }
+
+    fn item_download_info(&self, item_id: u64) -> Option<(u64, u64)> {
+        self.client.ugc().item_download_info(PublishedFileId(item_id))
+    }
+
+    fn query_subscribed_items(&self, page: u32) -> Result<WorkshopPage, String> {
+        let steam_id = self.client.user().steam_id();
+
+        // Create a user query for subscribed items
+        let query = self
+            .client
+            .ugc()
+            .query_user(
+                steam_id.account_id(),
+                UserList::Subscribed,             // Get subscribed items
+                UGCType::All,                     // All types of UGC
+                UserListOrder::CreationOrderDesc, // Order by creation date (descending)
+                AppIDs::ConsumerAppId(AppId(APP_ID)),
+                page,
+            )
+            .map_err(|e| format!("Failed to create query: {:?}", e))?;
+
+        fetch_page(query)
+    }
+
+    fn query_items(&self, query: &WorkshopQuery) -> Result<WorkshopPage, String> {
+        let query_type = match query.sort_order {
+            WorkshopSortOrder::Trend => UGCQueryType::RankedByTrend,
+            WorkshopSortOrder::Votes => UGCQueryType::RankedByVote,
+            WorkshopSortOrder::Recent => UGCQueryType::RankedByPublicationDate,
+            WorkshopSortOrder::Subscriptions => UGCQueryType::RankedByTotalUniqueSubscriptions,
+        };
+
+        let mut handle = self
+            .client
+            .ugc()
+            .query_all(
+                query_type,
+                UGCType::Items,
+                AppIDs::ConsumerAppId(AppId(APP_ID)),
+                query.page.max(1),
+            )
+            .map_err(|e| format!("Failed to create query: {:?}", e))?;
+
+        if let Some(search_text) = query.search_text.as_deref().map(str::trim).filter(|text| !text.is_empty()) {
+            handle = handle.set_search_text(search_text);
+        }
+
+        for tag in &query.required_tags {
+            handle = handle.require_tag(tag);
+        }
+
+        for tag in &query.excluded_tags {
+            handle = handle.exclude_tag(tag);
+        }
+
+        if let (WorkshopSortOrder::Trend, Some(time_window)) = (query.sort_order, query.time_window) {
+            handle = handle.set_ranked_by_trend_days(time_window.days());
+        }
+
+        fetch_page(handle)
+    }
+
+    fn web_api_ticket(&self, identity: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+        let (tx, rx) = mpsc::channel();
+
+        // The callback is unregistered when the handle is dropped
+        let _callback_handle = self.client.register_callback(move |response: TicketForWebApiResponse| {
+            let _ = tx.send(response);
+        });
+
+        let ticket_handle = self.client.user().authentication_session_ticket_for_webapi(identity);
+        let timeout_at = Instant::now() + timeout;
+
+        let response = loop {
+            let remaining = timeout_at.saturating_duration_since(Instant::now());
+
+            match rx.recv_timeout(remaining) {
+                Ok(response) if response.ticket_handle == ticket_handle => break response,
+                Ok(_) => continue,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    return Err(format!("Timed out waiting for Steam Web API ticket for identity '{identity}'"));
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err("Steam Web API ticket callback channel disconnected".to_string());
+                }
+            }
+        };
+
+        response
+            .result
+            .map_err(|error| format!("Steam Web API ticket request failed: {error:?}"))?;
+
+        let ticket_len = usize::try_from(response.ticket_len.max(0))
+            .unwrap_or_default()
+            .min(response.ticket.len());
+
+        Ok(response.ticket[..ticket_len].to_vec())
+    }
+
+    fn persona_name(&self) -> String {
+        self.client.friends().name()
+    }
+
+    fn large_avatar(&self) -> Option<Vec<u8>> {
+        let friends = self.client.friends();
+        friends.get_friend(self.client.user().steam_id()).large_avatar()
+    }
+
+    fn create_item(&self) -> Result<u64, String> {
+        let (item_id, _) = call_result(|callback| {
+            self.client.ugc().create_item(AppId(APP_ID), FileType::Community, callback)
+        })?
+        .map_err(|e| format!("Failed to create Workshop item: {:?}", e))?;
+
+        Ok(item_id.0)
+    }
+
+    fn submit_item_update(
+        &self,
+        item_id: u64,
+        update: &WorkshopItemUpdate,
+        on_progress: &mut dyn FnMut(UpdateProgress),
+    ) -> Result<bool, String> {
+        let mut handle = self
+            .client
+            .ugc()
+            .start_item_update(AppId(APP_ID), PublishedFileId(item_id));
+
+        if let Some(title) = &update.title {
+            handle = handle.title(title);
+        }
+
+        if let Some(description) = &update.description {
+            handle = handle.description(description);
+        }
+
+        if let Some(tags) = &update.tags {
+            handle = handle.tags(tags.clone(), false);
+        }
+
+        if let Some(preview_path) = &update.preview_path {
+            handle = handle.preview_path(preview_path);
+        }
+
+        if let Some(visibility) = update.visibility {
+            handle = handle.visibility(match visibility {
+                WorkshopVisibility::Public => PublishedFileVisibility::Public,
+                WorkshopVisibility::FriendsOnly => PublishedFileVisibility::FriendsOnly,
+                WorkshopVisibility::Private => PublishedFileVisibility::Private,
+                WorkshopVisibility::Unlisted => PublishedFileVisibility::Unlisted,
+            });
+        }
+
+        if let Some(content_folder) = &update.content_folder {
+            handle = handle.content_path(content_folder);
+        }
+
+        let (tx, rx) = mpsc::channel();
+
+        let watch_handle = handle.submit(update.change_note.as_deref(), move |result| {
+            let _ = tx.send(result);
+        });
+
+        let result = loop {
+            match rx.recv_timeout(UPDATE_PROGRESS_INTERVAL) {
+                Ok(result) => break result,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let (status, processed, total) = watch_handle.progress();
+
+                    on_progress(UpdateProgress {
+                        stage: match status {
+                            UpdateStatus::Invalid => UpdateStage::Invalid,
+                            UpdateStatus::PreparingConfig => UpdateStage::PreparingConfig,
+                            UpdateStatus::PreparingContent => UpdateStage::PreparingContent,
+                            UpdateStatus::UploadingContent => UpdateStage::UploadingContent,
+                            UpdateStatus::UploadingPreviewFile => UpdateStage::UploadingPreviewFile,
+                            UpdateStatus::CommittingChanges => UpdateStage::CommittingChanges,
+                        },
+                        processed,
+                        total,
+                    });
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err("Workshop item update callback channel disconnected".to_string());
+                }
+            }
+        };
+
+        let (_, needs_to_accept_agreement) =
+            result.map_err(|e| format!("Failed to submit Workshop item update: {:?}", e))?;
+
+        Ok(needs_to_accept_agreement)
+    }
+
+    fn subscribe_item(&self, item_id: u64) -> Result<(), String> {
+        call_result(|callback| self.client.ugc().subscribe_item(PublishedFileId(item_id), callback))?
+            .map_err(|e| format!("Failed to subscribe to Workshop item: {:?}", e))
+    }
+
+    fn unsubscribe_item(&self, item_id: u64) -> Result<(), String> {
+        call_result(|callback| self.client.ugc().unsubscribe_item(PublishedFileId(item_id), callback))?
+            .map_err(|e| format!("Failed to unsubscribe from Workshop item: {:?}", e))
+    }
+
+    fn set_item_vote(&self, item_id: u64, vote_up: bool) -> Result<(), String> {
+        call_result(|callback| {
+            self.client
+                .ugc()
+                .set_user_item_vote(PublishedFileId(item_id), vote_up, callback)
+        })?
+        .map_err(|e| format!("Failed to vote for Workshop item: {:?}", e))
+    }
+
+    fn item_vote(&self, item_id: u64) -> Result<ItemVote, String> {
+        let vote = call_result(|callback| self.client.ugc().get_user_item_vote(PublishedFileId(item_id), callback))?
+            .map_err(|e| format!("Failed to get vote for Workshop item: {:?}", e))?;
+
+        Ok(if vote.vote_up {
+            ItemVote::Up
+        } else if vote.vote_down {
+            ItemVote::Down
+        } else if vote.vote_skipped {
+            ItemVote::Skipped
+        } else {
+            ItemVote::NotVoted
+        })
+    }
+
+    fn set_item_favorite(&self, item_id: u64, favorite: bool) -> Result<(), String> {
+        let ugc = self.client.ugc();
+
+        call_result(|callback| {
+            if favorite {
+                ugc.add_item_to_favorites(AppId(APP_ID), PublishedFileId(item_id), callback)
+            } else {
+                ugc.remove_item_from_favorites(AppId(APP_ID), PublishedFileId(item_id), callback)
+            }
+        })?
+        .map_err(|e| format!("Failed to change favourites: {:?}", e))
+    }
+
+    fn add_download_listener(&self, listener: DownloadListener) {
+        if let Ok(mut listeners) = self.download_listeners.lock() {
+            listeners.push(listener);
+        }
+    }
+
+    fn open_overlay_url(&self, url: &str) {
+        self.client.friends().activate_game_overlay_to_web_page(url);
+    }
+
+    fn run_callbacks(&self) {
+        self.client.run_callbacks();
+    }
+}
@@ -0,0 +1,432 @@
+//! Preview images of package media.
+//!
+//! This module provides functionality to:
+//! - Downscale the package logo and question images into thumbnails
+//! - Compose a contact sheet of every round, with a row per theme and a cell per question
+//! - Cache the generated images on disk per package hash
+//!
+//! Thumbnails of a package are stored in `{escaped hash}/` together with a `thumbnails.json`
+//! manifest. The manifest is written last, so an interrupted generation is never taken for a cached one.
+
+use crate::archive::SafeArchive;
+use crate::content_service::escape_base64;
+use crate::siq::{ContentType, SiqError, SiqPackage};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::overlay;
+use image::{DynamicImage, ImageReader, Limits, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Maximum width and height of a thumbnail
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Width and height of a contact sheet cell
+pub const CONTACT_SHEET_CELL_SIZE: u32 = 96;
+
+/// Space between contact sheet cells
+const CONTACT_SHEET_GAP: u32 = 4;
+
+/// Maximum number of themes (rows) shown in a contact sheet
+const MAX_CONTACT_SHEET_ROWS: usize = 16;
+
+/// Maximum number of questions (columns) shown in a contact sheet
+const MAX_CONTACT_SHEET_COLUMNS: usize = 10;
+
+/// Images with a larger width or height are not decoded
+const MAX_IMAGE_DIMENSION: u32 = 16_384;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// Color behind transparent images and between contact sheet cells
+const BACKGROUND_COLOR: Rgba<u8> = Rgba([32, 32, 48, 255]);
+
+/// Color of contact sheet cells of questions without images
+const EMPTY_CELL_COLOR: Rgba<u8> = Rgba([64, 64, 88, 255]);
+
+const MANIFEST_FILE_NAME: &str = "thumbnails.json";
+
+/// Error generating thumbnails
+#[derive(Debug)]
+pub enum ThumbnailError {
+    /// The package cannot be read
+    Package(SiqError),
+    /// The package hash cannot be used as a cache key
+    InvalidHash(String),
+    IoError(String),
+    ImageError(String),
+}
+
+impl std::fmt::Display for ThumbnailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThumbnailError::Package(e) => write!(f, "{}", e),
+            ThumbnailError::InvalidHash(hash) => write!(f, "Invalid package hash: {}", hash),
+            ThumbnailError::IoError(msg) => write!(f, "IO error: {}", msg),
+            ThumbnailError::ImageError(msg) => write!(f, "Image error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ThumbnailError {}
+
+impl From<SiqError> for ThumbnailError {
+    fn from(error: SiqError) -> Self {
+        ThumbnailError::Package(error)
+    }
+}
+
+impl From<std::io::Error> for ThumbnailError {
+    fn from(error: std::io::Error) -> Self {
+        ThumbnailError::IoError(error.to_string())
+    }
+}
+
+/// Generated preview image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub uri: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Thumbnail of the first image of a question (indices are zero-based)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionThumbnail {
+    pub round: usize,
+    pub theme: usize,
+    pub question: usize,
+    #[serde(flatten)]
+    pub thumbnail: Thumbnail,
+}
+
+/// Contact sheet of a round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundContactSheet {
+    pub round: usize,
+    #[serde(flatten)]
+    pub thumbnail: Thumbnail,
+}
+
+/// Preview images of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageThumbnails {
+    pub package_hash: String,
+    pub logo: Option<Thumbnail>,
+    pub questions: Vec<QuestionThumbnail>,
+    /// Only rounds with images have a contact sheet
+    pub contact_sheets: Vec<RoundContactSheet>,
+}
+
+/// Disk cache of package thumbnails
+pub struct ThumbnailCache {
+    root: PathBuf,
+    /// URI the cache directory is served at
+    base_uri: String,
+}
+
+impl ThumbnailCache {
+    /// Create a cache in the given directory
+    pub fn new(root: PathBuf, base_uri: impl Into<String>) -> Self {
+        Self {
+            root,
+            base_uri: base_uri.into(),
+        }
+    }
+
+    /// Get the path of a generated thumbnail file
+    /// Returns None if the names are not valid or the file does not exist
+    pub fn file_path(&self, key: &str, file_name: &str) -> Option<PathBuf> {
+        let is_valid_file_name = !file_name.starts_with('.')
+            && file_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+
+        if !is_valid_key(key) || !is_valid_file_name {
+            return None;
+        }
+
+        Some(self.root.join(key).join(file_name)).filter(|path| path.is_file())
+    }
+
+    /// Get the thumbnails of a package, generating them if they are not cached yet
+    pub fn get_or_create(&self, package_hash: &str, package_path: &Path) -> Result<PackageThumbnails, ThumbnailError> {
+        let key = escape_base64(package_hash);
+
+        if !is_valid_key(&key) {
+            return Err(ThumbnailError::InvalidHash(package_hash.to_string()));
+        }
+
+        let dir = self.root.join(&key);
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+
+        if let Ok(data) = std::fs::read(&manifest_path) {
+            match serde_json::from_slice(&data) {
+                Ok(thumbnails) => return Ok(thumbnails),
+                Err(e) => log::warn!("Ignoring broken thumbnail manifest {}: {}", manifest_path.display(), e),
+            }
+        }
+
+        std::fs::create_dir_all(&dir)?;
+
+        let writer = ThumbnailWriter {
+            dir: dir.clone(),
+            uri: format!("{}/{}", self.base_uri, key),
+        };
+
+        let thumbnails = generate(&mut SafeArchive::open(package_path).map_err(SiqError::from)?, package_hash, &writer)?;
+
+        // Write the manifest atomically, so readers never see a partial one
+        let temp_path = dir.join(format!("{}.tmp", MANIFEST_FILE_NAME));
+        let manifest = serde_json::to_vec(&thumbnails).map_err(|e| ThumbnailError::IoError(e.to_string()))?;
+        std::fs::write(&temp_path, manifest)?;
+        std::fs::rename(&temp_path, &manifest_path)?;
+
+        Ok(thumbnails)
+    }
+}
+
+/// Cache keys are escaped hashes
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Writer of the thumbnail files of a package
+struct ThumbnailWriter {
+    dir: PathBuf,
+    uri: String,
+}
+
+impl ThumbnailWriter {
+    /// Save an image as JPEG
+    fn save(&self, file_name: &str, image: &RgbaImage) -> Result<Thumbnail, ThumbnailError> {
+        let mut background = RgbaImage::from_pixel(image.width(), image.height(), BACKGROUND_COLOR);
+        overlay(&mut background, image, 0, 0);
+
+        let rgb = DynamicImage::ImageRgba8(background).to_rgb8();
+        let mut writer = BufWriter::new(std::fs::File::create(self.dir.join(file_name))?);
+
+        JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY)
+            .encode_image(&rgb)
+            .map_err(|e| ThumbnailError::ImageError(e.to_string()))?;
+
+        writer.flush()?;
+
+        Ok(Thumbnail {
+            uri: format!("{}/{}", self.uri, file_name),
+            width: rgb.width(),
+            height: rgb.height(),
+        })
+    }
+}
+
+/// Generate the thumbnails of a package
+fn generate<R: Read + Seek>(
+    archive: &mut SafeArchive<R>,
+    package_hash: &str,
+    writer: &ThumbnailWriter,
+) -> Result<PackageThumbnails, ThumbnailError> {
+    let siq = SiqPackage::from_archive(archive)?;
+
+    let logo = match siq.package.logo.as_deref().and_then(|logo| logo.strip_prefix('@')) {
+        Some(logo) => load_thumbnail(archive, &siq, logo, THUMBNAIL_SIZE)
+            .map(|image| writer.save("logo.jpg", &image))
+            .transpose()?,
+        None => None,
+    };
+
+    let mut questions = Vec::new();
+    let mut contact_sheets = Vec::new();
+
+    for (round_index, round) in siq.package.rounds.iter().enumerate() {
+        // Contact sheet cells: None for questions without images
+        let mut cells: Vec<Vec<Option<RgbaImage>>> = Vec::new();
+
+        for (theme_index, theme) in round.themes.iter().enumerate() {
+            let mut row = Vec::new();
+
+            for (question_index, question) in theme.questions.iter().enumerate() {
+                let image = question
+                    .content_items()
+                    .into_iter()
+                    .find(|item| item.is_ref && item.content_type == ContentType::Image)
+                    .and_then(|item| load_thumbnail(archive, &siq, &item.value, THUMBNAIL_SIZE));
+
+                if let Some(image) = &image {
+                    let file_name = format!("q-{}-{}-{}.jpg", round_index, theme_index, question_index);
+
+                    questions.push(QuestionThumbnail {
+                        round: round_index,
+                        theme: theme_index,
+                        question: question_index,
+                        thumbnail: writer.save(&file_name, image)?,
+                    });
+                }
+
+                row.push(image);
+            }
+
+            cells.push(row);
+        }
+
+        if cells.iter().flatten().any(Option::is_some) {
+            contact_sheets.push(RoundContactSheet {
+                round: round_index,
+                thumbnail: writer.save(&format!("round-{}.jpg", round_index), &contact_sheet(&cells))?,
+            });
+        }
+    }
+
+    Ok(PackageThumbnails {
+        package_hash: package_hash.to_string(),
+        logo,
+        questions,
+        contact_sheets,
+    })
+}
+
+/// Decode an image of the package and downscale it to fit into a square of the given size
+/// Missing and broken images are skipped
+fn load_thumbnail<R: Read + Seek>(
+    archive: &mut SafeArchive<R>,
+    siq: &SiqPackage,
+    name: &str,
+    size: u32,
+) -> Option<RgbaImage> {
+    let media = siq.find_media(ContentType::Image, name)?;
+
+    let data = archive
+        .read_entry(&media.path)
+        .map_err(|e| log::warn!("Failed to read image {}: {}", media.path, e))
+        .ok()?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| log::warn!("Failed to read image {}: {}", media.path, e))
+        .ok()?;
+
+    reader.limits(limits);
+
+    let image = reader
+        .decode()
+        .map_err(|e| log::warn!("Failed to decode image {}: {}", media.path, e))
+        .ok()?;
+
+    Some(image.thumbnail(size, size).to_rgba8())
+}
+
+/// Compose a contact sheet with a row per theme and a cell per question
+fn contact_sheet(cells: &[Vec<Option<RgbaImage>>]) -> RgbaImage {
+    let rows = cells.len().min(MAX_CONTACT_SHEET_ROWS);
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0).min(MAX_CONTACT_SHEET_COLUMNS);
+
+    let step = CONTACT_SHEET_CELL_SIZE + CONTACT_SHEET_GAP;
+    let width = columns as u32 * step + CONTACT_SHEET_GAP;
+    let height = rows as u32 * step + CONTACT_SHEET_GAP;
+
+    let mut sheet = RgbaImage::from_pixel(width, height, BACKGROUND_COLOR);
+    let empty_cell = RgbaImage::from_pixel(CONTACT_SHEET_CELL_SIZE, CONTACT_SHEET_CELL_SIZE, EMPTY_CELL_COLOR);
+
+    for (row_index, row) in cells.iter().take(rows).enumerate() {
+        for (column_index, cell) in row.iter().take(columns).enumerate() {
+            let x = i64::from(CONTACT_SHEET_GAP + column_index as u32 * step);
+            let y = i64::from(CONTACT_SHEET_GAP + row_index as u32 * step);
+
+            overlay(&mut sheet, &empty_cell, x, y);
+
+            if let Some(image) = cell {
+                let image = DynamicImage::ImageRgba8(image.clone())
+                    .thumbnail(CONTACT_SHEET_CELL_SIZE, CONTACT_SHEET_CELL_SIZE)
+                    .to_rgba8();
+
+                // Center the image in its cell
+                let offset_x = i64::from((CONTACT_SHEET_CELL_SIZE - image.width()) / 2);
+                let offset_y = i64::from((CONTACT_SHEET_CELL_SIZE - image.height()) / 2);
+
+                overlay(&mut sheet, &image, x + offset_x, y + offset_y);
+            }
+        }
+    }
+
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::SimpleFileOptions;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        RgbaImage::from_pixel(width, height, Rgba([200, 40, 40, 255]))
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+
+        data
+    }
+
+    fn write_package(path: &Path) {
+        let content = r#"<package name="P" version="5" logo="@logo.png"><rounds>
+            <round name="R1"><themes><theme name="T"><questions>
+              <question price="100"><params><param name="question" type="content"><item>Text</item></param></params><right><answer>A</answer></right></question>
+              <question price="200"><params><param name="question" type="content"><item type="image" isRef="True">wide picture.png</item></param></params><right><answer>B</answer></right></question>
+            </questions></theme></themes></round>
+            <round name="R2"><themes><theme name="T"><questions>
+              <question price="100"><params><param name="question" type="content"><item>Text</item></param></params><right><answer>C</answer></right></question>
+            </questions></theme></themes></round>
+        </rounds></package>"#;
+
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+
+        for (name, data) in [
+            ("content.xml", content.as_bytes().to_vec()),
+            ("Images/logo.png", png(600, 600)),
+            ("Images/wide%20picture.png", png(1000, 500)),
+        ] {
+            writer.start_file(name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(&data).unwrap();
+        }
+
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_get_or_create_generates_and_caches_thumbnails() {
+        let root = std::env::temp_dir().join(format!("sigame-thumbnails-{}", std::process::id()));
+        let package_path = root.join("package.siq");
+        std::fs::create_dir_all(&root).unwrap();
+        write_package(&package_path);
+
+        let cache = ThumbnailCache::new(root.join("cache"), "http://sigame.localhost/thumbnails");
+        let thumbnails = cache.get_or_create("ab+/cd==", &package_path).unwrap();
+
+        let logo = thumbnails.logo.as_ref().unwrap();
+        assert_eq!(logo.uri, "http://sigame.localhost/thumbnails/ab-_cd/logo.jpg");
+        assert_eq!((logo.width, logo.height), (THUMBNAIL_SIZE, THUMBNAIL_SIZE));
+
+        assert_eq!(thumbnails.questions.len(), 1);
+        let question = &thumbnails.questions[0];
+        assert_eq!((question.round, question.theme, question.question), (0, 0, 1));
+        assert_eq!((question.thumbnail.width, question.thumbnail.height), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+
+        assert_eq!(thumbnails.contact_sheets.len(), 1);
+        let sheet = &thumbnails.contact_sheets[0].thumbnail;
+        assert_eq!(sheet.width, 2 * (CONTACT_SHEET_CELL_SIZE + CONTACT_SHEET_GAP) + CONTACT_SHEET_GAP);
+
+        assert!(cache.file_path("ab-_cd", "round-0.jpg").is_some());
+        assert!(cache.file_path("ab-_cd", "../package.siq").is_none());
+        assert!(cache.file_path("..", "package.siq").is_none());
+
+        // Cached thumbnails do not need the package anymore
+        std::fs::remove_file(&package_path).unwrap();
+        let cached = cache.get_or_create("ab+/cd==", &package_path).unwrap();
+        assert_eq!(cached.questions.len(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
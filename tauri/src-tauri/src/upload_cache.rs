@@ -0,0 +1,332 @@
+//! Persistent cache of package hashes and content service URIs.
+//!
+//! This module provides functionality to:
+//! - Remember the SHA-1 hash of a Workshop package while its file is unchanged (same mtime and size)
+//! - Remember the SHA-1 hash of a local package file the same way, by its path
+//! - Remember the content service URI of an already uploaded package
+//! - Remember that a Workshop package file has passed validation while it is unchanged
+//!
+//! The cache is stored as JSON in the app data directory. A missing, corrupt or outdated
+//! cache file is treated as empty.
+
+use crate::content_service::FileKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version of the cache file format
+const CACHE_VERSION: u32 = 1;
+
+/// How long a package URI is trusted before the content service is asked again
+/// (the service may remove packages that have not been used for a while)
+const URI_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How long a package hash or validation result is kept after it was computed
+const HASH_TTL: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Cached hash of a package file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HashEntry {
+    modified: u64,
+    size: u64,
+    hash: String,
+    cached_at: u64,
+}
+
+/// Workshop package file that has passed validation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ValidationEntry {
+    modified: u64,
+    size: u64,
+    cached_at: u64,
+}
+
+/// Cached URI of an uploaded package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UriEntry {
+    uri: String,
+    cached_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheData {
+    version: u32,
+    /// Hashes by Workshop item id
+    hashes: HashMap<u64, HashEntry>,
+    /// Hashes of local package files by path
+    #[serde(default)]
+    path_hashes: HashMap<String, HashEntry>,
+    /// Package URIs by content service URI and file key
+    uris: HashMap<String, UriEntry>,
+    /// Validated package files by Workshop item id
+    #[serde(default)]
+    validated: HashMap<u64, ValidationEntry>,
+}
+
+/// Upload cache
+pub struct UploadCache {
+    path: PathBuf,
+    data: CacheData,
+}
+
+impl UploadCache {
+    /// Load the cache from a file
+    pub fn load(path: PathBuf) -> Self {
+        let data = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheData>(&bytes).ok())
+            .filter(|data| data.version == CACHE_VERSION)
+            .unwrap_or_else(|| CacheData {
+                version: CACHE_VERSION,
+                ..CacheData::default()
+            });
+
+        let mut cache = Self { path, data };
+        cache.remove_expired(now_secs());
+        cache
+    }
+
+    /// Get the hash of a Workshop package if its file has not changed since it was hashed
+    pub fn get_hash(&self, item_id: u64, modified: u64, size: u64) -> Option<String> {
+        self.data
+            .hashes
+            .get(&item_id)
+            .filter(|entry| entry.modified == modified && entry.size == size)
+            .map(|entry| entry.hash.clone())
+    }
+
+    /// Remember the hash of a Workshop package file
+    pub fn set_hash(&mut self, item_id: u64, modified: u64, size: u64, hash: &str) {
+        self.data.hashes.insert(
+            item_id,
+            HashEntry {
+                modified,
+                size,
+                hash: hash.to_string(),
+                cached_at: now_secs(),
+            },
+        );
+    }
+
+    /// Get the hash of a local package file if it has not changed since it was hashed
+    pub fn get_path_hash(&self, path: &Path, modified: u64, size: u64) -> Option<String> {
+        self.data
+            .path_hashes
+            .get(path.to_string_lossy().as_ref())
+            .filter(|entry| entry.modified == modified && entry.size == size)
+            .map(|entry| entry.hash.clone())
+    }
+
+    /// Remember the hash of a local package file
+    pub fn set_path_hash(&mut self, path: &Path, modified: u64, size: u64, hash: &str) {
+        self.data.path_hashes.insert(
+            path.to_string_lossy().into_owned(),
+            HashEntry {
+                modified,
+                size,
+                hash: hash.to_string(),
+                cached_at: now_secs(),
+            },
+        );
+    }
+
+    /// Check whether a Workshop package has passed validation and its file has not changed since
+    pub fn is_validated(&self, item_id: u64, modified: u64, size: u64) -> bool {
+        self.data
+            .validated
+            .get(&item_id)
+            .is_some_and(|entry| entry.modified == modified && entry.size == size)
+    }
+
+    /// Remember that a Workshop package file has passed validation
+    pub fn set_validated(&mut self, item_id: u64, modified: u64, size: u64) {
+        self.data.validated.insert(
+            item_id,
+            ValidationEntry {
+                modified,
+                size,
+                cached_at: now_secs(),
+            },
+        );
+    }
+
+    /// Get the URI of a package already uploaded to a content service
+    pub fn get_uri(&self, service_uri: &str, package_key: &FileKey) -> Option<String> {
+        let now = now_secs();
+
+        self.data
+            .uris
+            .get(&uri_key(service_uri, package_key))
+            .filter(|entry| now.saturating_sub(entry.cached_at) < URI_TTL.as_secs())
+            .map(|entry| entry.uri.clone())
+    }
+
+    /// Remember the URI of a package uploaded to a content service
+    pub fn set_uri(&mut self, service_uri: &str, package_key: &FileKey, uri: &str) {
+        self.data.uris.insert(
+            uri_key(service_uri, package_key),
+            UriEntry {
+                uri: uri.to_string(),
+                cached_at: now_secs(),
+            },
+        );
+    }
+
+    /// Write the cache to its file
+    pub fn save(&mut self) -> std::io::Result<()> {
+        self.remove_expired(now_secs());
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let bytes = serde_json::to_vec(&self.data)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated cache
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, bytes)?;
+        std::fs::rename(&temp_path, &self.path)
+    }
+
+    fn remove_expired(&mut self, now: u64) {
+        self.data
+            .hashes
+            .retain(|_, entry| now.saturating_sub(entry.cached_at) < HASH_TTL.as_secs());
+
+        self.data
+            .path_hashes
+            .retain(|_, entry| now.saturating_sub(entry.cached_at) < HASH_TTL.as_secs());
+
+        self.data
+            .validated
+            .retain(|_, entry| now.saturating_sub(entry.cached_at) < HASH_TTL.as_secs());
+
+        self.data
+            .uris
+            .retain(|_, entry| now.saturating_sub(entry.cached_at) < URI_TTL.as_secs());
+    }
+}
+
+/// Get the modification time (in seconds since the Unix epoch) and size of a file
+pub fn file_stamp(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    Ok((modified, metadata.len()))
+}
+
+fn uri_key(service_uri: &str, package_key: &FileKey) -> String {
+    format!(
+        "{}|{}|{}",
+        service_uri.trim_end_matches('/'),
+        package_key.hash,
+        package_key.name
+    )
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sigame-{}-{}.json", name, std::process::id()))
+    }
+
+    fn key() -> FileKey {
+        FileKey {
+            name: "package.siq".to_string(),
+            hash: "COjkNaaXOVDBWzFXLKT5l7rLF7Q=".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_hash_requires_unchanged_file() {
+        let mut cache = UploadCache::load(cache_path("hash-cache"));
+        cache.set_hash(42, 1000, 500, "hash");
+
+        assert_eq!(cache.get_hash(42, 1000, 500).as_deref(), Some("hash"));
+        assert_eq!(cache.get_hash(42, 1001, 500), None);
+        assert_eq!(cache.get_hash(42, 1000, 501), None);
+        assert_eq!(cache.get_hash(43, 1000, 500), None);
+    }
+
+    #[test]
+    fn test_path_hash_requires_unchanged_file() {
+        let mut cache = UploadCache::load(cache_path("path-hash-cache"));
+        cache.set_path_hash(Path::new("/packages/a.siq"), 1000, 500, "hash");
+
+        assert_eq!(cache.get_path_hash(Path::new("/packages/a.siq"), 1000, 500).as_deref(), Some("hash"));
+        assert_eq!(cache.get_path_hash(Path::new("/packages/a.siq"), 1001, 500), None);
+        assert_eq!(cache.get_path_hash(Path::new("/packages/b.siq"), 1000, 500), None);
+        assert_eq!(cache.get_hash(42, 1000, 500), None);
+    }
+
+    #[test]
+    fn test_validation_requires_unchanged_file() {
+        let mut cache = UploadCache::load(cache_path("validation-cache"));
+        assert!(!cache.is_validated(42, 1000, 500));
+
+        cache.set_validated(42, 1000, 500);
+
+        assert!(cache.is_validated(42, 1000, 500));
+        assert!(!cache.is_validated(42, 1001, 500));
+        assert!(!cache.is_validated(42, 1000, 501));
+        assert!(!cache.is_validated(43, 1000, 500));
+    }
+
+    #[test]
+    fn test_uri_is_scoped_to_service_and_expires() {
+        let mut cache = UploadCache::load(cache_path("uri-cache"));
+        cache.set_uri("http://content/", &key(), "http://content/packages/1");
+
+        assert_eq!(cache.get_uri("http://content", &key()).as_deref(), Some("http://content/packages/1"));
+        assert_eq!(cache.get_uri("http://mirror", &key()), None);
+
+        for entry in cache.data.uris.values_mut() {
+            entry.cached_at -= URI_TTL.as_secs();
+        }
+
+        assert_eq!(cache.get_uri("http://content", &key()), None);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path = cache_path("round-trip-cache");
+
+        let mut cache = UploadCache::load(path.clone());
+        cache.set_hash(42, 1000, 500, "hash");
+        cache.set_uri("http://content", &key(), "http://content/packages/1");
+        cache.save().unwrap();
+
+        let loaded = UploadCache::load(path.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get_hash(42, 1000, 500).as_deref(), Some("hash"));
+        assert_eq!(loaded.get_uri("http://content", &key()).as_deref(), Some("http://content/packages/1"));
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let path = cache_path("corrupt-cache");
+        std::fs::write(&path, b"{ not json").unwrap();
+
+        let cache = UploadCache::load(path.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(cache.data.hashes.is_empty());
+        assert_eq!(cache.data.version, CACHE_VERSION);
+    }
+}
@@ -0,0 +1,364 @@
+//! Queue of Workshop package uploads to the content service.
+//!
+//! This module provides functionality to:
+//! - Queue uploads and start them with a configurable concurrency limit
+//! - Track the state and progress of every upload
+//! - Cancel queued and running uploads
+//!
+//! The queue only does the bookkeeping; running the uploads is up to the caller,
+//! which attaches a task handle `H` to every upload it starts.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Default number of uploads running at the same time
+pub const DEFAULT_MAX_CONCURRENT_UPLOADS: usize = 2;
+
+/// Number of finished uploads kept for `list`
+const MAX_FINISHED_UPLOADS: usize = 50;
+
+/// State of a queued upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl UploadState {
+    /// Whether the upload has reached its final state
+    pub fn is_finished(self) -> bool {
+        matches!(self, UploadState::Done | UploadState::Failed | UploadState::Cancelled)
+    }
+}
+
+/// Upload parameters
+#[derive(Debug, Clone)]
+pub struct UploadRequest {
+    pub item_id: u64,
+    pub content_service_uri: String,
+    /// Content services tried in order when the primary one is unavailable
+    pub mirror_uris: Vec<String>,
+    pub package_name: String,
+}
+
+/// Successful upload outcome
+#[derive(Debug, Clone)]
+pub struct UploadSuccess {
+    pub uri: String,
+    pub already_existed: bool,
+    /// Content service (primary or mirror) that stores the package
+    pub service_uri: String,
+}
+
+/// Failed upload outcome
+#[derive(Debug, Clone)]
+pub struct UploadFailure {
+    pub error: String,
+    /// Machine-readable error kind (see `ContentServiceError::kind`)
+    pub error_kind: String,
+}
+
+/// Upload information reported to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadEntry {
+    pub upload_id: u64,
+    pub item_id: u64,
+    pub package_name: String,
+    pub content_service_uri: String,
+    pub mirror_uris: Vec<String>,
+    pub state: UploadState,
+    pub loaded: u64,
+    pub total: u64,
+    pub uri: Option<String>,
+    pub error: Option<String>,
+    pub error_kind: Option<String>,
+    pub already_existed: bool,
+    /// Content service (primary or mirror) the package was uploaded to
+    pub service_uri: Option<String>,
+}
+
+struct QueuedUpload<H> {
+    entry: UploadEntry,
+    handle: Option<H>,
+}
+
+/// Upload queue
+pub struct UploadQueue<H> {
+    uploads: BTreeMap<u64, QueuedUpload<H>>,
+    next_id: u64,
+    max_concurrent: usize,
+}
+
+impl<H> UploadQueue<H> {
+    /// Create an empty queue
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            uploads: BTreeMap::new(),
+            next_id: 1,
+            max_concurrent: max_concurrent.max(1),
+        }
+    }
+
+    /// Change the number of uploads running at the same time
+    /// Running uploads are not interrupted when the limit is lowered
+    pub fn set_max_concurrent(&mut self, max_concurrent: usize) {
+        self.max_concurrent = max_concurrent.max(1);
+    }
+
+    /// Add an upload to the queue
+    pub fn enqueue(&mut self, request: UploadRequest) -> UploadEntry {
+        let upload_id = self.next_id;
+        self.next_id += 1;
+
+        let entry = UploadEntry {
+            upload_id,
+            item_id: request.item_id,
+            package_name: request.package_name,
+            content_service_uri: request.content_service_uri,
+            mirror_uris: request.mirror_uris,
+            state: UploadState::Queued,
+            loaded: 0,
+            total: 0,
+            uri: None,
+            error: None,
+            error_kind: None,
+            already_existed: false,
+            service_uri: None,
+        };
+
+        self.uploads.insert(upload_id, QueuedUpload { entry: entry.clone(), handle: None });
+        entry
+    }
+
+    /// Mark as many queued uploads as the concurrency limit allows as running, in queue order
+    /// Returns the uploads the caller must start
+    pub fn start_next(&mut self) -> Vec<(UploadEntry, UploadRequest)> {
+        let running = self
+            .uploads
+            .values()
+            .filter(|upload| upload.entry.state == UploadState::Running)
+            .count();
+
+        let available = self.max_concurrent.saturating_sub(running);
+
+        self.uploads
+            .values_mut()
+            .filter(|upload| upload.entry.state == UploadState::Queued)
+            .take(available)
+            .map(|upload| {
+                upload.entry.state = UploadState::Running;
+
+                let request = UploadRequest {
+                    item_id: upload.entry.item_id,
+                    content_service_uri: upload.entry.content_service_uri.clone(),
+                    mirror_uris: upload.entry.mirror_uris.clone(),
+                    package_name: upload.entry.package_name.clone(),
+                };
+
+                (upload.entry.clone(), request)
+            })
+            .collect()
+    }
+
+    /// Attach the task running an upload
+    /// Returns the handle back if the upload is no longer running
+    pub fn attach(&mut self, upload_id: u64, handle: H) -> Option<H> {
+        match self.uploads.get_mut(&upload_id) {
+            Some(upload) if upload.entry.state == UploadState::Running => {
+                upload.handle = Some(handle);
+                None
+            }
+            _ => Some(handle),
+        }
+    }
+
+    /// Update the progress of a running upload
+    pub fn update_progress(&mut self, upload_id: u64, loaded: u64, total: u64) -> Option<UploadEntry> {
+        let upload = self.uploads.get_mut(&upload_id)?;
+
+        if upload.entry.state != UploadState::Running {
+            return None;
+        }
+
+        upload.entry.loaded = loaded;
+        upload.entry.total = total;
+        Some(upload.entry.clone())
+    }
+
+    /// Record the outcome of a running upload
+    /// Returns None if the upload is not running anymore (e.g. it has been cancelled)
+    pub fn finish(&mut self, upload_id: u64, outcome: Result<UploadSuccess, UploadFailure>) -> Option<UploadEntry> {
+        let upload = self.uploads.get_mut(&upload_id)?;
+
+        if upload.entry.state != UploadState::Running {
+            return None;
+        }
+
+        upload.handle = None;
+
+        match outcome {
+            Ok(success) => {
+                upload.entry.state = UploadState::Done;
+                upload.entry.loaded = upload.entry.total;
+                upload.entry.uri = Some(success.uri);
+                upload.entry.already_existed = success.already_existed;
+                upload.entry.service_uri = Some(success.service_uri);
+            }
+            Err(failure) => {
+                upload.entry.state = UploadState::Failed;
+                upload.entry.error = Some(failure.error);
+                upload.entry.error_kind = Some(failure.error_kind);
+            }
+        }
+
+        let entry = upload.entry.clone();
+        self.prune_finished();
+        Some(entry)
+    }
+
+    /// Cancel a queued or running upload
+    /// Returns the cancelled upload and the handle of its task, which the caller must abort
+    pub fn cancel(&mut self, upload_id: u64) -> Option<(UploadEntry, Option<H>)> {
+        let upload = self.uploads.get_mut(&upload_id)?;
+
+        if upload.entry.state.is_finished() {
+            return None;
+        }
+
+        upload.entry.state = UploadState::Cancelled;
+        let result = (upload.entry.clone(), upload.handle.take());
+        self.prune_finished();
+        Some(result)
+    }
+
+    /// Get all known uploads in queue order
+    pub fn list(&self) -> Vec<UploadEntry> {
+        self.uploads.values().map(|upload| upload.entry.clone()).collect()
+    }
+
+    /// Forget the oldest finished uploads
+    fn prune_finished(&mut self) {
+        let finished: Vec<u64> = self
+            .uploads
+            .iter()
+            .filter(|(_, upload)| upload.entry.state.is_finished())
+            .map(|(upload_id, _)| *upload_id)
+            .collect();
+
+        for upload_id in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED_UPLOADS)) {
+            self.uploads.remove(upload_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(item_id: u64) -> UploadRequest {
+        UploadRequest {
+            item_id,
+            content_service_uri: "http://localhost".to_string(),
+            mirror_uris: Vec::new(),
+            package_name: format!("package{}.siq", item_id),
+        }
+    }
+
+    fn success() -> Result<UploadSuccess, UploadFailure> {
+        Ok(UploadSuccess {
+            uri: "http://localhost/package".to_string(),
+            already_existed: false,
+            service_uri: "http://localhost".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_start_next_respects_concurrency() {
+        let mut queue: UploadQueue<()> = UploadQueue::new(2);
+
+        for item_id in 1..=3 {
+            queue.enqueue(request(item_id));
+        }
+
+        let started: Vec<u64> = queue.start_next().iter().map(|(entry, _)| entry.item_id).collect();
+        assert_eq!(started, vec![1, 2]);
+        assert!(queue.start_next().is_empty());
+
+        queue.finish(1, success()).unwrap();
+
+        let started: Vec<u64> = queue.start_next().iter().map(|(entry, _)| entry.item_id).collect();
+        assert_eq!(started, vec![3]);
+    }
+
+    #[test]
+    fn test_finish_records_outcome() {
+        let mut queue: UploadQueue<()> = UploadQueue::new(2);
+        queue.enqueue(request(1));
+        queue.enqueue(request(2));
+        queue.start_next();
+
+        queue.update_progress(1, 50, 100).unwrap();
+        let done = queue.finish(1, success()).unwrap();
+        assert_eq!(done.state, UploadState::Done);
+        assert_eq!(done.loaded, 100);
+
+        let failure = UploadFailure { error: "Upload failed".to_string(), error_kind: "rejected".to_string() };
+        let failed = queue.finish(2, Err(failure)).unwrap();
+        assert_eq!(failed.state, UploadState::Failed);
+        assert_eq!(failed.error_kind.as_deref(), Some("rejected"));
+
+        // Finished uploads cannot finish or be cancelled again
+        assert!(queue.finish(1, success()).is_none());
+        assert!(queue.cancel(2).is_none());
+    }
+
+    #[test]
+    fn test_cancel_returns_handle_and_ignores_late_result() {
+        let mut queue: UploadQueue<&'static str> = UploadQueue::new(1);
+        queue.enqueue(request(1));
+        queue.enqueue(request(2));
+        queue.start_next();
+
+        assert!(queue.attach(1, "task").is_none());
+
+        let (entry, handle) = queue.cancel(1).unwrap();
+        assert_eq!(entry.state, UploadState::Cancelled);
+        assert_eq!(handle, Some("task"));
+        assert!(queue.finish(1, success()).is_none());
+
+        // Cancelling a queued upload needs no task, and the freed slot goes to nobody
+        let (_, handle) = queue.cancel(2).unwrap();
+        assert!(handle.is_none());
+        assert!(queue.start_next().is_empty());
+    }
+
+    #[test]
+    fn test_attach_to_finished_upload_returns_handle() {
+        let mut queue: UploadQueue<&'static str> = UploadQueue::new(1);
+        queue.enqueue(request(1));
+        queue.start_next();
+        queue.finish(1, success());
+
+        assert_eq!(queue.attach(1, "task"), Some("task"));
+    }
+
+    #[test]
+    fn test_finished_uploads_are_pruned() {
+        let mut queue: UploadQueue<()> = UploadQueue::new(1);
+
+        for item_id in 0..(MAX_FINISHED_UPLOADS as u64 + 5) {
+            let entry = queue.enqueue(request(item_id));
+            queue.cancel(entry.upload_id);
+        }
+
+        queue.enqueue(request(1000));
+
+        let uploads = queue.list();
+        assert_eq!(uploads.len(), MAX_FINISHED_UPLOADS + 1);
+        assert_eq!(uploads.last().unwrap().state, UploadState::Queued);
+    }
+}
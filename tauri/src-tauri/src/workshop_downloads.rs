@@ -0,0 +1,318 @@
+//! Downloads of Workshop packages.
+//!
+//! This module provides functionality to:
+//! - Download a Workshop item and wait for Steam to report the result of the download
+//! - Report the download progress of the item while waiting
+//! - Cancel waiting for a download and give up after a configurable timeout
+//!
+//! Steam reports finished downloads through the `DownloadItemResult` callback, which is
+//! forwarded to `download_finished`. The downloaded bytes are only available on request, so
+//! they are checked at a fixed interval while waiting.
+
+use crate::steam_backend::SteamBackend;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default time to wait for a download to finish
+pub const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Interval of download progress checks
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Download progress of a Workshop item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DownloadProgress {
+    pub item_id: u64,
+    pub downloaded: u64,
+    pub total: u64,
+}
+
+/// Error waiting for a Workshop package
+#[derive(Debug, PartialEq, Eq)]
+pub enum DownloadError {
+    StartFailed,
+    Failed(String),
+    /// The item was downloaded but does not contain a package
+    MissingPackage,
+    TimedOut,
+    Cancelled,
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::StartFailed => write!(f, "Failed to start download of Workshop item"),
+            DownloadError::Failed(e) => write!(f, "{}", e),
+            DownloadError::MissingPackage => write!(f, "Workshop item does not contain a package"),
+            DownloadError::TimedOut => write!(f, "Timed out waiting for Workshop item to download"),
+            DownloadError::Cancelled => write!(f, "Workshop item download was cancelled"),
+        }
+    }
+}
+
+/// Event received while waiting for a download
+enum WaitEvent {
+    Finished(Result<(), String>),
+    Cancelled,
+}
+
+/// Id of a waiting command and the sender of its events
+type Waiter = (u64, mpsc::Sender<WaitEvent>);
+
+/// Downloads that are being waited for
+pub struct WorkshopDownloads {
+    timeout: Mutex<Duration>,
+    progress_interval: Duration,
+    next_waiter_id: Mutex<u64>,
+    waiters: Mutex<HashMap<u64, Vec<Waiter>>>,
+}
+
+impl Default for WorkshopDownloads {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOWNLOAD_TIMEOUT, PROGRESS_INTERVAL)
+    }
+}
+
+impl WorkshopDownloads {
+    pub fn new(timeout: Duration, progress_interval: Duration) -> Self {
+        Self {
+            timeout: Mutex::new(timeout),
+            progress_interval,
+            next_waiter_id: Mutex::new(0),
+            waiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the time to wait for downloads that start from now on
+    pub fn set_timeout(&self, timeout: Duration) {
+        if let Ok(mut current) = self.timeout.lock() {
+            *current = timeout;
+        }
+    }
+
+    /// Get the package file of a Workshop item, downloading the item if it is not installed
+    /// Blocks until the download finishes, fails, times out or is cancelled
+    pub fn wait_for_package(
+        &self,
+        steam: &dyn SteamBackend,
+        item_id: u64,
+        on_progress: &mut dyn FnMut(DownloadProgress),
+    ) -> Result<PathBuf, DownloadError> {
+        if let Some(path) = installed_package(steam, item_id) {
+            return Ok(path);
+        }
+
+        log::info!("Workshop item not installed, attempting to download: {}", item_id);
+
+        // Register before starting the download, so its result cannot be missed
+        let (waiter_id, events) = self.add_waiter(item_id);
+        let result = self.wait(steam, item_id, &events, on_progress);
+        self.remove_waiter(item_id, waiter_id);
+
+        result
+    }
+
+    fn wait(
+        &self,
+        steam: &dyn SteamBackend,
+        item_id: u64,
+        events: &mpsc::Receiver<WaitEvent>,
+        on_progress: &mut dyn FnMut(DownloadProgress),
+    ) -> Result<PathBuf, DownloadError> {
+        if !steam.download_item(item_id, true) {
+            return Err(DownloadError::StartFailed);
+        }
+
+        let timeout = self.timeout.lock().map(|timeout| *timeout).unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT);
+        let timeout_at = Instant::now() + timeout;
+        let mut last_progress = None;
+
+        loop {
+            let remaining = timeout_at.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Err(DownloadError::TimedOut);
+            }
+
+            match events.recv_timeout(remaining.min(self.progress_interval)) {
+                Ok(WaitEvent::Finished(Ok(()))) => {
+                    return installed_package(steam, item_id).ok_or(DownloadError::MissingPackage);
+                }
+                Ok(WaitEvent::Finished(Err(e))) => return Err(DownloadError::Failed(e)),
+                Ok(WaitEvent::Cancelled) => return Err(DownloadError::Cancelled),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Some((downloaded, total)) = steam.item_download_info(item_id) {
+                        let progress = DownloadProgress {
+                            item_id,
+                            downloaded,
+                            total,
+                        };
+
+                        if last_progress != Some(progress) {
+                            on_progress(progress);
+                            last_progress = Some(progress);
+                        }
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(DownloadError::Cancelled),
+            }
+        }
+    }
+
+    /// Handle a finished download reported by Steam
+    pub fn download_finished(&self, item_id: u64, result: &Result<(), String>) {
+        self.notify(item_id, || WaitEvent::Finished(result.clone()));
+    }
+
+    /// Stop waiting for the download of an item
+    /// Steam keeps downloading the item in the background
+    /// Returns false if nothing waits for the item
+    pub fn cancel(&self, item_id: u64) -> bool {
+        self.notify(item_id, || WaitEvent::Cancelled)
+    }
+
+    fn notify(&self, item_id: u64, event: impl Fn() -> WaitEvent) -> bool {
+        let Ok(waiters) = self.waiters.lock() else {
+            return false;
+        };
+
+        let Some(item_waiters) = waiters.get(&item_id) else {
+            return false;
+        };
+
+        for (_, sender) in item_waiters {
+            let _ = sender.send(event());
+        }
+
+        true
+    }
+
+    fn add_waiter(&self, item_id: u64) -> (u64, mpsc::Receiver<WaitEvent>) {
+        let (tx, rx) = mpsc::channel();
+
+        let waiter_id = match self.next_waiter_id.lock() {
+            Ok(mut next_waiter_id) => {
+                *next_waiter_id += 1;
+                *next_waiter_id
+            }
+            Err(_) => 0,
+        };
+
+        if let Ok(mut waiters) = self.waiters.lock() {
+            waiters.entry(item_id).or_default().push((waiter_id, tx));
+        }
+
+        (waiter_id, rx)
+    }
+
+    fn remove_waiter(&self, item_id: u64, waiter_id: u64) {
+        if let Ok(mut waiters) = self.waiters.lock() {
+            if let Some(item_waiters) = waiters.get_mut(&item_id) {
+                item_waiters.retain(|(id, _)| *id != waiter_id);
+
+                if item_waiters.is_empty() {
+                    waiters.remove(&item_id);
+                }
+            }
+        }
+    }
+}
+
+/// Get the package file of an installed Workshop item
+fn installed_package(steam: &dyn SteamBackend, item_id: u64) -> Option<PathBuf> {
+    let info = steam.item_install_info(item_id)?;
+    let path = PathBuf::from(info.folder).join("package.siq");
+
+    path.exists().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_steam_backend::FakeSteamBackend;
+    use std::sync::Arc;
+
+    fn item_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("sigame-download-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("package.siq"), b"package").unwrap();
+        folder
+    }
+
+    /// Steam backend forwarding finished downloads to the waiters
+    fn connect(steam: FakeSteamBackend, downloads: &Arc<WorkshopDownloads>) -> FakeSteamBackend {
+        let downloads = downloads.clone();
+        steam.add_download_listener(Box::new(move |item_id, result| downloads.download_finished(item_id, &result)));
+        steam
+    }
+
+    #[test]
+    fn test_wait_for_download_reports_progress() {
+        let folder = item_folder("slow");
+        let downloads = Arc::new(WorkshopDownloads::new(Duration::from_secs(60), Duration::from_millis(1)));
+
+        let steam = connect(
+            FakeSteamBackend::new()
+                .with_slow_download(1, folder.to_str().unwrap(), 3)
+                .with_broken_download(2, 2, "k_EResultDiskFull")
+                .with_failing_download(3),
+            &downloads,
+        );
+
+        let mut progress = Vec::new();
+        let path = downloads.wait_for_package(&steam, 1, &mut |update| progress.push(update)).unwrap();
+
+        assert_eq!(path, folder.join("package.siq"));
+        assert!(steam.download_started(1));
+        assert_eq!(
+            progress,
+            vec![
+                DownloadProgress { item_id: 1, downloaded: 1024, total: 3072 },
+                DownloadProgress { item_id: 1, downloaded: 2048, total: 3072 },
+                DownloadProgress { item_id: 1, downloaded: 3072, total: 3072 },
+            ]
+        );
+
+        // Installed items are not downloaded again
+        assert_eq!(downloads.wait_for_package(&steam, 1, &mut |_| {}).unwrap(), path);
+
+        assert_eq!(
+            downloads.wait_for_package(&steam, 2, &mut |_| {}),
+            Err(DownloadError::Failed("k_EResultDiskFull".to_string()))
+        );
+        assert_eq!(downloads.wait_for_package(&steam, 3, &mut |_| {}), Err(DownloadError::StartFailed));
+        assert!(!downloads.cancel(1));
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn test_wait_for_download_times_out_or_is_cancelled() {
+        let downloads = Arc::new(WorkshopDownloads::new(Duration::from_millis(20), Duration::from_millis(1)));
+        let steam = Arc::new(connect(FakeSteamBackend::new().with_slow_download(1, "/workshop/1", u32::MAX), &downloads));
+
+        assert_eq!(downloads.wait_for_package(steam.as_ref(), 1, &mut |_| {}), Err(DownloadError::TimedOut));
+
+        downloads.set_timeout(Duration::from_secs(60));
+
+        let (started_tx, started_rx) = mpsc::channel();
+        let waiter = {
+            let (downloads, steam) = (downloads.clone(), steam.clone());
+
+            std::thread::spawn(move || {
+                downloads.wait_for_package(steam.as_ref(), 1, &mut |_| {
+                    let _ = started_tx.send(());
+                })
+            })
+        };
+
+        started_rx.recv().unwrap();
+        assert!(downloads.cancel(1));
+        assert_eq!(waiter.join().unwrap(), Err(DownloadError::Cancelled));
+        assert!(!downloads.cancel(1));
+    }
+}
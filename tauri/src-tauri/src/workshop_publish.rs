@@ -0,0 +1,353 @@
+//! Publishing packages to the Steam Workshop.
+//!
+//! This module provides functionality to:
+//! - Validate item properties against the Workshop limits
+//! - Check that the uploaded file is a readable package
+//! - Create Workshop items and submit their properties and content
+//!
+//! Steam uploads the content of a folder, so the package is copied into a staging folder
+//! as `package.siq` (the name installed items are read from) for the time of the upload.
+
+use crate::siq::{SiqError, SiqPackage};
+use crate::steam_backend::{SteamBackend, UpdateProgress, WorkshopItemUpdate, WorkshopVisibility};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Maximum length of an item title in bytes
+pub const MAX_TITLE_LENGTH: usize = 128;
+
+/// Maximum length of an item description in bytes
+pub const MAX_DESCRIPTION_LENGTH: usize = 8000;
+
+/// Maximum length of an item tag in bytes
+pub const MAX_TAG_LENGTH: usize = 255;
+
+/// Maximum size of a preview image in bytes
+pub const MAX_PREVIEW_SIZE: u64 = 1024 * 1024;
+
+/// Properties of a published item
+/// Properties that are not set keep their current values when an item is updated
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PublishOptions {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Path of a preview image (JPEG, PNG or GIF)
+    pub preview_path: Option<String>,
+    pub visibility: Option<WorkshopVisibility>,
+    pub change_note: Option<String>,
+}
+
+/// Published Workshop item
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishResult {
+    pub item_id: u64,
+    pub url: String,
+    /// The item stays hidden until the user accepts the Workshop legal agreement
+    pub needs_to_accept_agreement: bool,
+}
+
+/// Error publishing a package
+#[derive(Debug)]
+pub enum PublishError {
+    InvalidOptions(String),
+    Package(SiqError),
+    IoError(String),
+    Steam(String),
+    /// The item was created but its first update failed
+    /// It can be completed by updating the item
+    Incomplete { item_id: u64, error: String },
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::InvalidOptions(e) => write!(f, "Invalid item properties: {}", e),
+            PublishError::Package(e) => write!(f, "Invalid package: {}", e),
+            PublishError::IoError(e) => write!(f, "IO error: {}", e),
+            PublishError::Steam(e) => write!(f, "Steam error: {}", e),
+            PublishError::Incomplete { item_id, error } => {
+                write!(f, "Workshop item {} was created but could not be updated: {}", item_id, error)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for PublishError {
+    fn from(e: std::io::Error) -> Self {
+        PublishError::IoError(e.to_string())
+    }
+}
+
+/// Get the Steam Community page of a Workshop item
+pub fn item_url(item_id: u64) -> String {
+    format!("https://steamcommunity.com/sharedfiles/filedetails/?id={}", item_id)
+}
+
+/// Check item properties against the Workshop limits
+fn validate_options(options: &PublishOptions, title_required: bool) -> Result<(), PublishError> {
+    let invalid = |message: String| Err(PublishError::InvalidOptions(message));
+
+    match options.title.as_deref().map(str::trim) {
+        Some("") | None if title_required => return invalid("title is required".to_string()),
+        Some("") => return invalid("title cannot be empty".to_string()),
+        Some(title) if title.len() > MAX_TITLE_LENGTH => {
+            return invalid(format!("title is longer than {} bytes", MAX_TITLE_LENGTH));
+        }
+        _ => {}
+    }
+
+    if options.description.as_ref().is_some_and(|description| description.len() > MAX_DESCRIPTION_LENGTH) {
+        return invalid(format!("description is longer than {} bytes", MAX_DESCRIPTION_LENGTH));
+    }
+
+    for tag in options.tags.iter().flatten() {
+        if tag.trim().is_empty() {
+            return invalid("tags cannot be empty".to_string());
+        }
+
+        if tag.len() > MAX_TAG_LENGTH {
+            return invalid(format!("tag '{}' is longer than {} bytes", tag, MAX_TAG_LENGTH));
+        }
+
+        if tag.contains(',') || tag.chars().any(char::is_control) {
+            return invalid(format!("tag '{}' contains invalid characters", tag));
+        }
+    }
+
+    if let Some(preview_path) = &options.preview_path {
+        let size = std::fs::metadata(preview_path)
+            .map_err(|e| PublishError::InvalidOptions(format!("cannot read preview image {}: {}", preview_path, e)))?
+            .len();
+
+        if size > MAX_PREVIEW_SIZE {
+            return invalid(format!("preview image is larger than {} bytes", MAX_PREVIEW_SIZE));
+        }
+    }
+
+    Ok(())
+}
+
+/// Publish a package as a new Workshop item
+/// `staging_root` is the folder where the package is staged while it is uploaded
+pub fn publish(
+    steam: &dyn SteamBackend,
+    staging_root: &Path,
+    package_path: &Path,
+    options: &PublishOptions,
+    on_progress: &mut dyn FnMut(u64, UpdateProgress),
+) -> Result<PublishResult, PublishError> {
+    validate_options(options, true)?;
+    SiqPackage::open(package_path).map_err(PublishError::Package)?;
+
+    let item_id = steam.create_item().map_err(PublishError::Steam)?;
+
+    log::info!("Created Workshop item {}", item_id);
+
+    submit(steam, staging_root, item_id, Some(package_path), options, on_progress).map_err(|e| {
+        PublishError::Incomplete {
+            item_id,
+            error: e.to_string(),
+        }
+    })
+}
+
+/// Update an existing Workshop item
+/// The content is only replaced when a package is given
+pub fn update(
+    steam: &dyn SteamBackend,
+    staging_root: &Path,
+    item_id: u64,
+    package_path: Option<&Path>,
+    options: &PublishOptions,
+    on_progress: &mut dyn FnMut(u64, UpdateProgress),
+) -> Result<PublishResult, PublishError> {
+    validate_options(options, false)?;
+
+    if let Some(package_path) = package_path {
+        SiqPackage::open(package_path).map_err(PublishError::Package)?;
+    }
+
+    submit(steam, staging_root, item_id, package_path, options, on_progress)
+}
+
+fn submit(
+    steam: &dyn SteamBackend,
+    staging_root: &Path,
+    item_id: u64,
+    package_path: Option<&Path>,
+    options: &PublishOptions,
+    on_progress: &mut dyn FnMut(u64, UpdateProgress),
+) -> Result<PublishResult, PublishError> {
+    let content_folder = match package_path {
+        Some(package_path) => Some(stage_package(staging_root, item_id, package_path)?),
+        None => None,
+    };
+
+    let update = WorkshopItemUpdate {
+        title: options.title.as_deref().map(str::trim).map(String::from),
+        description: options.description.clone(),
+        tags: options
+            .tags
+            .as_ref()
+            .map(|tags| tags.iter().map(|tag| tag.trim().to_string()).collect()),
+        preview_path: options.preview_path.as_ref().map(PathBuf::from),
+        visibility: options.visibility,
+        content_folder: content_folder.clone(),
+        change_note: options.change_note.clone(),
+    };
+
+    let result = steam.submit_item_update(item_id, &update, &mut |progress| on_progress(item_id, progress));
+
+    if let Some(content_folder) = content_folder {
+        if let Err(e) = std::fs::remove_dir_all(&content_folder) {
+            log::warn!("Failed to remove staging folder {}: {}", content_folder.display(), e);
+        }
+    }
+
+    let needs_to_accept_agreement = result.map_err(PublishError::Steam)?;
+
+    log::info!("Workshop item {} updated", item_id);
+
+    Ok(PublishResult {
+        item_id,
+        url: item_url(item_id),
+        needs_to_accept_agreement,
+    })
+}
+
+/// Copy a package into an empty staging folder of the item
+fn stage_package(staging_root: &Path, item_id: u64, package_path: &Path) -> Result<PathBuf, PublishError> {
+    let folder = staging_root.join(item_id.to_string());
+
+    if folder.exists() {
+        std::fs::remove_dir_all(&folder)?;
+    }
+
+    std::fs::create_dir_all(&folder)?;
+    std::fs::copy(package_path, folder.join("package.siq"))?;
+
+    Ok(folder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_steam_backend::FakeSteamBackend;
+    use crate::steam_backend::UpdateStage;
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sigame-publish-{}-{}", name, std::process::id()))
+    }
+
+    fn write_package(path: &Path) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        writer
+            .start_file("content.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .write_all(br#"<package name="P" version="5"><rounds><round name="R"><themes /></round></rounds></package>"#)
+            .unwrap();
+        writer.finish().unwrap();
+    }
+
+    fn options(title: &str) -> PublishOptions {
+        PublishOptions {
+            title: Some(title.to_string()),
+            tags: Some(vec![" History ".to_string(), "Music".to_string()]),
+            visibility: Some(WorkshopVisibility::FriendsOnly),
+            change_note: Some("First version".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_publish_and_update_package() {
+        let package_path = temp_path("package.siq");
+        let staging_root = temp_path("staging");
+        write_package(&package_path);
+
+        let steam = FakeSteamBackend::new();
+        let mut progress = Vec::new();
+
+        let published = publish(&steam, &staging_root, &package_path, &options(" My package "), &mut |item_id, update| {
+            progress.push((item_id, update))
+        })
+        .unwrap();
+
+        assert_eq!(published.url, item_url(published.item_id));
+        assert!(!published.needs_to_accept_agreement);
+        assert!(progress.iter().all(|(item_id, _)| *item_id == published.item_id));
+        assert!(progress.iter().any(|(_, update)| update.stage == UpdateStage::UploadingContent));
+
+        let updated = update(
+            &steam,
+            &staging_root,
+            published.item_id,
+            None,
+            &PublishOptions {
+                description: Some("Updated".to_string()),
+                ..Default::default()
+            },
+            &mut |_, _| {},
+        )
+        .unwrap();
+
+        assert_eq!(updated.item_id, published.item_id);
+
+        let submitted = steam.submitted_updates();
+        assert_eq!(submitted.len(), 2);
+
+        let (item_id, first_update, content) = &submitted[0];
+        assert_eq!(*item_id, published.item_id);
+        assert_eq!(first_update.title.as_deref(), Some("My package"));
+        assert_eq!(first_update.tags, Some(vec!["History".to_string(), "Music".to_string()]));
+        assert_eq!(first_update.visibility, Some(WorkshopVisibility::FriendsOnly));
+        assert_eq!(first_update.change_note.as_deref(), Some("First version"));
+        assert_eq!(content.as_deref(), Some(std::fs::read(&package_path).unwrap().as_slice()));
+
+        let (_, second_update, content) = &submitted[1];
+        assert_eq!(second_update.title, None);
+        assert_eq!(second_update.description.as_deref(), Some("Updated"));
+        assert!(content.is_none());
+
+        // Staged packages are removed after the upload
+        assert!(!staging_root.join(published.item_id.to_string()).exists());
+
+        std::fs::remove_file(&package_path).unwrap();
+        let _ = std::fs::remove_dir_all(&staging_root);
+    }
+
+    #[test]
+    fn test_publish_errors() {
+        let package_path = temp_path("errors.siq");
+        let staging_root = temp_path("errors-staging");
+        std::fs::write(&package_path, b"not a package").unwrap();
+
+        let steam = FakeSteamBackend::new();
+
+        let result = publish(&steam, &staging_root, &package_path, &options("Package"), &mut |_, _| {});
+        assert!(matches!(result, Err(PublishError::Package(_))));
+
+        let result = publish(&steam, &staging_root, &package_path, &options(" "), &mut |_, _| {});
+        assert!(matches!(result, Err(PublishError::InvalidOptions(_))));
+
+        let mut long_tag = options("Package");
+        long_tag.tags = Some(vec!["a".repeat(MAX_TAG_LENGTH + 1)]);
+        let result = update(&steam, &staging_root, 1, None, &long_tag, &mut |_, _| {});
+        assert!(matches!(result, Err(PublishError::InvalidOptions(_))));
+
+        write_package(&package_path);
+        let steam = steam.with_submit_error("k_EResultLimitExceeded");
+
+        let result = publish(&steam, &staging_root, &package_path, &options("Package"), &mut |_, _| {});
+        assert!(matches!(result, Err(PublishError::Incomplete { .. })));
+        assert!(steam.submitted_updates().is_empty());
+
+        std::fs::remove_file(&package_path).unwrap();
+        let _ = std::fs::remove_dir_all(&staging_root);
+    }
+}
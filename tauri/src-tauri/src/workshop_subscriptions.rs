@@ -0,0 +1,125 @@
+//! Subscriptions to Workshop items.
+//!
+//! This module provides functionality to:
+//! - Subscribe to Workshop items and start downloading them right away
+//! - Track newly subscribed items until their download finishes
+//!
+//! Steam reports every finished download, including packages downloaded for a game;
+//! only the items subscribed to from the shell are reported as newly installed.
+
+use crate::steam_backend::SteamBackend;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Newly subscribed Workshop items that are not installed yet
+#[derive(Default)]
+pub struct WorkshopSubscriptions {
+    pending: Mutex<HashSet<u64>>,
+}
+
+impl WorkshopSubscriptions {
+    /// Subscribe to a Workshop item and start downloading it
+    /// Returns true if the item is already installed
+    pub fn subscribe(&self, steam: &dyn SteamBackend, item_id: u64) -> Result<bool, String> {
+        // Track the item before subscribing, as Steam may report the finished download right away
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(item_id);
+        }
+
+        if let Err(e) = steam.subscribe_item(item_id) {
+            self.forget(item_id);
+            return Err(e);
+        }
+
+        if steam.item_install_info(item_id).is_some() {
+            self.forget(item_id);
+            return Ok(true);
+        }
+
+        if !steam.download_item(item_id, true) {
+            // Steam still downloads subscribed items in the background
+            log::warn!("Failed to start download of subscribed Workshop item {}", item_id);
+        }
+
+        Ok(false)
+    }
+
+    /// Unsubscribe from a Workshop item
+    pub fn unsubscribe(&self, steam: &dyn SteamBackend, item_id: u64) -> Result<(), String> {
+        steam.unsubscribe_item(item_id)?;
+        self.forget(item_id);
+        Ok(())
+    }
+
+    /// Stop tracking a Workshop item
+    fn forget(&self, item_id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&item_id);
+        }
+    }
+
+    /// Handle a finished download
+    /// Returns true if a newly subscribed item has been installed
+    pub fn download_finished(&self, item_id: u64, result: &Result<(), String>) -> bool {
+        let Ok(mut pending) = self.pending.lock() else {
+            return false;
+        };
+
+        match result {
+            Ok(()) => pending.remove(&item_id),
+            Err(e) => {
+                // Steam retries failed downloads of subscribed items, so keep waiting for the item
+                if pending.contains(&item_id) {
+                    log::warn!("Download of subscribed Workshop item {} failed: {}", item_id, e);
+                }
+
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_steam_backend::{item_details, FakeSteamBackend};
+
+    #[test]
+    fn test_subscribed_item_is_reported_once_installed() {
+        let steam = FakeSteamBackend::new()
+            .with_workshop_item(item_details(1, "New"))
+            .with_workshop_item(item_details(2, "Installed"))
+            .with_installed_item(2, "/workshop/2", 10);
+
+        let subscriptions = WorkshopSubscriptions::default();
+
+        assert!(!subscriptions.subscribe(&steam, 1).unwrap());
+        assert!(subscriptions.subscribe(&steam, 2).unwrap());
+        assert!(steam.download_started(1));
+        assert!(!steam.download_started(2));
+        assert_eq!(steam.subscribed_items(), vec![1, 2]);
+
+        // Downloads that were not started by a subscription are ignored
+        assert!(!subscriptions.download_finished(2, &Ok(())));
+        assert!(!subscriptions.download_finished(1, &Err("Timeout".to_string())));
+        assert!(subscriptions.download_finished(1, &Ok(())));
+        assert!(!subscriptions.download_finished(1, &Ok(())));
+
+        // Failed subscriptions are not tracked
+        assert!(subscriptions.subscribe(&FakeSteamBackend::new(), 3).is_err());
+        assert!(!subscriptions.download_finished(3, &Ok(())));
+    }
+
+    #[test]
+    fn test_unsubscribe_stops_tracking_item() {
+        let steam = FakeSteamBackend::new().with_workshop_item(item_details(1, "New"));
+        let subscriptions = WorkshopSubscriptions::default();
+
+        subscriptions.subscribe(&steam, 1).unwrap();
+        subscriptions.unsubscribe(&steam, 1).unwrap();
+
+        assert!(steam.subscribed_items().is_empty());
+        assert!(!subscriptions.download_finished(1, &Ok(())));
+        assert!(subscriptions.unsubscribe(&steam, 1).is_err());
+    }
+}
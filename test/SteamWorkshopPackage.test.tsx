@@ -0,0 +1,71 @@
+import * as React from 'react';
+import { renderToStaticMarkup } from 'react-dom/server';
+import SIStoragePackage from '../src/components/panels/SIStoragePackage/SIStoragePackage';
+import SIStorageInfo from '../src/client/contracts/SIStorageInfo';
+import { workshopItemToPackage } from '../src/host/SteamWorkshopStorageClient';
+
+const steamStorage: SIStorageInfo = {
+	name: 'Steam Workshop',
+	uri: 'https://steamcommunity.com/app/3553500/workshop',
+	id: 'SteamWorkshop',
+	serviceUri: '',
+	randomPackagesSupported: false,
+	identifiersSupported: true,
+	maximumPageSize: 20,
+	packageProperties: [],
+	facets: [],
+	limitedApi: true,
+	packageDetailsSupported: true,
+};
+
+const workshopItem = {
+	id: 42,
+	title: 'Workshop package',
+	created_time: 1700000000,
+	preview_url: '',
+	file_size: 1024,
+	subscriptions: 3,
+	score: 0.5,
+};
+
+const statistics = {
+	rounds: [{ name: 'Round 1', theme_names: ['Theme 1', 'Theme 2'] }],
+	question_count: 10,
+	content_type_statistic: { text: 8, image: 2 },
+	media_duration: 95,
+};
+
+const render = (item: any, storage: SIStorageInfo) => renderToStaticMarkup(<SIStoragePackage
+	package={workshopItemToPackage(item)}
+	authors={{}}
+	publishers={{}}
+	restrictions={{}}
+	tags={{}}
+	culture='en'
+	storage={storage}
+	onSelect={() => {}}
+/>);
+
+describe('Steam Workshop package', () => {
+	it('maps Workshop item statistics', () => {
+		const pack: any = workshopItemToPackage({ ...workshopItem, statistics });
+
+		expect(pack.questionCount).toBe(10);
+		expect(pack.mediaDuration).toBe(95);
+		expect(pack.rounds).toEqual([{ name: 'Round 1', themeNames: ['Theme 1', 'Theme 2'] }]);
+	});
+
+	it('renders details of a Workshop item with statistics', () => {
+		expect(render({ ...workshopItem, statistics }, steamStorage)).toContain('packageDetails');
+	});
+
+	it('does not render details of a Workshop item without statistics', () => {
+		expect(render(workshopItem, steamStorage)).not.toContain('packageDetails');
+	});
+
+	it('does not render details for storages with limited API', () => {
+		const storage = { ...steamStorage, packageDetailsSupported: false };
+
+		expect(render({ ...workshopItem, statistics }, storage)).not.toContain('packageDetails');
+	});
+});